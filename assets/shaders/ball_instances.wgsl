@@ -0,0 +1,44 @@
+// Draws every ball in one go. The mesh is a row of quads, one for each ball, whose corners are
+// placed around the ball it's for.
+
+#import bevy_sprite::mesh2d_functions::mesh2d_position_world_to_clip
+
+struct BallInstance {
+    colour: vec4<f32>,
+    position: vec2<f32>,
+    radius: f32,
+}
+
+@group(2) @binding(0) var<storage, read> instances: array<BallInstance>;
+
+struct Vertex {
+    @builtin(vertex_index) index: u32,
+    // A corner of the quad, from -1 to 1 on each axis.
+    @location(0) position: vec3<f32>,
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) corner: vec2<f32>,
+    @location(1) colour: vec4<f32>,
+}
+
+@vertex
+fn vertex(vertex: Vertex) -> VertexOutput {
+    let ball = instances[vertex.index / 4u];
+    let world_position = vec4<f32>(ball.position + vertex.position.xy * ball.radius, 0.0, 1.0);
+    var out: VertexOutput;
+    out.clip_position = mesh2d_position_world_to_clip(world_position);
+    out.corner = vertex.position.xy;
+    out.colour = ball.colour;
+    return out;
+}
+
+@fragment
+fn fragment(in: VertexOutput) -> @location(0) vec4<f32> {
+    // Rounds the quad off into a circle.
+    if length(in.corner) > 1.0 {
+        discard;
+    }
+    return in.colour;
+}
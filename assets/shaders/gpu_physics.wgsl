@@ -0,0 +1,113 @@
+// Moves and collides the balls of the `--gpu-physics` mode. Each ball is a vec4, with its
+// position in xy and its velocity in zw.
+//
+// Every step runs `clear_grid`, `fill_grid` and `move_balls`, after which the new states are
+// copied from `balls_out` back into `balls_in`.
+
+struct Params {
+    gravity: vec2<f32>,
+    delta: f32,
+    cage_radius: f32,
+    ball_radius: f32,
+    restitution: f32,
+    count: u32,
+    // Cells along each side of the square grid around the cage.
+    grid_size: u32,
+    cell_size: f32,
+}
+
+// Balls past this many in one cell are left out of it, and so missed by the others.
+const CELL_CAPACITY: u32 = 8u;
+
+@group(0) @binding(0) var<uniform> params: Params;
+@group(0) @binding(1) var<storage, read> balls_in: array<vec4<f32>>;
+@group(0) @binding(2) var<storage, read_write> balls_out: array<vec4<f32>>;
+@group(0) @binding(3) var<storage, read_write> cell_counts: array<atomic<u32>>;
+@group(0) @binding(4) var<storage, read_write> cell_balls: array<u32>;
+
+fn cell_coords(position: vec2<f32>) -> vec2<i32> {
+    let coords = vec2<i32>(floor((position + params.cage_radius) / params.cell_size));
+    return clamp(coords, vec2<i32>(0), vec2<i32>(i32(params.grid_size) - 1));
+}
+
+fn cell_index(coords: vec2<i32>) -> u32 {
+    return u32(coords.y) * params.grid_size + u32(coords.x);
+}
+
+@compute @workgroup_size(64)
+fn clear_grid(@builtin(global_invocation_id) id: vec3<u32>) {
+    if id.x < params.grid_size * params.grid_size {
+        atomicStore(&cell_counts[id.x], 0u);
+    }
+}
+
+@compute @workgroup_size(64)
+fn fill_grid(@builtin(global_invocation_id) id: vec3<u32>) {
+    if id.x >= params.count {
+        return;
+    }
+    let cell = cell_index(cell_coords(balls_in[id.x].xy));
+    let slot = atomicAdd(&cell_counts[cell], 1u);
+    if slot < CELL_CAPACITY {
+        cell_balls[cell * CELL_CAPACITY + slot] = id.x;
+    }
+}
+
+@compute @workgroup_size(64)
+fn move_balls(@builtin(global_invocation_id) id: vec3<u32>) {
+    let index = id.x;
+    if index >= params.count {
+        return;
+    }
+    var position = balls_in[index].xy;
+    var velocity = balls_in[index].zw + params.gravity * params.delta;
+
+    // Each ball only moves itself, against where the others were at the start of the step, so
+    // every ball can be handled at once.
+    let diameter = 2.0 * params.ball_radius;
+    let coords = cell_coords(position);
+    for (var y = -1; y <= 1; y++) {
+        for (var x = -1; x <= 1; x++) {
+            let neighbour = coords + vec2<i32>(x, y);
+            if any(neighbour < vec2<i32>(0)) || any(neighbour >= vec2<i32>(i32(params.grid_size))) {
+                continue;
+            }
+            let cell = cell_index(neighbour);
+            let count = min(atomicLoad(&cell_counts[cell]), CELL_CAPACITY);
+            for (var slot = 0u; slot < count; slot++) {
+                let other = cell_balls[cell * CELL_CAPACITY + slot];
+                if other == index {
+                    continue;
+                }
+                let offset = position - balls_in[other].xy;
+                let separation = length(offset);
+                if separation >= diameter || separation == 0.0 {
+                    continue;
+                }
+                let normal = offset / separation;
+                // Half the overlap, as the other ball moves away by the other half.
+                position += normal * (diameter - separation) * 0.5;
+                let approach = dot(velocity - balls_in[other].zw, normal);
+                if approach < 0.0 {
+                    // Equal masses share the impulse.
+                    velocity -= normal * approach * (1.0 + params.restitution) * 0.5;
+                }
+            }
+        }
+    }
+
+    position += velocity * params.delta;
+
+    let limit = params.cage_radius - params.ball_radius;
+    let reach = length(position);
+    if reach > limit {
+        let normal = position / reach;
+        position = normal * limit;
+        let outwards = dot(velocity, normal);
+        if outwards > 0.0 {
+            velocity -= normal * outwards * (1.0 + params.restitution);
+        }
+    }
+
+    balls_out[index] = vec4<f32>(position, velocity);
+}
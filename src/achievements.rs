@@ -0,0 +1,222 @@
+use std::{fs, io::ErrorKind};
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{Ball, CageCollisionEvent, OtherCollisionEvent, ResetEvent};
+
+const ACHIEVEMENTS_PATH: &str = "achievements.ron";
+const TOAST_BACKGROUND_COLOR: Color = Color::rgba(0.0, 0.0, 0.0, 0.7);
+const TOAST_TEXT_COLOR: Color = Color::rgb(1.0, 0.8, 0.2);
+// In seconds.
+const TOAST_DURATION: f32 = 3.0;
+
+/// A milestone, unlocked once and for good.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Achievement {
+    HundredBounces,
+    TenThousandBounces,
+    FiftyBalls,
+    TwoHundredBalls,
+    SurviveAMinute,
+    SurviveFiveMinutes,
+}
+
+impl Achievement {
+    const ALL: [Achievement; 6] = [
+        Achievement::HundredBounces,
+        Achievement::TenThousandBounces,
+        Achievement::FiftyBalls,
+        Achievement::TwoHundredBalls,
+        Achievement::SurviveAMinute,
+        Achievement::SurviveFiveMinutes,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            Achievement::HundredBounces => "100 bounces",
+            Achievement::TenThousandBounces => "10,000 bounces",
+            Achievement::FiftyBalls => "50 balls at once",
+            Achievement::TwoHundredBalls => "200 balls at once",
+            Achievement::SurviveAMinute => "Survive a minute",
+            Achievement::SurviveFiveMinutes => "Survive five minutes",
+        }
+    }
+
+    fn reached(self, stats: &Stats) -> bool {
+        match self {
+            Achievement::HundredBounces => stats.total_bounces >= 100,
+            Achievement::TenThousandBounces => stats.total_bounces >= 10_000,
+            Achievement::FiftyBalls => stats.max_balls >= 50,
+            Achievement::TwoHundredBalls => stats.max_balls >= 200,
+            Achievement::SurviveAMinute => stats.longest_survival >= 60.0,
+            Achievement::SurviveFiveMinutes => stats.longest_survival >= 300.0,
+        }
+    }
+}
+
+/// Totals and records kept across every run.
+#[derive(Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Stats {
+    /// Collisions of any ball with anything.
+    pub total_bounces: u64,
+    /// The most balls there have been at the same time.
+    pub max_balls: usize,
+    /// The longest a run has gone without being reset, in seconds.
+    pub longest_survival: f32,
+}
+
+/// Saved between runs of the app in [`ACHIEVEMENTS_PATH`].
+#[derive(Resource, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Achievements {
+    pub stats: Stats,
+    pub unlocked: Vec<Achievement>,
+    /// How long the current run has lasted, in seconds.
+    #[serde(skip)]
+    current_run: f32,
+}
+
+#[derive(Event)]
+pub struct AchievementUnlockedEvent(pub Achievement);
+
+/// Holds the achievement notifications, stacked at the top of the screen.
+#[derive(Component)]
+pub struct ToastContainer;
+
+/// An achievement notification, which goes away when the timer runs out.
+#[derive(Component)]
+pub struct Toast(Timer);
+
+/// Restores the stats and achievements saved by an earlier run, if there are any.
+pub fn load_achievements(mut achievements: ResMut<Achievements>) {
+    let contents = match fs::read_to_string(ACHIEVEMENTS_PATH) {
+        Ok(contents) => contents,
+        Err(error) if error.kind() == ErrorKind::NotFound => return,
+        Err(error) => {
+            warn!("Couldn't read {ACHIEVEMENTS_PATH}: {error}");
+            return;
+        }
+    };
+    match ron::from_str(&contents) {
+        Ok(loaded) => *achievements = loaded,
+        Err(error) => warn!("Couldn't parse {ACHIEVEMENTS_PATH}: {error}"),
+    }
+}
+
+/// Saves on every unlock, and whenever a run stops, rather than on every bounce.
+pub fn save_achievements(achievements: Res<Achievements>) {
+    let result = ron::ser::to_string_pretty(&*achievements, Default::default())
+        .map_err(|error| error.to_string())
+        .and_then(|contents| {
+            fs::write(ACHIEVEMENTS_PATH, contents).map_err(|error| error.to_string())
+        });
+    if let Err(error) = result {
+        warn!("Couldn't save {ACHIEVEMENTS_PATH}: {error}");
+    }
+}
+
+pub fn track_stats(
+    mut wall_collision_events: EventReader<CageCollisionEvent>,
+    mut ball_collision_events: EventReader<OtherCollisionEvent>,
+    mut reset_events: EventReader<ResetEvent>,
+    ball_query: Query<(), With<Ball>>,
+    mut achievements: ResMut<Achievements>,
+    time: Res<Time>,
+) {
+    let bounces = wall_collision_events.read().count() + ball_collision_events.read().count();
+    let balls = ball_query.iter().count();
+    if !reset_events.is_empty() {
+        reset_events.clear();
+        achievements.current_run = 0.0;
+    }
+    achievements.current_run += time.delta_seconds();
+    let current_run = achievements.current_run;
+
+    let stats = &mut achievements.stats;
+    stats.total_bounces += bounces as u64;
+    stats.max_balls = stats.max_balls.max(balls);
+    stats.longest_survival = stats.longest_survival.max(current_run);
+}
+
+pub fn unlock_achievements(
+    mut achievements: ResMut<Achievements>,
+    mut unlocked_events: EventWriter<AchievementUnlockedEvent>,
+) {
+    for achievement in Achievement::ALL {
+        if achievements.unlocked.contains(&achievement) || !achievement.reached(&achievements.stats)
+        {
+            continue;
+        }
+        achievements.unlocked.push(achievement);
+        unlocked_events.send(AchievementUnlockedEvent(achievement));
+    }
+}
+
+pub fn spawn_toast_container(mut commands: Commands) {
+    commands.spawn((
+        NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                width: Val::Percent(100.0),
+                top: Val::Px(8.0),
+                flex_direction: FlexDirection::Column,
+                align_items: AlignItems::Center,
+                row_gap: Val::Px(4.0),
+                ..Default::default()
+            },
+            ..Default::default()
+        },
+        ToastContainer,
+    ));
+}
+
+pub fn show_toasts(
+    mut unlocked_events: EventReader<AchievementUnlockedEvent>,
+    container_query: Query<Entity, With<ToastContainer>>,
+    mut commands: Commands,
+) {
+    let Ok(container) = container_query.get_single() else {
+        return;
+    };
+    for event in unlocked_events.read() {
+        info!("Achievement unlocked: {}", event.0.name());
+        let toast = commands
+            .spawn((
+                NodeBundle {
+                    style: Style {
+                        padding: UiRect::all(Val::Px(8.0)),
+                        ..Default::default()
+                    },
+                    background_color: TOAST_BACKGROUND_COLOR.into(),
+                    ..Default::default()
+                },
+                Toast(Timer::from_seconds(TOAST_DURATION, TimerMode::Once)),
+            ))
+            .with_children(|parent| {
+                parent.spawn(TextBundle::from_section(
+                    format!("Achievement unlocked: {}", event.0.name()),
+                    TextStyle {
+                        font_size: 20.0,
+                        color: TOAST_TEXT_COLOR,
+                        ..Default::default()
+                    },
+                ));
+            })
+            .id();
+        commands.entity(container).add_child(toast);
+    }
+}
+
+pub fn expire_toasts(
+    mut toast_query: Query<(Entity, &mut Toast)>,
+    mut commands: Commands,
+    time: Res<Time<Real>>,
+) {
+    for (entity, mut toast) in &mut toast_query {
+        if toast.0.tick(time.delta()).finished() {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
@@ -0,0 +1,86 @@
+use bevy::{
+    asset::{io::Reader, AssetLoader, AsyncReadExt, LoadContext},
+    prelude::*,
+    utils::BoxedFuture,
+};
+use serde::Deserialize;
+
+const ARENA_PATHS: [&str; 3] = [
+    "arenas/star.arena.ron",
+    "arenas/funnel.arena.ron",
+    "arenas/l_shape.arena.ron",
+];
+
+/// A custom cage outline, loaded from a `.arena.ron` file.
+#[derive(Asset, TypePath, Deserialize)]
+pub struct Arena {
+    /// In multiples of the cage radius, relative to the cage center. The outline must not cross
+    /// itself, but may be in either winding order.
+    vertices: Vec<(f32, f32)>,
+}
+
+impl Arena {
+    /// The outline in counter-clockwise order.
+    pub fn vertices(&self) -> Vec<Vec2> {
+        let mut vertices: Vec<Vec2> = self
+            .vertices
+            .iter()
+            .map(|&(x, y)| Vec2::new(x, y))
+            .collect();
+        if signed_area(&vertices) < 0.0 {
+            vertices.reverse();
+        }
+        vertices
+    }
+}
+
+/// Positive for counter-clockwise outlines.
+pub fn signed_area(vertices: &[Vec2]) -> f32 {
+    let count = vertices.len();
+    (0..count)
+        .map(|i| vertices[i].perp_dot(vertices[(i + 1) % count]))
+        .sum::<f32>()
+        / 2.0
+}
+
+/// All the arenas that can be cycled through, in order.
+#[derive(Resource)]
+pub struct ArenaHandles(pub Vec<Handle<Arena>>);
+
+#[derive(Default)]
+pub struct ArenaLoader;
+
+impl AssetLoader for ArenaLoader {
+    type Asset = Arena;
+    type Settings = ();
+    type Error = Box<dyn std::error::Error + Send + Sync>;
+
+    fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader,
+        _settings: &'a (),
+        _load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<Arena, Self::Error>> {
+        Box::pin(async move {
+            let mut bytes = Vec::new();
+            reader.read_to_end(&mut bytes).await?;
+            let arena: Arena = ron::de::from_bytes(&bytes)?;
+            if arena.vertices.len() < 3 {
+                return Err("an arena needs at least three vertices".into());
+            }
+            Ok(arena)
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["arena.ron"]
+    }
+}
+
+pub fn load_arenas(mut commands: Commands, asset_server: Res<AssetServer>) {
+    let handles = ARENA_PATHS
+        .iter()
+        .map(|path| asset_server.load(*path))
+        .collect();
+    commands.insert_resource(ArenaHandles(handles));
+}
@@ -0,0 +1,532 @@
+use std::{f32::consts::SQRT_2, fs, io::ErrorKind, time::Duration};
+
+use bevy::{
+    asset::LoadState,
+    audio::{AudioSinkPlayback, Volume},
+    prelude::*,
+    utils::HashMap,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    keybindings::{Action, Actions},
+    kind::BallKind,
+    menu::AppState,
+    tone::{Pad, Tone},
+    BallsMergedEvent, CageCollisionEvent, OtherCollisionEvent, Radius, BALL_RADIUS,
+};
+
+// Playback speed multipliers, which also shift the pitch. Heavy balls thud, bouncy ones click.
+const HEAVY_PITCH: f32 = 0.6;
+const BOUNCY_PITCH: f32 = 1.5;
+const MERGE_SOUND_SPEED: f32 = 0.6;
+// Every sound plays up to this fraction faster or slower, so a stream of hits doesn't sound
+// like the same sample over and over.
+const PITCH_VARIATION: f32 = 0.15;
+// Collisions at this impact speed or faster play at full volume, slower ones more quietly.
+const FULL_VOLUME_IMPACT_SPEED: f32 = 400.0;
+// Quieter collisions than this aren't worth playing at all.
+const MIN_COLLISION_VOLUME: f32 = 0.05;
+// In hertz, for a ball of the size balls spawn at. Smaller balls ring higher, bigger ones lower.
+const TONE_FREQUENCY: f32 = 440.0;
+const MENU_MUSIC_PATH: &str = "sounds/music_menu.ogg";
+const PLAYING_MUSIC_PATH: &str = "sounds/music_playing.ogg";
+const GAME_OVER_MUSIC_PATH: &str = "sounds/music_game_over.ogg";
+// The chords played instead of a music file that can't be loaded, in hertz: calm on the menus,
+// brighter while playing, and minor once a run is over.
+const MENU_CHORD: [f32; 3] = [130.81, 196.0, 329.63];
+const PLAYING_CHORD: [f32; 4] = [146.83, 220.0, 293.66, 369.99];
+const GAME_OVER_CHORD: [f32; 3] = [110.0, 164.81, 261.63];
+
+// Past this many sound effects playing at once, new collisions stay quiet.
+const MAX_VOICES: usize = 16;
+// How long a ball stays quiet after making a sound, so one rattling ball can't hog the voices.
+const SOUND_COOLDOWN: Duration = Duration::from_millis(100);
+// Seconds for music to fade all the way in or out.
+const MUSIC_FADE_TIME: f32 = 1.5;
+// Relative to the working directory.
+const AUDIO_SETTINGS_PATH: &str = "audio_settings.ron";
+
+/// Volume controls and sound choices, saved to [`AUDIO_SETTINGS_PATH`] whenever they change.
+#[derive(Resource, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AudioSettings {
+    /// Multiplier on the volume of every sound, from 0.0 (silent) to 1.0.
+    pub master_volume: f32,
+    /// Whether sound effects play at all.
+    pub sfx_enabled: bool,
+    /// Volume of the background music, on top of `master_volume`.
+    pub music_volume: f32,
+    pub music_enabled: bool,
+    /// Whether collisions play synthesized tones instead of the collision samples. They do
+    /// anyway for a sample that isn't loaded, like one whose file is missing.
+    pub synthesize_sounds: bool,
+    /// Asset path of the sample balls make hitting walls and obstacles. Read at startup.
+    pub wall_sound_path: String,
+    /// Asset path of the sample balls make hitting each other. Read at startup.
+    pub ball_sound_path: String,
+}
+
+impl Default for AudioSettings {
+    fn default() -> Self {
+        Self {
+            master_volume: 1.0,
+            sfx_enabled: true,
+            music_volume: 0.5,
+            music_enabled: true,
+            synthesize_sounds: false,
+            wall_sound_path: "sounds/wall_collision.ogg".to_string(),
+            ball_sound_path: "sounds/ball_collision.ogg".to_string(),
+        }
+    }
+}
+
+/// The background music. Changing it crossfades from the old track to the new one.
+#[derive(Resource)]
+pub struct MusicTrack(pub MusicSource);
+
+/// A piece of music: the file loaded for it, and a chord synthesized instead if the file can't be
+/// loaded.
+#[derive(Clone, PartialEq)]
+pub struct MusicSource {
+    file: Handle<AudioSource>,
+    fallback: Handle<Pad>,
+}
+
+/// The music for each screen, chosen by [`choose_music_track`].
+#[derive(Resource)]
+pub struct MusicTracks {
+    menu: MusicSource,
+    playing: MusicSource,
+    game_over: MusicSource,
+}
+
+/// A looping music track, which fades out and despawns once it's been replaced.
+#[derive(Component)]
+pub struct Music {
+    fading_out: bool,
+}
+
+/// Marks a playing sound effect, so they can be counted against [`MAX_VOICES`].
+#[derive(Component)]
+pub struct Voice;
+
+/// Whether sounds can be played yet. Browsers keep a page quiet until it's been interacted with,
+/// so on the web this waits for the first key press, click or touch.
+#[derive(Resource)]
+pub struct AudioUnlocked(pub bool);
+
+impl Default for AudioUnlocked {
+    fn default() -> Self {
+        Self(!cfg!(target_arch = "wasm32"))
+    }
+}
+
+/// When each ball last made a collision sound, measured from startup.
+#[derive(Resource, Default)]
+pub struct SoundCooldowns(HashMap<Entity, Duration>);
+
+/// What a ball hit, as far as the sound is concerned. Obstacles count as walls.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Surface {
+    Wall,
+    Ball,
+}
+
+/// A sample and the speed it's played back at.
+#[derive(Clone)]
+pub struct Sound {
+    pub source: Handle<AudioSource>,
+    pub speed: f32,
+}
+
+impl Sound {
+    pub fn is_loaded(&self, audio_sources: &Assets<AudioSource>) -> bool {
+        audio_sources.contains(&self.source)
+    }
+}
+
+/// The playback speed of every collision sound a kind of ball makes.
+fn kind_pitch(kind: BallKind) -> f32 {
+    match kind {
+        BallKind::Heavy => HEAVY_PITCH,
+        BallKind::Bouncy => BOUNCY_PITCH,
+        BallKind::Normal | BallKind::Ghost => 1.0,
+    }
+}
+
+/// The sounds collisions make, keyed by the kind of ball and what it hit.
+#[derive(Resource)]
+pub struct CollisionSound {
+    wall_sample: Handle<AudioSource>,
+    table: HashMap<(BallKind, Surface), Sound>,
+}
+
+impl CollisionSound {
+    /// Fills the table with the sample for each surface, played at a different pitch for every
+    /// kind of ball.
+    pub fn new(wall_sample: Handle<AudioSource>, ball_sample: Handle<AudioSource>) -> Self {
+        let surfaces = [(Surface::Wall, &wall_sample), (Surface::Ball, &ball_sample)];
+        let mut table = HashMap::new();
+        for kind in BallKind::ALL {
+            for (surface, sample) in surfaces {
+                let sound = Sound {
+                    source: sample.clone(),
+                    speed: kind_pitch(kind),
+                };
+                table.insert((kind, surface), sound);
+            }
+        }
+        Self { wall_sample, table }
+    }
+
+    pub fn get(&self, kind: BallKind, surface: Surface) -> Sound {
+        self.table
+            .get(&(kind, surface))
+            .cloned()
+            .unwrap_or_else(|| self.with_speed(kind_pitch(kind)))
+    }
+
+    /// The wall sample at `speed`, for sounds that aren't collisions.
+    pub fn with_speed(&self, speed: f32) -> Sound {
+        Sound {
+            source: self.wall_sample.clone(),
+            speed,
+        }
+    }
+}
+
+pub fn play_sound(
+    commands: &mut Commands,
+    sound: &Sound,
+    volume: f32,
+    audio_settings: &AudioSettings,
+) {
+    if !audio_settings.sfx_enabled {
+        return;
+    }
+    commands.spawn((
+        AudioBundle {
+            source: sound.source.clone(),
+            settings: sound_effect_playback(sound.speed, volume, audio_settings),
+        },
+        Voice,
+    ));
+}
+
+/// Plays `tone` like [`play_sound`] would a sample, with its pitch scaled by `speed`.
+pub fn play_tone(
+    commands: &mut Commands,
+    tone: Handle<Tone>,
+    speed: f32,
+    volume: f32,
+    audio_settings: &AudioSettings,
+) {
+    if !audio_settings.sfx_enabled {
+        return;
+    }
+    commands.spawn((
+        AudioSourceBundle {
+            source: tone,
+            settings: sound_effect_playback(speed, volume, audio_settings),
+        },
+        Voice,
+    ));
+}
+
+fn sound_effect_playback(
+    speed: f32,
+    volume: f32,
+    audio_settings: &AudioSettings,
+) -> PlaybackSettings {
+    let variation = 1.0 + (rand::random::<f32>() * 2.0 - 1.0) * PITCH_VARIATION;
+    // auto-despawn the entity when playback finishes
+    PlaybackSettings::DESPAWN
+        .with_speed(speed * variation)
+        .with_volume(Volume::new(volume * audio_settings.master_volume))
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn play_collision_sound(
+    mut commands: Commands,
+    mut wall_collision_events: EventReader<CageCollisionEvent>,
+    mut ball_collision_events: EventReader<OtherCollisionEvent>,
+    ball_query: Query<(&BallKind, &Radius)>,
+    voice_query: Query<(), With<Voice>>,
+    sound: Res<CollisionSound>,
+    audio_sources: Res<Assets<AudioSource>>,
+    mut tones: ResMut<Assets<Tone>>,
+    audio_settings: Res<AudioSettings>,
+    mut cooldowns: ResMut<SoundCooldowns>,
+    time: Res<Time>,
+) {
+    let now = time.elapsed();
+    cooldowns
+        .0
+        .retain(|_, &mut last_sound| now - last_sound < SOUND_COOLDOWN);
+
+    // Play every sound at most once per frame, however many collisions made it, as loud as
+    // the hardest of them and pitched for the size of the ball that made it.
+    let mut hits = HashMap::new();
+    let mut hit = |entity: Entity, kind: BallKind, surface: Surface, impact_speed: f32, radius| {
+        if cooldowns.0.contains_key(&entity) {
+            return;
+        }
+        let fastest = hits
+            .entry((kind, surface))
+            .or_insert((0.0_f32, radius, entity));
+        if impact_speed > fastest.0 {
+            *fastest = (impact_speed, radius, entity);
+        }
+    };
+    for event in wall_collision_events.read() {
+        if let Ok((kind, radius)) = ball_query.get(event.entity) {
+            hit(
+                event.entity,
+                *kind,
+                Surface::Wall,
+                event.impact_speed,
+                radius.0,
+            );
+        }
+    }
+    for event in ball_collision_events.read() {
+        let Ok((kind, radius)) = ball_query.get(event.self_entity) else {
+            continue;
+        };
+        let surface = if ball_query.contains(event.other_entity) {
+            Surface::Ball
+        } else {
+            Surface::Wall
+        };
+        hit(
+            event.self_entity,
+            *kind,
+            surface,
+            event.impact_speed,
+            radius.0,
+        );
+    }
+
+    // The loudest sounds get the free voices.
+    let mut hits: Vec<_> = hits.into_iter().collect();
+    hits.sort_by(|(_, (a, ..)), (_, (b, ..))| b.total_cmp(a));
+    let free_voices = MAX_VOICES.saturating_sub(voice_query.iter().count());
+    for ((kind, surface), (impact_speed, radius, entity)) in hits.into_iter().take(free_voices) {
+        let volume = (impact_speed / FULL_VOLUME_IMPACT_SPEED).min(1.0);
+        // A ball that hit two things this frame only makes one of their sounds.
+        if volume < MIN_COLLISION_VOLUME || cooldowns.0.contains_key(&entity) {
+            continue;
+        }
+        // Only the ball that's heard goes quiet, so a dropped hit doesn't mute its next one.
+        cooldowns.0.insert(entity, now);
+        let sample = sound.get(kind, surface);
+        // A sample that failed to load, or hasn't yet, is stood in for by a tone.
+        if audio_settings.synthesize_sounds || !sample.is_loaded(&audio_sources) {
+            let frequency = TONE_FREQUENCY * (BALL_RADIUS / 2.0) / radius;
+            let tone = tones.add(Tone { frequency });
+            play_tone(&mut commands, tone, sample.speed, volume, &audio_settings);
+        } else {
+            play_sound(&mut commands, &sample, volume, &audio_settings);
+        }
+    }
+}
+
+/// Plays a merge sound, once a frame however many balls merged. The bigger the biggest ball they
+/// made, the deeper it is.
+pub fn play_merge_sound(
+    mut commands: Commands,
+    mut merged_events: EventReader<BallsMergedEvent>,
+    ball_query: Query<&Radius>,
+    sound: Res<CollisionSound>,
+    audio_settings: Res<AudioSettings>,
+) {
+    let biggest = merged_events
+        .read()
+        .filter_map(|event| ball_query.get(event.merged).ok())
+        .map(|radius| radius.0)
+        .max_by(f32::total_cmp);
+    let Some(radius) = biggest else {
+        return;
+    };
+    // Two balls of the size they spawn at merge into one this big.
+    let first_merge_radius = BALL_RADIUS / 2.0 * SQRT_2;
+    play_sound(
+        &mut commands,
+        &sound.with_speed(MERGE_SOUND_SPEED * first_merge_radius / radius),
+        1.0,
+        &audio_settings,
+    );
+}
+
+/// Mutes and unmutes sound effects and music together with M.
+pub fn toggle_mute(actions: Actions, mut audio_settings: ResMut<AudioSettings>) {
+    if actions.just_pressed(Action::ToggleMute) {
+        // Anything still audible counts as unmuted.
+        let mute = audio_settings.sfx_enabled || audio_settings.music_enabled;
+        audio_settings.sfx_enabled = !mute;
+        audio_settings.music_enabled = !mute;
+    }
+}
+
+pub fn unlock_audio(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mouse_input: Res<ButtonInput<MouseButton>>,
+    touches: Res<Touches>,
+    mut unlocked: ResMut<AudioUnlocked>,
+) {
+    if unlocked.0 {
+        return;
+    }
+    if keyboard_input.get_just_pressed().next().is_some()
+        || mouse_input.get_just_pressed().next().is_some()
+        || touches.any_just_pressed()
+    {
+        unlocked.0 = true;
+    }
+}
+
+pub fn audio_unlocked(unlocked: Res<AudioUnlocked>) -> bool {
+    unlocked.0
+}
+
+/// Loads the music for every screen, starting with the menu's.
+pub fn setup_music(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut pads: ResMut<Assets<Pad>>,
+) {
+    let mut source = |path: &'static str, chord: &[f32]| MusicSource {
+        file: asset_server.load(path),
+        fallback: pads.add(Pad {
+            frequencies: chord.to_vec(),
+        }),
+    };
+    let tracks = MusicTracks {
+        menu: source(MENU_MUSIC_PATH, &MENU_CHORD),
+        playing: source(PLAYING_MUSIC_PATH, &PLAYING_CHORD),
+        game_over: source(GAME_OVER_MUSIC_PATH, &GAME_OVER_CHORD),
+    };
+    commands.insert_resource(MusicTrack(tracks.menu.clone()));
+    commands.insert_resource(tracks);
+}
+
+/// Switches to the music for the screen the app has moved to.
+pub fn choose_music_track(
+    state: Res<State<AppState>>,
+    tracks: Res<MusicTracks>,
+    mut track: ResMut<MusicTrack>,
+) {
+    let next = match state.get() {
+        AppState::Menu | AppState::Paused | AppState::Replay => &tracks.menu,
+        AppState::Running | AppState::Online => &tracks.playing,
+        AppState::GameOver => &tracks.game_over,
+    };
+    // Moving between screens with the same music carries on with it.
+    if track.0 != *next {
+        track.0 = next.clone();
+    }
+}
+
+/// Starts the new track when it changes, and the first one once sound is allowed. Waits for its
+/// file to finish loading, and plays its chord instead if the file couldn't be loaded.
+pub fn switch_music_track(
+    track: Res<MusicTrack>,
+    mut waiting: Local<bool>,
+    mut music_query: Query<&mut Music>,
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    audio_sources: Res<Assets<AudioSource>>,
+) {
+    if track.is_changed() {
+        *waiting = true;
+    }
+    let loaded = audio_sources.contains(&track.0.file);
+    if !*waiting || (!loaded && asset_server.load_state(track.0.file.id()) == LoadState::Loading) {
+        return;
+    }
+    *waiting = false;
+    for mut music in &mut music_query {
+        music.fading_out = true;
+    }
+    // Starts silent and fades in from there.
+    let settings = PlaybackSettings::LOOP.with_volume(Volume::new(0.0));
+    let music = Music { fading_out: false };
+    if loaded {
+        commands.spawn((
+            AudioSourceBundle {
+                source: track.0.file.clone(),
+                settings,
+            },
+            music,
+        ));
+    } else {
+        commands.spawn((
+            AudioSourceBundle {
+                source: track.0.fallback.clone(),
+                settings,
+            },
+            music,
+        ));
+    }
+}
+
+/// Moves the volume of every music track a little closer to where it should be, so muting,
+/// unmuting and switching tracks fade rather than cut.
+pub fn fade_music(
+    music_query: Query<(Entity, &Music, &AudioSink)>,
+    mut commands: Commands,
+    audio_settings: Res<AudioSettings>,
+    time: Res<Time>,
+) {
+    let step = time.delta_seconds() / MUSIC_FADE_TIME;
+    for (entity, music, sink) in &music_query {
+        let target = if music.fading_out || !audio_settings.music_enabled {
+            0.0
+        } else {
+            audio_settings.master_volume * audio_settings.music_volume
+        };
+        let volume = sink.volume();
+        let volume = if volume < target {
+            (volume + step).min(target)
+        } else {
+            (volume - step).max(target)
+        };
+        sink.set_volume(volume);
+
+        if music.fading_out && volume <= 0.0 {
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+/// Restores the audio settings saved by an earlier run, if there are any.
+pub fn load_audio_settings(mut audio_settings: ResMut<AudioSettings>) {
+    let contents = match fs::read_to_string(AUDIO_SETTINGS_PATH) {
+        Ok(contents) => contents,
+        Err(error) if error.kind() == ErrorKind::NotFound => return,
+        Err(error) => {
+            warn!("Couldn't read {AUDIO_SETTINGS_PATH}: {error}");
+            return;
+        }
+    };
+    match ron::from_str(&contents) {
+        Ok(loaded) => *audio_settings = loaded,
+        Err(error) => warn!("Couldn't parse {AUDIO_SETTINGS_PATH}: {error}"),
+    }
+}
+
+pub fn save_audio_settings(audio_settings: Res<AudioSettings>) {
+    // Nothing to save for the defaults, or for what was just loaded.
+    if !audio_settings.is_changed() || audio_settings.is_added() {
+        return;
+    }
+    let result = ron::ser::to_string_pretty(&*audio_settings, Default::default())
+        .map_err(|error| error.to_string())
+        .and_then(|contents| {
+            fs::write(AUDIO_SETTINGS_PATH, contents).map_err(|error| error.to_string())
+        });
+    if let Err(error) = result {
+        warn!("Couldn't save {AUDIO_SETTINGS_PATH}: {error}");
+    }
+}
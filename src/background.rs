@@ -0,0 +1,201 @@
+use bevy::{
+    prelude::*,
+    sprite::{MaterialMesh2dBundle, Mesh2dHandle},
+    window::PrimaryWindow,
+};
+
+use crate::{
+    keybindings::{Action, Actions},
+    settings::{Background, Settings},
+};
+
+// Behind the cages at 0, and just in front of the 2D camera's far plane.
+const BACKGROUND_Z: f32 = -0.05;
+// The gradient overhangs the view by this fraction, so screen shake doesn't uncover its edges.
+const GRADIENT_OVERHANG: f32 = 0.1;
+// In degrees per second.
+const GRADIENT_HUE_SPEED: f32 = 6.0;
+// How far apart in hue the top and bottom of the gradient are, in degrees.
+const GRADIENT_HUE_SPREAD: f32 = 60.0;
+const GRADIENT_SATURATION: f32 = 0.4;
+const GRADIENT_TOP_LIGHTNESS: f32 = 0.15;
+const GRADIENT_BOTTOM_LIGHTNESS: f32 = 0.06;
+const STARS_PER_LAYER: usize = 60;
+// In pixels on screen, however far the camera is zoomed.
+const STAR_RADIUS: f32 = 1.0;
+// How far each layer of stars moves along with the camera, from 0 (fixed to the screen) to 1
+// (fixed to the world), and how bright its stars are. Nearer layers move more.
+const STAR_LAYERS: [(f32, f32); 3] = [(0.05, 0.3), (0.15, 0.55), (0.3, 0.8)];
+// In pixels per second, for a layer that moves fully with the camera.
+const STAR_DRIFT: Vec2 = Vec2::new(-40.0, -10.0);
+
+/// The full-screen quad the gradient is drawn on.
+#[derive(Component)]
+pub struct GradientBackground;
+
+/// A star of the starfield, which wraps around the screen as it drifts and the camera moves.
+#[derive(Component)]
+pub struct Star {
+    /// Where it is on the screen, from 0 to 1 on each axis, before drifting and parallax.
+    position: Vec2,
+    depth: f32,
+}
+
+pub fn spawn_background(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+) {
+    commands.spawn((
+        MaterialMesh2dBundle {
+            mesh: meshes.add(Rectangle::new(1.0, 1.0)).into(),
+            // Coloured in by its vertices.
+            material: materials.add(Color::WHITE),
+            visibility: Visibility::Hidden,
+            ..Default::default()
+        },
+        GradientBackground,
+    ));
+
+    let star_mesh: Mesh2dHandle = meshes
+        .add(Circle {
+            radius: STAR_RADIUS,
+        })
+        .into();
+    for (depth, brightness) in STAR_LAYERS {
+        let material = materials.add(Color::rgba(1.0, 1.0, 1.0, brightness));
+        for _ in 0..STARS_PER_LAYER {
+            commands.spawn((
+                MaterialMesh2dBundle {
+                    mesh: star_mesh.clone(),
+                    material: material.clone(),
+                    visibility: Visibility::Hidden,
+                    ..Default::default()
+                },
+                Star {
+                    position: Vec2::new(rand::random(), rand::random()),
+                    depth,
+                },
+            ));
+        }
+    }
+}
+
+/// Switches between the plain background colour, the gradient and the starfield with F6.
+pub fn cycle_background(actions: Actions, mut settings: ResMut<Settings>) {
+    if actions.just_pressed(Action::CycleBackground) {
+        settings.background = match settings.background {
+            Background::Plain => Background::Gradient,
+            Background::Gradient => Background::Starfield,
+            Background::Starfield => Background::Plain,
+        };
+    }
+}
+
+pub fn show_background(
+    settings: Res<Settings>,
+    mut gradient_query: Query<&mut Visibility, (With<GradientBackground>, Without<Star>)>,
+    mut star_query: Query<&mut Visibility, With<Star>>,
+) {
+    if !settings.is_changed() {
+        return;
+    }
+    let visibility = |shown| {
+        if shown {
+            Visibility::Inherited
+        } else {
+            Visibility::Hidden
+        }
+    };
+    for mut gradient_visibility in &mut gradient_query {
+        *gradient_visibility = visibility(settings.background == Background::Gradient);
+    }
+    for mut star_visibility in &mut star_query {
+        *star_visibility = visibility(settings.background == Background::Starfield);
+    }
+}
+
+/// Stretches the gradient over the whole view and slowly shifts its colours.
+#[allow(clippy::type_complexity)]
+pub fn animate_gradient(
+    settings: Res<Settings>,
+    window_query: Query<&Window, With<PrimaryWindow>>,
+    camera_query: Query<(&Transform, &OrthographicProjection), With<Camera>>,
+    mut gradient_query: Query<
+        (&mut Transform, &Mesh2dHandle),
+        (With<GradientBackground>, Without<Camera>),
+    >,
+    mut meshes: ResMut<Assets<Mesh>>,
+    time: Res<Time<Real>>,
+) {
+    if settings.background != Background::Gradient {
+        return;
+    }
+    let (Ok(window), Ok((camera_transform, projection))) =
+        (window_query.get_single(), camera_query.get_single())
+    else {
+        return;
+    };
+    let view_size = Vec2::new(window.width(), window.height()) * projection.scale;
+
+    let hue = (time.elapsed_seconds() * GRADIENT_HUE_SPEED) % 360.0;
+    let top = Color::hsl(hue, GRADIENT_SATURATION, GRADIENT_TOP_LIGHTNESS);
+    let bottom = Color::hsl(
+        (hue + GRADIENT_HUE_SPREAD) % 360.0,
+        GRADIENT_SATURATION,
+        GRADIENT_BOTTOM_LIGHTNESS,
+    );
+    for (mut transform, mesh) in &mut gradient_query {
+        transform.translation = camera_transform.translation.truncate().extend(BACKGROUND_Z);
+        transform.scale = (view_size * (1.0 + GRADIENT_OVERHANG)).extend(1.0);
+        let Some(mesh) = meshes.get_mut(&mesh.0) else {
+            continue;
+        };
+        let Some(positions) = mesh
+            .attribute(Mesh::ATTRIBUTE_POSITION)
+            .and_then(|positions| positions.as_float3())
+        else {
+            continue;
+        };
+        let colours: Vec<_> = positions
+            .iter()
+            .map(|[_, y, _]| {
+                let colour = if *y > 0.0 { top } else { bottom };
+                colour.as_linear_rgba_f32()
+            })
+            .collect();
+        mesh.insert_attribute(Mesh::ATTRIBUTE_COLOR, colours);
+    }
+}
+
+/// Drifts the stars across the view, with nearer ones moving further when the camera moves.
+pub fn move_stars(
+    settings: Res<Settings>,
+    window_query: Query<&Window, With<PrimaryWindow>>,
+    camera_query: Query<(&Transform, &OrthographicProjection), With<Camera>>,
+    mut star_query: Query<(&Star, &mut Transform), Without<Camera>>,
+    time: Res<Time<Real>>,
+) {
+    if settings.background != Background::Starfield {
+        return;
+    }
+    let (Ok(window), Ok((camera_transform, projection))) =
+        (window_query.get_single(), camera_query.get_single())
+    else {
+        return;
+    };
+    let view_size = Vec2::new(window.width(), window.height()) * projection.scale;
+    if view_size.min_element() <= 0.0 {
+        return;
+    }
+    let camera_position = camera_transform.translation.truncate();
+    let drift = STAR_DRIFT * time.elapsed_seconds();
+
+    for (star, mut transform) in &mut star_query {
+        let offset = star.position * view_size + (drift - camera_position) * star.depth;
+        // Wraps around to stay within the view, centred on the camera.
+        let wrapped = offset.rem_euclid(view_size) - view_size / 2.0;
+        transform.translation = (camera_position + wrapped).extend(BACKGROUND_Z);
+        transform.scale = Vec3::new(projection.scale, projection.scale, 1.0);
+    }
+}
@@ -0,0 +1,100 @@
+use bevy::{
+    prelude::*,
+    utils::{HashMap, HashSet},
+};
+
+use crate::Ball;
+
+// Colour channels are rounded to this many steps per unit, so barely different colours, like
+// the steps of a fade, share a material.
+const COLOUR_STEPS: f32 = 255.0;
+// In seconds.
+const PRUNE_INTERVAL: f32 = 5.0;
+
+type MaterialKey = ([i32; 4], Option<AssetId<Image>>);
+
+/// The circle mesh every ball is drawn with, scaled to its radius by its transform, and the
+/// materials shared by balls that look the same.
+#[derive(Resource)]
+pub struct BallAssets {
+    pub mesh: Handle<Mesh>,
+    materials: HashMap<MaterialKey, Handle<ColorMaterial>>,
+    prune_timer: Timer,
+}
+
+impl BallAssets {
+    pub fn new(meshes: &mut Assets<Mesh>) -> Self {
+        Self {
+            mesh: meshes.add(Circle::default()),
+            materials: HashMap::new(),
+            prune_timer: Timer::from_seconds(PRUNE_INTERVAL, TimerMode::Repeating),
+        }
+    }
+
+    /// The shared material with `material`'s colour and texture, which is added if it's the
+    /// first of its kind.
+    fn shared(
+        &mut self,
+        materials: &mut Assets<ColorMaterial>,
+        material: ColorMaterial,
+    ) -> Handle<ColorMaterial> {
+        let key = (
+            material
+                .color
+                .as_rgba_f32()
+                .map(|channel| (channel * COLOUR_STEPS).round() as i32),
+            material.texture.as_ref().map(Handle::id),
+        );
+        self.materials
+            .entry(key)
+            .or_insert_with(|| materials.add(material))
+            .clone()
+    }
+
+    pub fn material(
+        &mut self,
+        materials: &mut Assets<ColorMaterial>,
+        colour: Color,
+    ) -> Handle<ColorMaterial> {
+        self.shared(materials, ColorMaterial::from(colour))
+    }
+
+    /// Changes how a ball looks by moving it over to the shared material for its new look.
+    /// Changing its material in place would change every other ball using it too.
+    pub fn restyle(
+        &mut self,
+        materials: &mut Assets<ColorMaterial>,
+        handle: &mut Handle<ColorMaterial>,
+        change: impl FnOnce(&mut ColorMaterial),
+    ) {
+        let mut material = materials.get(&*handle).cloned().unwrap_or_default();
+        change(&mut material);
+        let shared = self.shared(materials, material);
+        if *handle != shared {
+            *handle = shared;
+        }
+    }
+}
+
+/// Every so often, removes the shared materials no ball uses anymore, like those of despawned
+/// balls and of the steps of a fade. They'd otherwise be kept alive by the pool forever.
+///
+/// Runs in `Last`, once the balls spawned this frame have their materials.
+pub fn prune_materials(
+    mut ball_assets: ResMut<BallAssets>,
+    ball_query: Query<&Handle<ColorMaterial>, With<Ball>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    time: Res<Time>,
+) {
+    if !ball_assets.prune_timer.tick(time.delta()).just_finished() {
+        return;
+    }
+    let in_use: HashSet<AssetId<ColorMaterial>> = ball_query.iter().map(Handle::id).collect();
+    ball_assets.materials.retain(|_, handle| {
+        let used = in_use.contains(&handle.id());
+        if !used {
+            materials.remove(handle.id());
+        }
+        used
+    });
+}
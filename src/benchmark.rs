@@ -0,0 +1,125 @@
+use std::time::{Duration, Instant};
+
+use bevy::{
+    prelude::*,
+    tasks::{ComputeTaskPool, TaskPool},
+};
+
+use crate::{
+    apply_gravity, apply_velocity,
+    cage::{self, Cage, CageVelocity, InCage},
+    collide_others, command_line_value, free_spawn_position,
+    kind::BallKind,
+    powerup::ActiveEffects,
+    rng::SimRng,
+    settings::Settings,
+    Acceleration, Ball, CageCollisionEvent, Collision, Gravity, GravityField, OtherCollisionEvent,
+    Radius, Spin, Velocity, BALL_GRAVITY_SCALE, BALL_RADIUS, GRAVITY,
+};
+
+const BENCHMARK_ARG: &str = "--benchmark";
+const BENCHMARK_STEPS_ARG: &str = "--steps";
+const DEFAULT_BALLS: usize = 500;
+const DEFAULT_STEPS: u32 = 1000;
+// Bevy's default fixed timestep.
+const STEP: Duration = Duration::from_micros(15625);
+// Room for every ball with plenty to spare, so they aren't packed in from the start.
+const CAGE_AREA_PER_BALL: f32 = 4.0 * BALL_RADIUS * BALL_RADIUS;
+// Always the same, so runs can be compared.
+const SEED: u64 = 0;
+
+/// How many balls and fixed steps `--benchmark [balls] [--steps <steps>]` asked for, if it was
+/// passed at all.
+pub fn benchmark_args() -> Option<(usize, u32)> {
+    if !std::env::args().any(|arg| arg == BENCHMARK_ARG) {
+        return None;
+    }
+    let balls = command_line_value(BENCHMARK_ARG)
+        .and_then(|balls| balls.parse().ok())
+        .unwrap_or(DEFAULT_BALLS);
+    let steps = command_line_value(BENCHMARK_STEPS_ARG)
+        .and_then(|steps| steps.parse().ok())
+        .unwrap_or(DEFAULT_STEPS);
+    Some((balls, steps))
+}
+
+/// Drops `balls` balls into a single cage and runs the movement and collision systems for
+/// `steps` fixed steps, without a window, then prints how fast that went.
+///
+/// Every ball is tested against every other one in its cage, which is the only broad phase so
+/// far. Any others should be run here too, one after the other, so they can be compared.
+pub fn run(balls: usize, steps: u32) {
+    // Usually set up by the `TaskPoolPlugin`, for collisions to be found in parallel.
+    ComputeTaskPool::get_or_init(TaskPool::default);
+    let mut world = World::new();
+    world.insert_resource(Settings::default());
+    world.insert_resource(GravityField(GRAVITY));
+    world.insert_resource(ActiveEffects::default());
+    world.insert_resource(Time::<()>::default());
+    world.init_resource::<Events<CageCollisionEvent>>();
+    world.init_resource::<Events<OtherCollisionEvent>>();
+    let mut rng = SimRng::from_seed(SEED);
+
+    let cage = Cage::new((balls as f32 * CAGE_AREA_PER_BALL / std::f32::consts::PI).sqrt());
+    let cage_transform = Transform::default();
+    let mut others = Vec::new();
+    for _ in 0..balls {
+        let Some(position) = free_spawn_position(&mut rng, &cage, &cage_transform, &others) else {
+            break;
+        };
+        others.push((position, BALL_RADIUS / 2.0));
+    }
+    let cage_entity = world
+        .spawn((cage, cage_transform, CageVelocity::default()))
+        .id();
+    for &(position, radius) in &others {
+        world.spawn((
+            Transform::from_translation(position.extend(1.0)),
+            Ball,
+            Velocity(Vec2::ZERO),
+            Radius(radius),
+            Spin::default(),
+            BallKind::default(),
+            Acceleration::default(),
+            Gravity(BALL_GRAVITY_SCALE),
+            Collision,
+            InCage(cage_entity),
+        ));
+    }
+    if others.len() < balls {
+        println!("Only found room for {} of the {balls} balls", others.len());
+    }
+
+    let mut schedule = Schedule::default();
+    schedule.add_systems(
+        (
+            apply_gravity,
+            apply_velocity,
+            cage::collide_cage,
+            collide_others,
+        )
+            .chain(),
+    );
+
+    let mut contacts = 0;
+    let start = Instant::now();
+    for _ in 0..steps {
+        world.resource_mut::<Time>().advance_by(STEP);
+        schedule.run(&mut world);
+        world.resource_mut::<Events<CageCollisionEvent>>().clear();
+        contacts += world
+            .resource_mut::<Events<OtherCollisionEvent>>()
+            .drain()
+            .count();
+    }
+    let elapsed = start.elapsed().as_secs_f64();
+
+    // Each ball is checked against every other one, both ways round.
+    let pairs_tested = others.len() * others.len().saturating_sub(1);
+    println!(
+        "All pairs: {balls} balls, {steps} steps in {elapsed:.3} s ({:.1} steps/s), \
+         {pairs_tested} pairs tested and {:.1} contacts per step",
+        steps as f64 / elapsed,
+        contacts as f64 / steps.max(1) as f64,
+    );
+}
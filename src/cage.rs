@@ -0,0 +1,1645 @@
+use std::f32::consts::{FRAC_1_SQRT_2, FRAC_PI_2, PI, TAU};
+
+use bevy::{
+    prelude::*,
+    render::{mesh::Indices, render_asset::RenderAssetUsages, render_resource::PrimitiveTopology},
+    sprite::{MaterialMesh2dBundle, Mesh2dHandle},
+    utils::HashMap,
+    window::PrimaryWindow,
+};
+
+use crate::{
+    arena::{signed_area, Arena, ArenaHandles},
+    cage_at, cursor_world_position,
+    keybindings::{Action, Actions},
+    kind::BallKind,
+    menu::AppState,
+    settings::Settings,
+    theme::Theme,
+    Ball, CageCollisionEvent, Collision, Radius, Sleeping, Spin, Velocity,
+};
+
+pub const CAGE_RADIUS: f32 = 100.0;
+// The wall grows inwards from the cage radius, so balls bounce off its inner surface.
+const CAGE_WALL_THICKNESS: f32 = 2.0;
+
+pub const CAGE_MIN_RADIUS: f32 = 30.0;
+pub const CAGE_MAX_RADIUS: f32 = 350.0;
+// In pixels per second.
+const CAGE_RESIZE_SPEED: f32 = 60.0;
+
+// In pixels per second.
+const CAGE_SHRINK_SPEED: f32 = 3.0;
+// The fraction of the cage area covered by balls at which a shrinking run ends.
+const CAGE_MAX_PRESSURE: f32 = 0.75;
+// The run is over once the balls cover this fraction of any cage.
+const CAGE_SATURATION: f32 = 0.6;
+
+// How quickly a dragged cage closes the distance to the cursor, per second.
+const CAGE_FOLLOW_STIFFNESS: f32 = 8.0;
+// In pixels per second.
+const CAGE_MAX_FOLLOW_SPEED: f32 = 1500.0;
+
+// Size of a nested cage relative to the cage it's placed in.
+const NESTED_CAGE_SCALE: f32 = 0.4;
+// Draws nested cages on top of the cage they're in.
+const NESTED_CAGE_Z: f32 = 0.2;
+// Thick enough that balls can be seen bouncing off both sides of the wall.
+const NESTED_CAGE_WALL_THICKNESS: f32 = 6.0;
+
+// Number of triangles used to draw the gap cover.
+const CAGE_GAP_SEGMENTS: u32 = 16;
+
+// Number of arcs a breakable cage wall is split into.
+const CAGE_SEGMENT_COUNT: usize = 24;
+const CAGE_SEGMENT_HIT_POINTS: u32 = 8;
+// Slower impacts, in pixels per second, don't damage the wall.
+const CAGE_SEGMENT_DAMAGE_SPEED: f32 = 150.0;
+// What a segment looks like right before it breaks.
+const CAGE_CRACK_COLOR: Color = Color::rgb(0.9, 0.3, 0.2);
+// How far past every cage's wall a ball that escaped can fall before it's despawned.
+const ESCAPED_BALL_RANGE: f32 = 2000.0;
+
+/// The container balls bounce around in. Its children draw the wall and interior.
+#[derive(Component)]
+pub struct Cage {
+    /// For polygons, the distance from the center to each corner. For rectangles, half the height.
+    /// Measured to the outer surface of the wall.
+    pub radius: f32,
+    pub shape: CageShape,
+    pub gap: Option<CageGap>,
+    /// How far the wall reaches in from the radius. Balls inside bounce off its inner surface, and
+    /// balls outside a nested cage off its outer one.
+    pub wall_thickness: f32,
+}
+
+/// How fast a cage is being dragged around. Balls bouncing off its wall pick this up.
+#[derive(Component, Default)]
+pub struct CageVelocity(pub Vec2);
+
+/// Marks a cage placed inside another one. Its gap acts as a portal between the two regions: balls
+/// outside it bounce off its outer surface, and pass between the cages only through the gap.
+#[derive(Component)]
+pub struct NestedIn(pub Entity);
+
+/// An opening in the cage wall that balls pass straight through.
+#[derive(Clone, Copy, Debug)]
+pub struct CageGap {
+    /// Angle of the middle of the gap, relative to the cage's rotation.
+    pub direction: f32,
+    /// Angular width of the gap, in radians.
+    pub width: f32,
+}
+
+impl CageGap {
+    fn contains(&self, angle: f32) -> bool {
+        let offset = (angle - self.direction + PI).rem_euclid(TAU) - PI;
+        offset.abs() < self.width / 2.0
+    }
+}
+
+/// Hit points of each arc of a breakable cage wall, counter-clockwise from the cage's local x axis.
+/// Balls pass straight through an arc once it's down to zero.
+#[derive(Component)]
+pub struct CageSegments {
+    pub hit_points: Vec<u32>,
+}
+
+impl CageSegments {
+    fn new() -> Self {
+        Self {
+            hit_points: vec![CAGE_SEGMENT_HIT_POINTS; CAGE_SEGMENT_COUNT],
+        }
+    }
+
+    fn index(&self, angle: f32) -> usize {
+        let index = (angle.rem_euclid(TAU) / TAU * self.hit_points.len() as f32) as usize;
+        index.min(self.hit_points.len() - 1)
+    }
+
+    fn is_broken(&self, angle: f32) -> bool {
+        self.hit_points[self.index(angle)] == 0
+    }
+
+    fn damage(&mut self, angle: f32) {
+        let index = self.index(angle);
+        self.hit_points[index] = self.hit_points[index].saturating_sub(1);
+    }
+
+    /// The arc covered by a segment.
+    fn arc(&self, index: usize) -> CageGap {
+        let width = TAU / self.hit_points.len() as f32;
+        CageGap {
+            direction: (index as f32 + 0.5) * width,
+            width,
+        }
+    }
+}
+
+/// Sent when a ball gets out through a gap in its cage wall.
+#[derive(Event)]
+pub struct BallEscapedEvent {
+    /// The cage it got out of.
+    pub cage: Entity,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum CageShape {
+    Circle,
+    /// A regular polygon, which spins at [`Settings::cage_angular_velocity`].
+    Polygon {
+        sides: u32,
+    },
+    /// An axis-aligned box.
+    Rect {
+        aspect_ratio: f32,
+    },
+    /// An axis-aligned ellipse, `aspect_ratio` times as wide as it is tall.
+    Ellipse {
+        aspect_ratio: f32,
+    },
+    /// An outline loaded from an [`Arena`], counter-clockwise and in multiples of the radius.
+    Custom(Vec<Vec2>),
+}
+
+impl CageShape {
+    /// The built-in shapes cycled through with the O key, followed by any loaded arenas.
+    const BUILT_IN: [CageShape; 6] = [
+        CageShape::Circle,
+        CageShape::Polygon { sides: 6 },
+        CageShape::Polygon { sides: 4 },
+        CageShape::Polygon { sides: 3 },
+        CageShape::Rect { aspect_ratio: 1.5 },
+        CageShape::Ellipse { aspect_ratio: 1.6 },
+    ];
+}
+
+/// Where a ball overlaps the cage wall.
+struct WallContact {
+    /// Points into the cage.
+    normal: Vec2,
+    overlap: f32,
+    point: Vec2,
+}
+
+impl Cage {
+    pub fn new(radius: f32) -> Self {
+        Self {
+            radius,
+            shape: CageShape::Circle,
+            gap: None,
+            wall_thickness: CAGE_WALL_THICKNESS,
+        }
+    }
+
+    /// The area inside the wall.
+    pub fn area(&self) -> f32 {
+        match self.shape {
+            CageShape::Circle => PI * (self.radius - self.wall_thickness).powi(2),
+            CageShape::Ellipse { .. } => {
+                let half_size = self.ellipse_half_size(-self.wall_thickness);
+                PI * half_size.x * half_size.y
+            }
+            CageShape::Polygon { .. } | CageShape::Rect { .. } | CageShape::Custom(_) => {
+                signed_area(&self.vertices(-self.wall_thickness))
+            }
+        }
+    }
+
+    /// The corners of a non-circular cage relative to its center, counter-clockwise, with every
+    /// edge pushed outwards by `offset`.
+    fn vertices(&self, offset: f32) -> Vec<Vec2> {
+        match &self.shape {
+            CageShape::Circle | CageShape::Ellipse { .. } => Vec::new(),
+            &CageShape::Polygon { sides } => {
+                let radius = self.radius + offset / (PI / sides as f32).cos();
+                (0..sides)
+                    .map(|i| Vec2::from_angle(FRAC_PI_2 + i as f32 * TAU / sides as f32) * radius)
+                    .collect()
+            }
+            &CageShape::Rect { aspect_ratio } => {
+                let half_size = Vec2::new(self.radius * aspect_ratio, self.radius) + offset;
+                vec![
+                    Vec2::new(half_size.x, half_size.y),
+                    Vec2::new(-half_size.x, half_size.y),
+                    Vec2::new(-half_size.x, -half_size.y),
+                    Vec2::new(half_size.x, -half_size.y),
+                ]
+            }
+            CageShape::Custom(outline) => {
+                let vertices: Vec<Vec2> =
+                    outline.iter().map(|vertex| *vertex * self.radius).collect();
+                if offset == 0.0 {
+                    return vertices;
+                }
+                let count = vertices.len();
+                (0..count)
+                    .map(|i| {
+                        // Move each corner along its miter, so both adjacent edges move by `offset`.
+                        let previous = vertices[(i + count - 1) % count];
+                        let vertex = vertices[i];
+                        let next = vertices[(i + 1) % count];
+                        let previous_normal = -(vertex - previous).perp().normalize();
+                        let next_normal = -(next - vertex).perp().normalize();
+                        let miter = (previous_normal + next_normal).normalize();
+                        vertex + miter * offset / miter.dot(previous_normal)
+                    })
+                    .collect()
+            }
+        }
+    }
+
+    /// The inner surface of the wall as a counter-clockwise loop, with curved walls split into
+    /// `segments` straight edges.
+    #[cfg(feature = "rapier")]
+    pub fn inner_outline(&self, segments: u32) -> Vec<Vec2> {
+        match &self.shape {
+            CageShape::Circle | CageShape::Ellipse { .. } => {
+                let half_size = self.ellipse_half_size(-self.wall_thickness);
+                (0..segments)
+                    .map(|i| Vec2::from_angle(i as f32 * TAU / segments as f32) * half_size)
+                    .collect()
+            }
+            CageShape::Polygon { .. } | CageShape::Rect { .. } | CageShape::Custom(_) => {
+                self.vertices(-self.wall_thickness)
+            }
+        }
+    }
+
+    /// The cage outline grown by `offset`, so the wall is equally thick everywhere.
+    fn mesh(&self, offset: f32) -> Mesh {
+        match &self.shape {
+            CageShape::Circle => Circle {
+                radius: self.radius + offset,
+            }
+            .into(),
+            CageShape::Ellipse { .. } => Ellipse {
+                half_size: self.ellipse_half_size(offset),
+            }
+            .into(),
+            CageShape::Polygon { .. } | CageShape::Rect { .. } | CageShape::Custom(_) => {
+                polygon_mesh(&self.vertices(offset))
+            }
+        }
+    }
+
+    /// The radius of the smallest circle around the center containing the whole wall.
+    fn bounding_radius(&self) -> f32 {
+        match &self.shape {
+            CageShape::Circle => self.radius,
+            CageShape::Ellipse { .. } => self.ellipse_half_size(0.0).max_element(),
+            CageShape::Polygon { .. } | CageShape::Rect { .. } | CageShape::Custom(_) => self
+                .vertices(0.0)
+                .iter()
+                .map(|vertex| vertex.length())
+                .fold(0.0, f32::max),
+        }
+    }
+
+    fn wall_contact(
+        &self,
+        transform: &Transform,
+        ball_position: Vec2,
+        ball_radius: f32,
+    ) -> Option<WallContact> {
+        let contact =
+            self.solid_wall_contact(transform, ball_position, ball_radius, -self.wall_thickness)?;
+        (!self.in_gap(transform, contact.point)).then_some(contact)
+    }
+
+    /// Like [`Cage::wall_contact`], but for a ball outside the cage that the wall pushes away.
+    fn outer_wall_contact(
+        &self,
+        transform: &Transform,
+        ball_position: Vec2,
+        ball_radius: f32,
+    ) -> Option<WallContact> {
+        let contact = match &self.shape {
+            CageShape::Circle => {
+                let cage_position = transform.translation.truncate();
+                let distance = ball_position.distance(cage_position);
+                if distance - ball_radius >= self.radius {
+                    return None;
+                }
+                let normal = (ball_position - cage_position).normalize_or_zero();
+                WallContact {
+                    normal,
+                    overlap: self.radius + ball_radius - distance,
+                    point: cage_position + normal * self.radius,
+                }
+            }
+            CageShape::Polygon { .. }
+            | CageShape::Rect { .. }
+            | CageShape::Ellipse { .. }
+            | CageShape::Custom(_) => {
+                let inner = self.solid_wall_contact(transform, ball_position, ball_radius, 0.0)?;
+                // The same contact seen from the other side of the outer surface.
+                WallContact {
+                    normal: -inner.normal,
+                    overlap: 2.0 * ball_radius - inner.overlap,
+                    point: inner.point,
+                }
+            }
+        };
+        (!self.in_gap(transform, contact.point)).then_some(contact)
+    }
+
+    fn in_gap(&self, transform: &Transform, point: Vec2) -> bool {
+        self.gap
+            .is_some_and(|gap| gap.contains(local_angle(transform, point)))
+    }
+
+    fn angular_velocity(&self, settings: &Settings) -> f32 {
+        match self.shape {
+            CageShape::Polygon { .. } => settings.cage_angular_velocity,
+            CageShape::Circle
+            | CageShape::Rect { .. }
+            | CageShape::Ellipse { .. }
+            | CageShape::Custom(_) => 0.0,
+        }
+    }
+
+    /// The semi-axes of an elliptical cage, with the wall moved outwards by `offset`.
+    fn ellipse_half_size(&self, offset: f32) -> Vec2 {
+        let aspect_ratio = match self.shape {
+            CageShape::Ellipse { aspect_ratio } => aspect_ratio,
+            _ => 1.0,
+        };
+        Vec2::new(self.radius * aspect_ratio, self.radius) + offset
+    }
+
+    fn world_vertices(&self, transform: &Transform, offset: f32) -> Vec<Vec2> {
+        self.vertices(offset)
+            .into_iter()
+            .map(|vertex| transform.transform_point(vertex.extend(0.0)).truncate())
+            .collect()
+    }
+
+    /// Whether a circle of `radius` around `point` fits entirely inside the cage.
+    pub fn contains(&self, transform: &Transform, point: Vec2, radius: f32) -> bool {
+        match &self.shape {
+            CageShape::Circle => {
+                point.distance(transform.translation.truncate()) + radius
+                    < self.radius - self.wall_thickness
+            }
+            CageShape::Ellipse { .. } => {
+                let half_size = self.ellipse_half_size(-self.wall_thickness);
+                let local_point = local_position(transform, point);
+                (local_point / half_size).length_squared() < 1.0
+                    && closest_point_on_ellipse(half_size, local_point).distance(local_point)
+                        > radius
+            }
+            CageShape::Polygon { .. } | CageShape::Rect { .. } | CageShape::Custom(_) => {
+                let vertices = self.world_vertices(transform, -self.wall_thickness);
+                polygon_contains(&vertices, point)
+                    && (0..vertices.len()).all(|i| {
+                        let start = vertices[i];
+                        let end = vertices[(i + 1) % vertices.len()];
+                        closest_point_on_segment(start, end, point).distance(point) > radius
+                    })
+            }
+        }
+    }
+
+    /// Whether the ball has made it entirely through the wall.
+    fn has_escaped(&self, transform: &Transform, ball_position: Vec2, ball_radius: f32) -> bool {
+        let distance = ball_position.distance(transform.translation.truncate());
+        distance - ball_radius > self.bounding_radius()
+    }
+
+    /// Contact with the cage outline grown by `offset`, for a ball that belongs inside it.
+    fn solid_wall_contact(
+        &self,
+        transform: &Transform,
+        ball_position: Vec2,
+        ball_radius: f32,
+        offset: f32,
+    ) -> Option<WallContact> {
+        let cage_position = transform.translation.truncate();
+        match &self.shape {
+            CageShape::Circle => {
+                let radius = self.radius + offset;
+                let distance = ball_position.distance(cage_position);
+                if distance + ball_radius <= radius {
+                    return None;
+                }
+                let normal = (cage_position - ball_position).normalize();
+                Some(WallContact {
+                    normal,
+                    overlap: ball_radius + distance - radius,
+                    point: cage_position - normal * radius,
+                })
+            }
+            CageShape::Ellipse { .. } => {
+                let half_size = self.ellipse_half_size(offset);
+                let local_ball_position = local_position(transform, ball_position);
+                let point = closest_point_on_ellipse(half_size, local_ball_position);
+                let distance = point.distance(local_ball_position);
+                if distance >= ball_radius {
+                    return None;
+                }
+
+                // Unlike on a circle, the normal follows the gradient of the ellipse at the contact
+                // point rather than pointing at the center.
+                let normal = -(point / half_size.powf(2.0)).normalize_or_zero();
+                let overlap = if (local_ball_position / half_size).length_squared() < 1.0 {
+                    ball_radius - distance
+                } else {
+                    // The center already made it through the wall, push it back the other way.
+                    ball_radius + distance
+                };
+                Some(WallContact {
+                    normal: (transform.rotation * normal.extend(0.0)).truncate(),
+                    overlap,
+                    point: transform.transform_point(point.extend(0.0)).truncate(),
+                })
+            }
+            CageShape::Polygon { .. } | CageShape::Rect { .. } | CageShape::Custom(_) => {
+                let vertices = self.world_vertices(transform, offset);
+
+                // Only resolve against the closest edge, corners get sorted out over the next
+                // steps.
+                let (point, distance) = (0..vertices.len())
+                    .map(|i| {
+                        let start = vertices[i];
+                        let end = vertices[(i + 1) % vertices.len()];
+                        let point = closest_point_on_segment(start, end, ball_position);
+                        (point, point.distance(ball_position))
+                    })
+                    .min_by(|(_, a), (_, b)| a.total_cmp(b))?;
+                if distance >= ball_radius {
+                    return None;
+                }
+
+                if polygon_contains(&vertices, ball_position) {
+                    Some(WallContact {
+                        normal: (ball_position - point).normalize_or_zero(),
+                        overlap: ball_radius - distance,
+                        point,
+                    })
+                } else {
+                    // The center already made it through the wall, push it back the other way.
+                    Some(WallContact {
+                        normal: (point - ball_position).normalize_or_zero(),
+                        overlap: ball_radius + distance,
+                        point,
+                    })
+                }
+            }
+        }
+    }
+}
+
+/// Where a point is relative to the cage center and rotation.
+fn local_position(transform: &Transform, point: Vec2) -> Vec2 {
+    (transform.rotation.inverse() * (point.extend(0.0) - transform.translation)).truncate()
+}
+
+/// The angle of a point around the cage center, relative to the cage's rotation.
+fn local_angle(transform: &Transform, point: Vec2) -> f32 {
+    let local_point = local_position(transform, point);
+    local_point.y.atan2(local_point.x)
+}
+
+/// The closest point on an origin-centered ellipse, for points both inside and outside of it.
+/// There's no closed form for this, so it refines a guess a few times by treating the ellipse as a
+/// circle around its local center of curvature.
+fn closest_point_on_ellipse(half_size: Vec2, point: Vec2) -> Vec2 {
+    let (a, b) = (half_size.x, half_size.y);
+    // Flattened all the way, it's just a line segment along one axis.
+    if a.min(b) <= 0.0 {
+        return point.clamp(-half_size, half_size);
+    }
+    let p = point.abs();
+    // Every direction is as good as any other from the center of a circle, and the ends of the
+    // minor axis are closest for other ellipses. Refining the guess would get nowhere.
+    if p == Vec2::ZERO {
+        return if a < b {
+            Vec2::new(a, 0.0)
+        } else {
+            Vec2::new(0.0, b)
+        };
+    }
+    let mut t = Vec2::splat(FRAC_1_SQRT_2);
+    for _ in 0..3 {
+        let on_ellipse = half_size * t;
+        // The center of curvature at the current guess.
+        let evolute = Vec2::new(
+            (a * a - b * b) * t.x.powi(3) / a,
+            (b * b - a * a) * t.y.powi(3) / b,
+        );
+        let r = on_ellipse - evolute;
+        let q = p - evolute;
+        t = ((q * r.length() / q.length().max(f32::EPSILON) + evolute) / half_size)
+            .clamp(Vec2::ZERO, Vec2::ONE)
+            .normalize_or_zero();
+    }
+    half_size * t * point.signum()
+}
+
+fn closest_point_on_segment(start: Vec2, end: Vec2, point: Vec2) -> Vec2 {
+    let segment = end - start;
+    let t = ((point - start).dot(segment) / segment.length_squared()).clamp(0.0, 1.0);
+    start + segment * t
+}
+
+/// Even-odd test, so it works for concave outlines too.
+fn polygon_contains(vertices: &[Vec2], point: Vec2) -> bool {
+    let mut inside = false;
+    for i in 0..vertices.len() {
+        let start = vertices[i];
+        let end = vertices[(i + 1) % vertices.len()];
+        if (start.y > point.y) != (end.y > point.y)
+            && point.x < start.x + (point.y - start.y) / (end.y - start.y) * (end.x - start.x)
+        {
+            inside = !inside;
+        }
+    }
+    inside
+}
+
+/// Triangulates an outline by ear clipping, so concave outlines work too. Clockwise outlines are
+/// walked backwards, so the triangles always come out counter-clockwise.
+fn polygon_mesh(vertices: &[Vec2]) -> Mesh {
+    let mut remaining: Vec<usize> = (0..vertices.len()).collect();
+    if signed_area(vertices) < 0.0 {
+        remaining.reverse();
+    }
+    let mut indices = Vec::new();
+    while remaining.len() > 3 {
+        let count = remaining.len();
+        let corner = |i: usize| {
+            (
+                remaining[(i + count - 1) % count],
+                remaining[i],
+                remaining[(i + 1) % count],
+            )
+        };
+        let ear = (0..count).find(|&i| {
+            let (a, b, c) = corner(i);
+            let (a, b, c) = (vertices[a], vertices[b], vertices[c]);
+            (b - a).perp_dot(c - b) > 0.0
+                && remaining.iter().all(|&other| {
+                    let point = vertices[other];
+                    point == a || point == b || point == c || !triangle_contains(a, b, c, point)
+                })
+        });
+        // Only happens for outlines that cross themselves, draw what we have so far.
+        let Some(ear) = ear else {
+            break;
+        };
+        let (a, b, c) = corner(ear);
+        indices.extend([a as u32, b as u32, c as u32]);
+        remaining.remove(ear);
+    }
+    if remaining.len() == 3 {
+        indices.extend(remaining.iter().map(|&i| i as u32));
+    }
+
+    triangle_mesh(vertices, indices)
+}
+
+fn triangle_contains(a: Vec2, b: Vec2, c: Vec2, point: Vec2) -> bool {
+    (b - a).perp_dot(point - a) >= 0.0
+        && (c - b).perp_dot(point - b) >= 0.0
+        && (a - c).perp_dot(point - c) >= 0.0
+}
+
+/// A pie slice of the given radius, used to hide the wall where the gap is.
+fn sector_mesh(radius: f32, gap: CageGap) -> Mesh {
+    let start = gap.direction - gap.width / 2.0;
+    let mut vertices = vec![Vec2::ZERO];
+    vertices.extend((0..=CAGE_GAP_SEGMENTS).map(|i| {
+        let angle = start + gap.width * i as f32 / CAGE_GAP_SEGMENTS as f32;
+        Vec2::from_angle(angle) * radius
+    }));
+    let indices = (1..=CAGE_GAP_SEGMENTS)
+        .flat_map(|i| [0, i, i + 1])
+        .collect();
+    triangle_mesh(&vertices, indices)
+}
+
+fn triangle_mesh(vertices: &[Vec2], indices: Vec<u32>) -> Mesh {
+    let positions: Vec<[f32; 3]> = vertices
+        .iter()
+        .map(|vertex| [vertex.x, vertex.y, 0.0])
+        .collect();
+    let normals = vec![[0.0, 0.0, 1.0]; positions.len()];
+    let uvs = vec![[0.0, 0.0]; positions.len()];
+
+    Mesh::new(
+        PrimitiveTopology::TriangleList,
+        RenderAssetUsages::default(),
+    )
+    .with_inserted_attribute(Mesh::ATTRIBUTE_POSITION, positions)
+    .with_inserted_attribute(Mesh::ATTRIBUTE_NORMAL, normals)
+    .with_inserted_attribute(Mesh::ATTRIBUTE_UV_0, uvs)
+    .with_inserted_indices(Indices::U32(indices))
+}
+
+/// The cage a ball is contained by. Balls only collide with others in the same cage.
+#[derive(Component, Clone, Copy, PartialEq, Eq)]
+pub struct InCage(pub Entity);
+
+/// While active, the cage slowly closes in on the balls until they're packed too tightly.
+#[derive(Resource, Default)]
+pub struct ShrinkingCage {
+    pub active: bool,
+}
+
+/// Marks the mesh drawn behind the interior, whose visible rim forms the cage wall.
+#[derive(Component)]
+struct CageWall;
+
+#[derive(Component)]
+struct CageInterior;
+
+/// Drawn over the wall in the background colour wherever the cage has a gap.
+#[derive(Component)]
+struct CageGapCover;
+
+/// Draws the damage on one segment of a breakable cage wall.
+#[derive(Component)]
+struct CageSegmentCover(usize);
+
+pub fn spawn_cage(
+    commands: &mut Commands,
+    materials: &mut ResMut<Assets<ColorMaterial>>,
+    meshes: &mut ResMut<Assets<Mesh>>,
+    translation: Vec3,
+    cage: Cage,
+) -> Entity {
+    let wall_mesh = meshes.add(cage.mesh(0.0));
+    let interior_mesh = meshes.add(cage.mesh(-cage.wall_thickness));
+
+    commands
+        .spawn((
+            SpatialBundle::from_transform(Transform::from_translation(translation)),
+            cage,
+            CageVelocity::default(),
+        ))
+        .with_children(|parent| {
+            // Cage outside
+            parent.spawn((
+                MaterialMesh2dBundle {
+                    mesh: wall_mesh.into(),
+                    // Coloured in from the theme by `apply_cage_theme`, like the interior and gap.
+                    material: materials.add(ColorMaterial::default()),
+                    ..Default::default()
+                },
+                CageWall,
+            ));
+
+            // Cage inside
+            parent.spawn((
+                MaterialMesh2dBundle {
+                    mesh: interior_mesh.into(),
+                    transform: Transform {
+                        translation: Vec3::new(0.0, 0.0, 0.1),
+                        ..Default::default()
+                    },
+                    material: materials.add(ColorMaterial::default()),
+                    ..Default::default()
+                },
+                CageInterior,
+            ));
+
+            parent.spawn((
+                MaterialMesh2dBundle {
+                    transform: Transform {
+                        translation: Vec3::new(0.0, 0.0, 0.05),
+                        ..Default::default()
+                    },
+                    material: materials.add(ColorMaterial::default()),
+                    visibility: Visibility::Hidden,
+                    ..Default::default()
+                },
+                CageGapCover,
+            ));
+        })
+        .id()
+}
+
+pub fn resize_cage(
+    actions: Actions,
+    mut cage_query: Query<&mut Cage, Without<NestedIn>>,
+    sleeping_query: Query<Entity, With<Sleeping>>,
+    mut commands: Commands,
+    time: Res<Time>,
+) {
+    let mut direction = 0.0;
+    if actions.pressed(Action::ShrinkCages) {
+        direction -= 1.0;
+    }
+    if actions.pressed(Action::GrowCages) {
+        direction += 1.0;
+    }
+    if direction == 0.0 {
+        return;
+    }
+
+    for mut cage in &mut cage_query {
+        cage.radius = (cage.radius + direction * CAGE_RESIZE_SPEED * time.delta_seconds())
+            .clamp(CAGE_MIN_RADIUS, CAGE_MAX_RADIUS);
+    }
+    wake_all(&mut commands, &sleeping_query);
+}
+
+// A moving wall has to be able to push settled balls around.
+pub fn wake_all(commands: &mut Commands, sleeping_query: &Query<Entity, With<Sleeping>>) {
+    for entity in sleeping_query {
+        commands.entity(entity).remove::<Sleeping>();
+    }
+}
+
+/// Places an extra cage centered on the cursor with N.
+pub fn spawn_cage_at_cursor(
+    actions: Actions,
+    window_query: Query<&Window, With<PrimaryWindow>>,
+    camera_query: Query<(&Camera, &GlobalTransform)>,
+    mut commands: Commands,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+) {
+    if !actions.just_pressed(Action::SpawnCage) {
+        return;
+    }
+    let (Ok(window), Ok((camera, camera_transform))) =
+        (window_query.get_single(), camera_query.get_single())
+    else {
+        return;
+    };
+    let Some(position) = cursor_world_position(window, camera, camera_transform) else {
+        return;
+    };
+
+    spawn_cage(
+        &mut commands,
+        &mut materials,
+        &mut meshes,
+        position.extend(0.0),
+        Cage::new(CAGE_RADIUS),
+    );
+}
+
+/// Places a smaller cage with a portal inside every cage with I, or removes them again.
+#[allow(clippy::too_many_arguments)]
+pub fn toggle_nested_cages(
+    actions: Actions,
+    nested_query: Query<(Entity, &NestedIn)>,
+    cage_query: Query<(Entity, &Cage, &Transform), Without<NestedIn>>,
+    mut ball_query: Query<&mut InCage, With<Ball>>,
+    mut commands: Commands,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    settings: Res<Settings>,
+) {
+    if !actions.just_pressed(Action::ToggleNestedCages) {
+        return;
+    }
+    if !nested_query.is_empty() {
+        for (entity, nested_in) in &nested_query {
+            // Hand the balls back to the surrounding cage.
+            for mut in_cage in &mut ball_query {
+                if in_cage.0 == entity {
+                    in_cage.0 = nested_in.0;
+                }
+            }
+            commands.entity(entity).despawn_recursive();
+        }
+        return;
+    }
+
+    for (entity, cage, cage_transform) in &cage_query {
+        let nested = spawn_cage(
+            &mut commands,
+            &mut materials,
+            &mut meshes,
+            cage_transform.translation.truncate().extend(NESTED_CAGE_Z),
+            Cage {
+                gap: Some(CageGap {
+                    direction: FRAC_PI_2,
+                    width: settings.cage_gap_width,
+                }),
+                wall_thickness: NESTED_CAGE_WALL_THICKNESS,
+                ..Cage::new(cage.radius * NESTED_CAGE_SCALE)
+            },
+        );
+        commands.entity(nested).insert(NestedIn(entity));
+    }
+}
+
+/// Moves balls that made it all the way through a nested cage's portal into that cage.
+#[allow(clippy::type_complexity)]
+#[cfg_attr(feature = "rapier", allow(dead_code))]
+pub fn transfer_through_portals(
+    mut ball_query: Query<(&Transform, &Radius, &mut InCage), With<Ball>>,
+    nested_query: Query<(Entity, &Cage, &Transform, &NestedIn), Without<Ball>>,
+) {
+    for (transform, radius, mut in_cage) in &mut ball_query {
+        let position = transform.translation.truncate();
+        for (entity, cage, cage_transform, nested_in) in &nested_query {
+            if nested_in.0 == in_cage.0 && cage.contains(cage_transform, position, radius.0) {
+                in_cage.0 = entity;
+            }
+        }
+    }
+}
+
+pub fn cycle_cage_shape(
+    actions: Actions,
+    mut cage_query: Query<(&mut Cage, &mut Transform)>,
+    arena_handles: Res<ArenaHandles>,
+    arenas: Res<Assets<Arena>>,
+) {
+    if !actions.just_pressed(Action::CycleCageShape) {
+        return;
+    }
+
+    let mut shapes = CageShape::BUILT_IN.to_vec();
+    shapes.extend(
+        arena_handles
+            .0
+            .iter()
+            .filter_map(|handle| arenas.get(handle))
+            .map(|arena| CageShape::Custom(arena.vertices())),
+    );
+
+    for (mut cage, mut transform) in &mut cage_query {
+        let index = shapes
+            .iter()
+            .position(|shape| *shape == cage.shape)
+            .unwrap_or(0);
+        cage.shape = shapes[(index + 1) % shapes.len()].clone();
+        // Undo any spinning, only regular polygons are meant to rotate.
+        transform.rotation = Quat::IDENTITY;
+    }
+}
+
+/// Opens or closes a gap at the bottom of every cage with E.
+pub fn toggle_cage_gap(
+    actions: Actions,
+    // Nested cages always keep their portal.
+    mut cage_query: Query<&mut Cage, Without<NestedIn>>,
+    settings: Res<Settings>,
+) {
+    if !actions.just_pressed(Action::ToggleCageGap) {
+        return;
+    }
+    for mut cage in &mut cage_query {
+        cage.gap = match cage.gap {
+            Some(_) => None,
+            None => Some(CageGap {
+                direction: -FRAC_PI_2,
+                width: settings.cage_gap_width,
+            }),
+        };
+    }
+}
+
+/// Splits the wall of every cage into breakable segments with W, or makes them solid again.
+pub fn toggle_breakable_cages(
+    actions: Actions,
+    // Nested cages only ever open up at their portal.
+    cage_query: Query<(Entity, &Cage, Has<CageSegments>), Without<NestedIn>>,
+    cover_query: Query<Entity, With<CageSegmentCover>>,
+    mut commands: Commands,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+) {
+    if !actions.just_pressed(Action::ToggleBreakableCages) {
+        return;
+    }
+    if cage_query.iter().any(|(_, _, breakable)| breakable) {
+        for (entity, _, _) in &cage_query {
+            commands.entity(entity).remove::<CageSegments>();
+        }
+        for cover in &cover_query {
+            commands.entity(cover).despawn_recursive();
+        }
+        return;
+    }
+
+    for (entity, cage, _) in &cage_query {
+        let segments = CageSegments::new();
+        commands.entity(entity).with_children(|parent| {
+            for index in 0..segments.hit_points.len() {
+                parent.spawn((
+                    MaterialMesh2dBundle {
+                        mesh: meshes
+                            .add(sector_mesh(
+                                cage.bounding_radius() + 1.0,
+                                segments.arc(index),
+                            ))
+                            .into(),
+                        // Below the gap cover, so the gap still looks open.
+                        transform: Transform {
+                            translation: Vec3::new(0.0, 0.0, 0.04),
+                            ..Default::default()
+                        },
+                        // Coloured in once it's damaged.
+                        material: materials.add(ColorMaterial::default()),
+                        visibility: Visibility::Hidden,
+                        ..Default::default()
+                    },
+                    CageSegmentCover(index),
+                ));
+            }
+        });
+        commands.entity(entity).insert(segments);
+    }
+}
+
+/// Tints damaged segments towards [`CAGE_CRACK_COLOR`], and hides broken ones.
+#[allow(clippy::type_complexity)]
+pub fn update_cage_damage(
+    cage_query: Query<(Ref<Cage>, Ref<CageSegments>, &Children)>,
+    mut cover_query: Query<(
+        &CageSegmentCover,
+        &mut Mesh2dHandle,
+        &Handle<ColorMaterial>,
+        &mut Visibility,
+    )>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    theme: Res<Theme>,
+) {
+    for (cage, segments, children) in &cage_query {
+        if !cage.is_changed() && !segments.is_changed() && !theme.is_changed() {
+            continue;
+        }
+        for &child in children.iter() {
+            let Ok((cover, mut mesh, material, mut visibility)) = cover_query.get_mut(child) else {
+                continue;
+            };
+            if cage.is_changed() {
+                *mesh = meshes
+                    .add(sector_mesh(
+                        cage.bounding_radius() + 1.0,
+                        segments.arc(cover.0),
+                    ))
+                    .into();
+            }
+
+            let hit_points = segments.hit_points[cover.0];
+            if hit_points == CAGE_SEGMENT_HIT_POINTS {
+                *visibility = Visibility::Hidden;
+                continue;
+            }
+            *visibility = Visibility::Inherited;
+            let Some(material) = materials.get_mut(material) else {
+                continue;
+            };
+            material.color = if hit_points == 0 {
+                theme.background
+            } else {
+                let damage = 1.0 - hit_points as f32 / CAGE_SEGMENT_HIT_POINTS as f32;
+                let intact = theme.cage;
+                Color::rgb(
+                    intact.r() + (CAGE_CRACK_COLOR.r() - intact.r()) * damage,
+                    intact.g() + (CAGE_CRACK_COLOR.g() - intact.g()) * damage,
+                    intact.b() + (CAGE_CRACK_COLOR.b() - intact.b()) * damage,
+                )
+            };
+        }
+    }
+}
+
+/// Paints new cages in the current theme, and every cage again when the theme changes.
+#[allow(clippy::type_complexity)]
+pub fn apply_cage_theme(
+    theme: Res<Theme>,
+    wall_query: Query<Ref<Handle<ColorMaterial>>, With<CageWall>>,
+    interior_query: Query<Ref<Handle<ColorMaterial>>, Or<(With<CageInterior>, With<CageGapCover>)>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+) {
+    let walls = wall_query.iter().map(|material| (material, theme.cage));
+    let interiors = interior_query
+        .iter()
+        .map(|material| (material, theme.background));
+    for (material, color) in walls.chain(interiors) {
+        if !theme.is_changed() && !material.is_added() {
+            continue;
+        }
+        if let Some(material) = materials.get_mut(&*material) {
+            material.color = color;
+        }
+    }
+}
+
+/// Drags the cage closest to the cursor towards it while Shift and the middle mouse button are
+/// held. Nested cages come along with the cage they're in.
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+#[cfg_attr(feature = "rapier", allow(dead_code))]
+pub fn follow_cursor(
+    mouse_input: Res<ButtonInput<MouseButton>>,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    window_query: Query<&Window, With<PrimaryWindow>>,
+    camera_query: Query<(&Camera, &GlobalTransform)>,
+    mut cage_query: Query<
+        (Entity, &mut Transform, &mut CageVelocity),
+        (With<Cage>, Without<NestedIn>, Without<Ball>),
+    >,
+    mut nested_query: Query<(&mut Transform, &mut CageVelocity, &NestedIn), Without<Ball>>,
+    sleeping_query: Query<Entity, With<Sleeping>>,
+    mut commands: Commands,
+    time: Res<Time>,
+) {
+    let dragging = mouse_input.pressed(MouseButton::Middle)
+        && keyboard_input.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+    let target = if dragging {
+        match (window_query.get_single(), camera_query.get_single()) {
+            (Ok(window), Ok((camera, camera_transform))) => {
+                cursor_world_position(window, camera, camera_transform)
+            }
+            _ => None,
+        }
+    } else {
+        None
+    };
+    let dragged = target.and_then(|target| {
+        cage_query
+            .iter()
+            .min_by(|(_, a, _), (_, b, _)| {
+                let a = a.translation.truncate().distance_squared(target);
+                let b = b.translation.truncate().distance_squared(target);
+                a.total_cmp(&b)
+            })
+            .map(|(entity, _, _)| entity)
+    });
+
+    for (entity, mut transform, mut velocity) in &mut cage_query {
+        velocity.0 = match target {
+            Some(target) if dragged == Some(entity) => {
+                ((target - transform.translation.truncate()) * CAGE_FOLLOW_STIFFNESS)
+                    .clamp_length_max(CAGE_MAX_FOLLOW_SPEED)
+            }
+            _ => Vec2::ZERO,
+        };
+        transform.translation += (velocity.0 * time.delta_seconds()).extend(0.0);
+    }
+    for (mut transform, mut velocity, nested_in) in &mut nested_query {
+        velocity.0 = cage_query
+            .get(nested_in.0)
+            .map_or(Vec2::ZERO, |(_, _, outer_velocity)| outer_velocity.0);
+        transform.translation += (velocity.0 * time.delta_seconds()).extend(0.0);
+    }
+
+    if dragged.is_some() {
+        wake_all(&mut commands, &sleeping_query);
+    }
+}
+
+#[cfg_attr(feature = "rapier", allow(dead_code))]
+pub fn rotate_cages(
+    mut cage_query: Query<(&Cage, &mut Transform)>,
+    settings: Res<Settings>,
+    time: Res<Time>,
+) {
+    for (cage, mut transform) in &mut cage_query {
+        if let CageShape::Polygon { .. } = cage.shape {
+            transform.rotate_z(settings.cage_angular_velocity * time.delta_seconds());
+        }
+    }
+}
+
+pub fn toggle_shrinking_cage(actions: Actions, mut shrinking_cage: ResMut<ShrinkingCage>) {
+    if actions.just_pressed(Action::ToggleShrinkingCage) {
+        shrinking_cage.active = !shrinking_cage.active;
+    }
+}
+
+#[allow(clippy::type_complexity)]
+pub fn shrink_cage(
+    shrinking_cage: Res<ShrinkingCage>,
+    mut cage_query: Query<(Entity, &mut Cage, &Transform), (Without<NestedIn>, Without<Ball>)>,
+    sleeping_query: Query<(Entity, &Transform, &Radius, &InCage), (With<Ball>, With<Sleeping>)>,
+    mut commands: Commands,
+    time: Res<Time>,
+) {
+    if !shrinking_cage.active {
+        return;
+    }
+
+    for (cage_entity, mut cage, cage_transform) in &mut cage_query {
+        let radius = (cage.radius - CAGE_SHRINK_SPEED * time.delta_seconds()).max(CAGE_MIN_RADIUS);
+        if radius == cage.radius {
+            continue;
+        }
+        cage.radius = radius;
+        // Only the balls the wall has closed in on need to wake up and get pushed.
+        let center = cage_transform.translation.truncate();
+        for (entity, transform, ball_radius, in_cage) in &sleeping_query {
+            let distance = transform.translation.truncate().distance(center);
+            if in_cage.0 == cage_entity && distance + ball_radius.0 >= radius {
+                commands.entity(entity).remove::<Sleeping>();
+            }
+        }
+    }
+}
+
+/// Ends a shrinking run with a game over once the balls cover too much of the cage, like
+/// [`end_run_when_saturated`] does for other runs. The cage is set back to its full size for the
+/// next one.
+pub fn check_cage_pressure(
+    mut shrinking_cage: ResMut<ShrinkingCage>,
+    mut cage_query: Query<(Entity, &mut Cage)>,
+    ball_query: Query<(&Radius, &InCage), With<Ball>>,
+    mut next_state: ResMut<NextState<AppState>>,
+) {
+    if !shrinking_cage.active {
+        return;
+    }
+
+    let ball_areas = ball_areas(ball_query.iter());
+    let crowded_cage = cage_query.iter().find(|(entity, cage)| {
+        let ball_area = ball_areas.get(entity).copied().unwrap_or(0.0);
+        ball_area / cage.area() > CAGE_MAX_PRESSURE
+    });
+
+    if let Some((_, cage)) = crowded_cage {
+        info!("The cage got too crowded at a radius of {:.0}", cage.radius);
+        shrinking_cage.active = false;
+        for (_, mut cage) in &mut cage_query {
+            cage.radius = CAGE_RADIUS;
+        }
+        next_state.set(AppState::GameOver);
+    }
+}
+
+/// Ends the run once the balls cover too much of any cage. Shrinking runs end their own way.
+pub fn end_run_when_saturated(
+    shrinking_cage: Res<ShrinkingCage>,
+    cage_query: Query<(Entity, &Cage)>,
+    ball_query: Query<(&Radius, &InCage), With<Ball>>,
+    mut next_state: ResMut<NextState<AppState>>,
+) {
+    if shrinking_cage.active {
+        return;
+    }
+    let ball_areas = ball_areas(ball_query.iter());
+    let saturated = cage_query.iter().any(|(entity, cage)| {
+        let ball_area = ball_areas.get(&entity).copied().unwrap_or(0.0);
+        ball_area / cage.area() > CAGE_SATURATION
+    });
+    if saturated {
+        next_state.set(AppState::GameOver);
+    }
+}
+
+/// The total area of the balls in each cage.
+fn ball_areas<'a>(balls: impl Iterator<Item = (&'a Radius, &'a InCage)>) -> HashMap<Entity, f32> {
+    let mut ball_areas: HashMap<Entity, f32> = HashMap::new();
+    for (radius, in_cage) in balls {
+        *ball_areas.entry(in_cage.0).or_default() += PI * radius.0.powi(2);
+    }
+    ball_areas
+}
+
+#[allow(clippy::type_complexity)]
+pub fn update_cage_meshes(
+    cage_query: Query<(&Cage, &Children), Changed<Cage>>,
+    mut part_query: Query<
+        (&mut Mesh2dHandle, Has<CageWall>),
+        (
+            Or<(With<CageWall>, With<CageInterior>)>,
+            Without<CageGapCover>,
+        ),
+    >,
+    mut gap_cover_query: Query<(&mut Mesh2dHandle, &mut Visibility), With<CageGapCover>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+) {
+    for (cage, children) in &cage_query {
+        for &child in children.iter() {
+            if let Ok((mut mesh, mut visibility)) = gap_cover_query.get_mut(child) {
+                if let Some(gap) = cage.gap {
+                    *mesh = meshes
+                        .add(sector_mesh(cage.bounding_radius() + 1.0, gap))
+                        .into();
+                    *visibility = Visibility::Inherited;
+                } else {
+                    *visibility = Visibility::Hidden;
+                }
+                continue;
+            }
+
+            let Ok((mut mesh, is_wall)) = part_query.get_mut(child) else {
+                continue;
+            };
+            let offset = if is_wall { 0.0 } else { -cage.wall_thickness };
+            // Replacing the handle drops the old mesh once nothing else uses it.
+            *mesh = meshes.add(cage.mesh(offset)).into();
+        }
+    }
+}
+
+#[allow(clippy::type_complexity)]
+#[cfg_attr(feature = "rapier", allow(dead_code))]
+pub fn collide_cage(
+    mut ball_query: Query<
+        (
+            Entity,
+            &mut Transform,
+            &mut Velocity,
+            &mut Spin,
+            &Radius,
+            &BallKind,
+            &Collision,
+            &InCage,
+        ),
+        (With<Ball>, Without<Sleeping>),
+    >,
+    cage_query: Query<(&Cage, &Transform, &CageVelocity), Without<Ball>>,
+    nested_query: Query<(&Cage, &Transform, &CageVelocity, &NestedIn), Without<Ball>>,
+    mut segments_query: Query<&mut CageSegments>,
+    mut collision_events: EventWriter<CageCollisionEvent>,
+    settings: Res<Settings>,
+) {
+    for (entity, mut ball_transform, mut ball_velocity, mut spin, radius, kind, _, in_cage) in
+        &mut ball_query
+    {
+        let restitution = kind.restitution(settings.restitution);
+        let Ok((cage, cage_transform, cage_velocity)) = cage_query.get(in_cage.0) else {
+            continue;
+        };
+        let mut segments = segments_query.get_mut(in_cage.0).ok();
+        let ball_radius = radius.0;
+
+        let contact = cage
+            .wall_contact(
+                cage_transform,
+                ball_transform.translation.truncate(),
+                ball_radius,
+            )
+            .filter(|contact| {
+                !segments.as_ref().is_some_and(|segments| {
+                    segments.is_broken(local_angle(cage_transform, contact.point))
+                })
+            });
+        if let Some(contact) = contact {
+            let impact_speed = bounce_off_wall(
+                &mut ball_transform,
+                &mut ball_velocity,
+                &contact,
+                cage_transform,
+                cage_velocity.0,
+                cage.angular_velocity(&settings),
+                restitution,
+            );
+            if impact_speed > CAGE_SEGMENT_DAMAGE_SPEED {
+                if let Some(segments) = &mut segments {
+                    segments.damage(local_angle(cage_transform, contact.point));
+                }
+            }
+            // Roll along the wall without slipping.
+            spin.0 = ball_velocity.0.dot(contact.normal.perp()) / ball_radius;
+
+            collision_events.send(CageCollisionEvent {
+                entity,
+                impact_speed,
+                point: contact.point,
+                normal: contact.normal,
+            });
+        }
+
+        // Nested cages are walls on the inside of this one.
+        for (nested, nested_transform, nested_velocity, nested_in) in &nested_query {
+            if nested_in.0 != in_cage.0 {
+                continue;
+            }
+            let Some(contact) = nested.outer_wall_contact(
+                nested_transform,
+                ball_transform.translation.truncate(),
+                ball_radius,
+            ) else {
+                continue;
+            };
+            let impact_speed = bounce_off_wall(
+                &mut ball_transform,
+                &mut ball_velocity,
+                &contact,
+                nested_transform,
+                nested_velocity.0,
+                nested.angular_velocity(&settings),
+                restitution,
+            );
+
+            collision_events.send(CageCollisionEvent {
+                entity,
+                impact_speed,
+                point: contact.point,
+                normal: contact.normal,
+            });
+        }
+    }
+}
+
+/// Pushes a ball out of a cage wall and reflects its velocity, returning how fast it hit the wall.
+fn bounce_off_wall(
+    ball_transform: &mut Transform,
+    ball_velocity: &mut Velocity,
+    contact: &WallContact,
+    wall_transform: &Transform,
+    linear_velocity: Vec2,
+    angular_velocity: f32,
+    restitution: f32,
+) -> f32 {
+    // Bounce relative to the wall, so a spinning or dragged cage flings balls along with it.
+    let wall_center = wall_transform.translation.truncate();
+    let wall_velocity = linear_velocity + angular_velocity * (contact.point - wall_center).perp();
+    let approach = (ball_velocity.0 - wall_velocity).dot(contact.normal);
+    if approach < 0.0 {
+        ball_velocity.0 -= (1.0 + restitution) * approach * contact.normal;
+    }
+
+    ball_transform.translation += (contact.overlap * contact.normal).extend(0.0);
+
+    (-approach).max(0.0)
+}
+
+#[allow(clippy::type_complexity)]
+#[cfg_attr(feature = "rapier", allow(dead_code))]
+pub fn detect_escaped_balls(
+    ball_query: Query<(Entity, &Transform, &Radius, &InCage), With<Ball>>,
+    cage_query: Query<(&Cage, &Transform, Option<&NestedIn>), Without<Ball>>,
+    mut escaped_events: EventWriter<BallEscapedEvent>,
+    mut commands: Commands,
+    settings: Res<Settings>,
+) {
+    for (entity, transform, radius, in_cage) in &ball_query {
+        let Ok((cage, cage_transform, nested_in)) = cage_query.get(in_cage.0) else {
+            continue;
+        };
+        if !cage.has_escaped(cage_transform, transform.translation.truncate(), radius.0) {
+            continue;
+        }
+
+        // Leaving a nested cage through its portal just puts the ball back in the outer one.
+        if let Some(nested_in) = nested_in {
+            commands.entity(entity).insert(InCage(nested_in.0));
+            continue;
+        }
+
+        escaped_events.send(BallEscapedEvent { cage: in_cage.0 });
+        if settings.despawn_escaped_balls {
+            commands.entity(entity).despawn();
+        } else {
+            // Free the ball, so it stops colliding with anything in its old cage.
+            commands.entity(entity).remove::<InCage>();
+        }
+    }
+}
+
+/// Puts a ball that escaped into whichever cage it falls into next, or despawns it once it's
+/// fallen [`ESCAPED_BALL_RANGE`] past every cage, so it doesn't keep counting towards the cap on
+/// balls.
+#[allow(clippy::type_complexity)]
+#[cfg_attr(feature = "rapier", allow(dead_code))]
+pub fn catch_escaped_balls(
+    ball_query: Query<(Entity, &Transform), (With<Ball>, Without<InCage>)>,
+    cage_query: Query<(Entity, &Cage, &Transform, Has<NestedIn>)>,
+    mut commands: Commands,
+) {
+    for (entity, transform) in &ball_query {
+        let position = transform.translation.truncate();
+        if let Some(cage) = cage_at(&cage_query, position) {
+            commands.entity(entity).insert(InCage(cage));
+            continue;
+        }
+        let out_of_reach = cage_query.iter().all(|(_, cage, cage_transform, _)| {
+            let distance = position.distance(cage_transform.translation.truncate());
+            distance > cage.bounding_radius() + ESCAPED_BALL_RANGE
+        });
+        if out_of_reach {
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::RunSystemOnce;
+
+    use super::*;
+
+    fn spawn_test_cage(world: &mut World, position: Vec2) -> Entity {
+        world
+            .spawn((
+                Cage::new(CAGE_RADIUS),
+                Transform::from_translation(position.extend(0.0)),
+            ))
+            .id()
+    }
+
+    fn spawn_free_ball(world: &mut World, position: Vec2) -> Entity {
+        world
+            .spawn((Ball, Transform::from_translation(position.extend(0.0))))
+            .id()
+    }
+
+    #[test]
+    fn escaped_ball_is_caught_by_the_cage_it_falls_into() {
+        let mut world = World::new();
+        spawn_test_cage(&mut world, Vec2::ZERO);
+        let lower_cage = spawn_test_cage(&mut world, Vec2::new(0.0, -500.0));
+        let ball = spawn_free_ball(&mut world, Vec2::new(0.0, -500.0));
+
+        world.run_system_once(catch_escaped_balls);
+
+        assert_eq!(
+            world.get::<InCage>(ball).map(|in_cage| in_cage.0),
+            Some(lower_cage)
+        );
+    }
+
+    #[test]
+    fn escaped_ball_is_despawned_once_out_of_reach() {
+        let mut world = World::new();
+        spawn_test_cage(&mut world, Vec2::ZERO);
+        let falling = spawn_free_ball(&mut world, Vec2::new(0.0, -500.0));
+        let gone = spawn_free_ball(
+            &mut world,
+            Vec2::new(0.0, -(CAGE_RADIUS + ESCAPED_BALL_RANGE + 1.0)),
+        );
+
+        world.run_system_once(catch_escaped_balls);
+
+        assert!(world.get_entity(falling).is_some());
+        assert!(world.get::<InCage>(falling).is_none());
+        assert!(world.get_entity(gone).is_none());
+    }
+
+    // An L, counter-clockwise, with the notch at the top right.
+    const L_OUTLINE: [Vec2; 6] = [
+        Vec2::new(0.0, 0.0),
+        Vec2::new(2.0, 0.0),
+        Vec2::new(2.0, 1.0),
+        Vec2::new(1.0, 1.0),
+        Vec2::new(1.0, 2.0),
+        Vec2::new(0.0, 2.0),
+    ];
+
+    fn clockwise(vertices: &[Vec2]) -> Vec<Vec2> {
+        vertices.iter().rev().copied().collect()
+    }
+
+    fn mesh_triangles(mesh: &Mesh, vertices: &[Vec2]) -> Vec<[Vec2; 3]> {
+        let indices: Vec<usize> = mesh.indices().unwrap().iter().collect();
+        indices
+            .chunks_exact(3)
+            .map(|triangle| triangle.iter().map(|&i| vertices[i]).collect::<Vec<_>>())
+            .map(|triangle| [triangle[0], triangle[1], triangle[2]])
+            .collect()
+    }
+
+    fn is_on_ellipse(half_size: Vec2, point: Vec2) -> bool {
+        ((point / half_size).length_squared() - 1.0).abs() < 1e-3
+    }
+
+    /// The distance to the closest of many points spread around the ellipse.
+    fn sampled_distance_to_ellipse(half_size: Vec2, point: Vec2) -> f32 {
+        (0..10_000)
+            .map(|i| Vec2::from_angle(i as f32 * TAU / 10_000.0) * half_size)
+            .map(|on_ellipse| on_ellipse.distance(point))
+            .fold(f32::MAX, f32::min)
+    }
+
+    #[test]
+    fn closest_point_on_circle_is_along_the_radius() {
+        let closest = closest_point_on_ellipse(Vec2::splat(10.0), Vec2::new(3.0, 4.0));
+        assert!(closest.abs_diff_eq(Vec2::new(6.0, 8.0), 1e-3));
+    }
+
+    #[test]
+    fn closest_point_from_the_center_is_on_the_ellipse() {
+        let closest = closest_point_on_ellipse(Vec2::splat(10.0), Vec2::ZERO);
+        assert!((closest.length() - 10.0).abs() < 1e-3);
+
+        let closest = closest_point_on_ellipse(Vec2::new(20.0, 10.0), Vec2::ZERO);
+        assert!(closest.abs_diff_eq(Vec2::new(0.0, 10.0), 1e-3));
+        let closest = closest_point_on_ellipse(Vec2::new(10.0, 20.0), Vec2::ZERO);
+        assert!(closest.abs_diff_eq(Vec2::new(10.0, 0.0), 1e-3));
+    }
+
+    #[test]
+    fn closest_point_on_ellipse_matches_sampling() {
+        let half_size = Vec2::new(20.0, 10.0);
+        for point in [
+            Vec2::new(30.0, 30.0),
+            Vec2::new(-12.0, 3.0),
+            Vec2::new(19.0, 1.0),
+            Vec2::new(10.0, -25.0),
+            Vec2::new(0.0, 5.0),
+        ] {
+            let closest = closest_point_on_ellipse(half_size, point);
+            assert!(is_on_ellipse(half_size, closest), "{point} -> {closest}");
+            let expected = sampled_distance_to_ellipse(half_size, point);
+            assert!(
+                (closest.distance(point) - expected).abs() < 1e-2,
+                "{point} -> {closest}"
+            );
+        }
+    }
+
+    #[test]
+    fn closest_point_on_flattened_ellipse_is_on_the_segment() {
+        let half_size = Vec2::new(20.0, 0.0);
+        let closest = closest_point_on_ellipse(half_size, Vec2::new(5.0, 3.0));
+        assert_eq!(closest, Vec2::new(5.0, 0.0));
+        let closest = closest_point_on_ellipse(half_size, Vec2::new(30.0, -2.0));
+        assert_eq!(closest, Vec2::new(20.0, 0.0));
+    }
+
+    #[test]
+    fn polygon_contains_handles_concave_outlines_either_way_round() {
+        for vertices in [L_OUTLINE.to_vec(), clockwise(&L_OUTLINE)] {
+            assert!(polygon_contains(&vertices, Vec2::new(0.5, 0.5)));
+            assert!(polygon_contains(&vertices, Vec2::new(1.5, 0.5)));
+            assert!(polygon_contains(&vertices, Vec2::new(0.5, 1.5)));
+            // In the notch.
+            assert!(!polygon_contains(&vertices, Vec2::new(1.5, 1.5)));
+            assert!(!polygon_contains(&vertices, Vec2::new(3.0, 0.5)));
+            assert!(!polygon_contains(&vertices, Vec2::new(-1.0, 1.0)));
+        }
+    }
+
+    #[test]
+    fn polygon_mesh_covers_concave_outlines_either_way_round() {
+        for vertices in [L_OUTLINE.to_vec(), clockwise(&L_OUTLINE)] {
+            let triangles = mesh_triangles(&polygon_mesh(&vertices), &vertices);
+            assert_eq!(triangles.len(), vertices.len() - 2);
+            // Every triangle is counter-clockwise, and together they cover the outline exactly.
+            let areas: Vec<f32> = triangles
+                .iter()
+                .map(|triangle| signed_area(triangle))
+                .collect();
+            assert!(areas.iter().all(|&area| area > 0.0), "{areas:?}");
+            assert!((areas.iter().sum::<f32>() - 3.0).abs() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn polygon_mesh_of_a_triangle_is_itself() {
+        let vertices = [Vec2::ZERO, Vec2::X, Vec2::Y];
+        let triangles = mesh_triangles(&polygon_mesh(&vertices), &vertices);
+        assert_eq!(triangles, vec![vertices]);
+    }
+
+    #[test]
+    fn polygon_vertices_are_counter_clockwise_and_offset_along_the_edges() {
+        let cage = Cage {
+            shape: CageShape::Polygon { sides: 6 },
+            ..Cage::new(100.0)
+        };
+        let vertices = cage.vertices(0.0);
+        assert_eq!(vertices.len(), 6);
+        assert!(vertices
+            .iter()
+            .all(|vertex| (vertex.length() - 100.0).abs() < 1e-3));
+        assert!(signed_area(&vertices) > 0.0);
+
+        // Pushing the corners out moves every edge out by the offset.
+        let grown = cage.vertices(5.0);
+        let apothem = (grown[0] + grown[1]).length() / 2.0;
+        assert!((apothem - (100.0 * (PI / 6.0).cos() + 5.0)).abs() < 1e-3);
+    }
+
+    #[test]
+    fn rect_vertices_are_counter_clockwise() {
+        let cage = Cage {
+            shape: CageShape::Rect { aspect_ratio: 1.5 },
+            ..Cage::new(10.0)
+        };
+        let vertices = cage.vertices(1.0);
+        assert!(vertices
+            .iter()
+            .all(|vertex| vertex.abs().abs_diff_eq(Vec2::new(16.0, 11.0), 1e-5)));
+        assert!((signed_area(&vertices) - 32.0 * 22.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn custom_vertices_offset_around_concave_corners() {
+        let cage = Cage {
+            shape: CageShape::Custom(L_OUTLINE.to_vec()),
+            ..Cage::new(1.0)
+        };
+        assert_eq!(cage.vertices(0.0), L_OUTLINE.to_vec());
+        let grown = cage.vertices(0.1);
+        // The notch's corner moves into the notch, the rest away from the L.
+        assert!(grown[3].abs_diff_eq(Vec2::new(1.1, 1.1), 1e-5));
+        assert!(grown[0].abs_diff_eq(Vec2::new(-0.1, -0.1), 1e-5));
+        // The L's area, plus its perimeter times the offset, plus a square for each convex corner
+        // less one for the concave one.
+        assert!((signed_area(&grown) - (3.0 + 8.0 * 0.1 + 4.0 * 0.01)).abs() < 1e-4);
+    }
+
+    #[test]
+    fn round_cages_have_no_vertices() {
+        assert!(Cage::new(100.0).vertices(0.0).is_empty());
+        let ellipse = Cage {
+            shape: CageShape::Ellipse { aspect_ratio: 1.6 },
+            ..Cage::new(100.0)
+        };
+        assert!(ellipse.vertices(0.0).is_empty());
+    }
+}
@@ -0,0 +1,166 @@
+use bevy::{
+    input::mouse::{MouseMotion, MouseScrollUnit, MouseWheel},
+    prelude::*,
+    window::PrimaryWindow,
+};
+
+use crate::{
+    cage::CAGE_RADIUS,
+    cursor_world_position,
+    keybindings::{Action, Actions},
+};
+
+/// The part of the world that's kept in view whatever the window's size and shape: room for the
+/// two cages of two-player mode side by side, with a margin around them.
+const ARENA_VIEW_SIZE: Vec2 = Vec2::new(7.0 * CAGE_RADIUS, 3.5 * CAGE_RADIUS);
+// How much closer each notch of the mouse wheel zooms in.
+const ZOOM_STEP: f32 = 1.2;
+// Touchpads scroll in pixels rather than notches. This many make up one.
+const PIXELS_PER_NOTCH: f32 = 50.0;
+// Compared to the default framing. Smaller is closer.
+const MIN_ZOOM: f32 = 0.05;
+const MAX_ZOOM: f32 = 4.0;
+
+/// How far the camera is zoomed out.
+#[derive(Resource)]
+pub struct CameraZoom {
+    /// The scale that fits the arena to the window.
+    fit: f32,
+    /// Set with the mouse wheel or by pinching, on top of `fit`.
+    zoom: f32,
+}
+
+impl Default for CameraZoom {
+    fn default() -> Self {
+        Self {
+            fit: 1.0,
+            zoom: 1.0,
+        }
+    }
+}
+
+impl CameraZoom {
+    fn scale(&self) -> f32 {
+        self.fit * self.zoom
+    }
+}
+
+/// Zooms the camera so the arena fills as much of the window as it can, before any zooming with
+/// the mouse wheel. Runs at startup, and whenever the window is resized.
+pub fn fit_camera_to_window(
+    window_query: Query<&Window, With<PrimaryWindow>>,
+    mut projection_query: Query<&mut OrthographicProjection>,
+    mut camera_zoom: ResMut<CameraZoom>,
+) {
+    let Ok(window) = window_query.get_single() else {
+        return;
+    };
+    // Minimised windows have no size to fit.
+    let size = Vec2::new(window.width(), window.height());
+    if size.min_element() <= 0.0 {
+        return;
+    }
+    camera_zoom.fit = (ARENA_VIEW_SIZE / size).max_element();
+    for mut projection in &mut projection_query {
+        projection.scale = camera_zoom.scale();
+    }
+}
+
+/// Zooms in and out with the mouse wheel, keeping whatever's under the cursor where it is.
+#[allow(clippy::type_complexity)]
+pub fn zoom_camera(
+    mut wheel_events: EventReader<MouseWheel>,
+    window_query: Query<&Window, With<PrimaryWindow>>,
+    mut camera_query: Query<(
+        &Camera,
+        &GlobalTransform,
+        &mut Transform,
+        &mut OrthographicProjection,
+    )>,
+    mut camera_zoom: ResMut<CameraZoom>,
+) {
+    let notches: f32 = wheel_events
+        .read()
+        .map(|event| match event.unit {
+            MouseScrollUnit::Line => event.y,
+            MouseScrollUnit::Pixel => event.y / PIXELS_PER_NOTCH,
+        })
+        .sum();
+    if notches == 0.0 {
+        return;
+    }
+    let Ok((camera, camera_global_transform, mut camera_transform, mut projection)) =
+        camera_query.get_single_mut()
+    else {
+        return;
+    };
+    let anchor = window_query
+        .get_single()
+        .ok()
+        .and_then(|window| cursor_world_position(window, camera, camera_global_transform));
+    zoom_around(
+        &mut camera_zoom,
+        &mut camera_transform,
+        &mut projection,
+        ZOOM_STEP.powf(-notches),
+        anchor,
+    );
+}
+
+/// Multiplies the zoom by `factor`, keeping the world point `anchor` where it is on screen if
+/// there is one.
+pub fn zoom_around(
+    camera_zoom: &mut CameraZoom,
+    camera_transform: &mut Transform,
+    projection: &mut OrthographicProjection,
+    factor: f32,
+    anchor: Option<Vec2>,
+) {
+    let old_scale = camera_zoom.scale();
+    camera_zoom.zoom = (camera_zoom.zoom * factor).clamp(MIN_ZOOM, MAX_ZOOM);
+    projection.scale = camera_zoom.scale();
+
+    if let Some(anchor) = anchor {
+        let offset = camera_transform.translation.truncate() - anchor;
+        let position = anchor + offset * projection.scale / old_scale;
+        camera_transform.translation = position.extend(camera_transform.translation.z);
+    }
+}
+
+/// Moves the camera along with the mouse while the middle button is held.
+pub fn pan_camera(
+    mouse_input: Res<ButtonInput<MouseButton>>,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut motion_events: EventReader<MouseMotion>,
+    mut camera_query: Query<(&mut Transform, &OrthographicProjection), With<Camera>>,
+) {
+    let motion: Vec2 = motion_events.read().map(|event| event.delta).sum();
+    // Shift+middle drags a cage instead.
+    if !mouse_input.pressed(MouseButton::Middle)
+        || keyboard_input.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight])
+    {
+        return;
+    }
+    for (mut transform, projection) in &mut camera_query {
+        // Screen y points down, world y up.
+        transform.translation.x -= motion.x * projection.scale;
+        transform.translation.y += motion.y * projection.scale;
+    }
+}
+
+/// Goes back to the default framing with Home.
+pub fn reset_camera(
+    actions: Actions,
+    mut camera_query: Query<(&mut Transform, &mut OrthographicProjection), With<Camera>>,
+    mut camera_zoom: ResMut<CameraZoom>,
+) {
+    if !actions.just_pressed(Action::ResetCamera) {
+        return;
+    }
+    camera_zoom.zoom = 1.0;
+    for (mut transform, mut projection) in &mut camera_query {
+        transform.translation.x = 0.0;
+        transform.translation.y = 0.0;
+        projection.scale = camera_zoom.scale();
+    }
+}
@@ -0,0 +1,128 @@
+use std::f32::consts::FRAC_PI_2;
+
+use bevy::prelude::*;
+
+use crate::{
+    ball_assets::BallAssets,
+    cage::{Cage, InCage},
+    keybindings::{Action, Actions},
+    palette::BallPalette,
+    players::Player,
+    rng::SimRng,
+    settings::Settings,
+    spawn_ball, Velocity,
+};
+
+const CANNON_COLOR: Color = Color::rgb(0.9, 0.9, 0.3);
+const CANNON_AIM_COLOR: Color = Color::rgba(0.9, 0.9, 0.3, 0.4);
+const CANNON_BARREL_LENGTH: f32 = 16.0;
+const CANNON_AIM_LENGTH: f32 = 60.0;
+// How far in from the wall the barrel is mounted.
+const CANNON_INSET: f32 = 4.0;
+// In radians per second.
+const CANNON_TURN_SPEED: f32 = 1.5;
+// How far the aim can turn away from straight into the cage, in radians.
+const CANNON_MAX_AIM: f32 = 1.3;
+// In pixels per second.
+const CANNON_MUZZLE_SPEED: f32 = 400.0;
+
+/// A player-aimed ball launcher mounted at the top of the wall of the cage it's in.
+#[derive(Component, Default)]
+pub struct Cannon {
+    /// Angle away from pointing straight into the cage, in radians. Positive is counter-clockwise.
+    pub aim: f32,
+}
+
+impl Cannon {
+    /// Where the barrel is mounted and which way it points, in world space.
+    fn mount(&self, cage: &Cage, cage_transform: &Transform) -> (Vec2, Vec2) {
+        let local_position = Vec2::Y * (cage.radius - cage.wall_thickness - CANNON_INSET);
+        let local_direction = Vec2::from_angle(-FRAC_PI_2 + self.aim);
+        let position = cage_transform
+            .transform_point(local_position.extend(0.0))
+            .truncate();
+        let direction = (cage_transform.rotation * local_direction.extend(0.0)).truncate();
+        (position, direction)
+    }
+}
+
+/// Turns every cannon with A and D. In two-player mode, the second player's turns with the
+/// arrow keys.
+pub fn aim_cannons(
+    actions: Actions,
+    mut cannon_query: Query<(&mut Cannon, &InCage)>,
+    player_query: Query<&Player>,
+    time: Res<Time>,
+) {
+    for (mut cannon, in_cage) in &mut cannon_query {
+        let (left, right, _) = Player::cannon_actions(player_query.get(in_cage.0).ok().copied());
+        let mut direction = 0.0;
+        if actions.pressed(left) {
+            direction -= 1.0;
+        }
+        if actions.pressed(right) {
+            direction += 1.0;
+        }
+        cannon.aim = (cannon.aim + direction * CANNON_TURN_SPEED * time.delta_seconds())
+            .clamp(-CANNON_MAX_AIM, CANNON_MAX_AIM);
+    }
+}
+
+/// Fires a ball out of every cannon with Enter. In two-player mode, each player fires their own
+/// with W or Up.
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+pub fn fire_cannons(
+    actions: Actions,
+    cannon_query: Query<(&Cannon, &InCage)>,
+    cage_query: Query<(&Cage, &Transform, Option<&Player>)>,
+    mut commands: Commands,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut ball_assets: ResMut<BallAssets>,
+    mut rng: ResMut<SimRng>,
+    palette: Res<BallPalette>,
+    settings: Res<Settings>,
+) {
+    for (cannon, in_cage) in &cannon_query {
+        let Ok((cage, cage_transform, player)) = cage_query.get(in_cage.0) else {
+            continue;
+        };
+        let (_, _, fire) = Player::cannon_actions(player.copied());
+        if !actions.just_pressed(fire) {
+            continue;
+        }
+        let (position, direction) = cannon.mount(cage, cage_transform);
+        let ball = spawn_ball(
+            &mut commands,
+            &mut materials,
+            &mut ball_assets,
+            &mut rng,
+            &palette,
+            &settings,
+            in_cage.0,
+            position + direction * CANNON_BARREL_LENGTH,
+        );
+        commands
+            .entity(ball)
+            .insert(Velocity(direction * CANNON_MUZZLE_SPEED));
+    }
+}
+
+pub fn draw_cannons(
+    mut gizmos: Gizmos,
+    cannon_query: Query<(&Cannon, &InCage)>,
+    cage_query: Query<(&Cage, &Transform)>,
+) {
+    for (cannon, in_cage) in &cannon_query {
+        let Ok((cage, cage_transform)) = cage_query.get(in_cage.0) else {
+            continue;
+        };
+        let (position, direction) = cannon.mount(cage, cage_transform);
+        let muzzle = position + direction * CANNON_BARREL_LENGTH;
+        gizmos.line_2d(position, muzzle, CANNON_COLOR);
+        gizmos.arrow_2d(
+            muzzle,
+            muzzle + direction * CANNON_AIM_LENGTH,
+            CANNON_AIM_COLOR,
+        );
+    }
+}
@@ -0,0 +1,230 @@
+use bevy::{prelude::*, utils::HashSet};
+
+use crate::{
+    cage::InCage,
+    keybindings::{Action, Actions},
+    settings::Settings,
+    Ball, OtherCollisionEvent, Radius, Spin, Velocity,
+};
+
+/// Marks a ball that sticks to other sticky balls it touches.
+#[derive(Component)]
+pub struct Sticky;
+
+/// The [`Cluster`] entity a ball is stuck into.
+#[derive(Component, Clone, Copy, PartialEq, Eq)]
+pub struct InCluster(pub Entity);
+
+/// Sticky balls that move as one rigid body.
+#[derive(Component)]
+pub struct Cluster {
+    /// Every ball in the cluster, with where it sits relative to the others. The offsets are
+    /// taken when the cluster forms, and the cluster's rotation is worked out from them.
+    members: Vec<(Entity, Vec2)>,
+}
+
+/// Makes balls spawned from then on sticky, or not, with Z.
+pub fn toggle_sticky_balls(actions: Actions, mut settings: ResMut<Settings>) {
+    if actions.just_pressed(Action::ToggleStickyBalls) {
+        settings.sticky_balls = !settings.sticky_balls;
+    }
+}
+
+pub fn add_stickiness(
+    ball_query: Query<Entity, Added<Ball>>,
+    mut commands: Commands,
+    settings: Res<Settings>,
+) {
+    if !settings.sticky_balls {
+        return;
+    }
+    for entity in &ball_query {
+        commands.entity(entity).insert(Sticky);
+    }
+}
+
+/// Joins touching sticky balls, along with anything already stuck to them, into clusters.
+#[allow(clippy::type_complexity)]
+#[cfg_attr(feature = "rapier", allow(dead_code))]
+pub fn stick_balls(
+    mut collision_events: EventReader<OtherCollisionEvent>,
+    ball_query: Query<(&Transform, &Radius, &InCage, Option<&InCluster>), With<Sticky>>,
+    cluster_query: Query<&Cluster>,
+    mut commands: Commands,
+) {
+    let members_of = |entity: Entity| -> Vec<Entity> {
+        ball_query
+            .get(entity)
+            .ok()
+            .and_then(|(_, _, _, in_cluster)| in_cluster)
+            .and_then(|in_cluster| cluster_query.get(in_cluster.0).ok())
+            .map_or(vec![entity], |cluster| {
+                cluster.members.iter().map(|&(member, _)| member).collect()
+            })
+    };
+
+    // Several pairs can touch in one step, so gather everything that ends up connected first.
+    let mut groups: Vec<Vec<Entity>> = Vec::new();
+    for event in collision_events.read() {
+        let (entity, other_entity) = (event.self_entity, event.other_entity);
+        let (Ok((_, _, in_cage, in_cluster)), Ok((_, _, other_cage, other_cluster))) =
+            (ball_query.get(entity), ball_query.get(other_entity))
+        else {
+            continue;
+        };
+        if in_cage != other_cage || (in_cluster.is_some() && in_cluster == other_cluster) {
+            continue;
+        }
+
+        let mut group = members_of(entity);
+        for member in members_of(other_entity) {
+            if !group.contains(&member) {
+                group.push(member);
+            }
+        }
+        // Fold in any groups formed earlier this step that share a ball with this one.
+        groups.retain(|other_group| {
+            if !other_group.iter().any(|member| group.contains(member)) {
+                return true;
+            }
+            for &member in other_group {
+                if !group.contains(&member) {
+                    group.push(member);
+                }
+            }
+            false
+        });
+        groups.push(group);
+    }
+
+    for group in groups {
+        // The clusters being joined are replaced by one new one.
+        let mut replaced = HashSet::new();
+        let positions: Vec<(Entity, Vec2, f32)> = group
+            .iter()
+            .filter_map(|&entity| {
+                let (transform, radius, _, in_cluster) = ball_query.get(entity).ok()?;
+                if let Some(in_cluster) = in_cluster {
+                    replaced.insert(in_cluster.0);
+                }
+                Some((entity, transform.translation.truncate(), mass(radius)))
+            })
+            .collect();
+        for cluster in replaced {
+            commands.entity(cluster).despawn();
+        }
+        let center = center_of_mass(
+            positions
+                .iter()
+                .map(|&(_, position, mass)| (position, mass)),
+        );
+        let cluster = commands
+            .spawn(Cluster {
+                members: positions
+                    .iter()
+                    .map(|&(entity, position, _)| (entity, position - center))
+                    .collect(),
+            })
+            .id();
+        for (entity, _, _) in positions {
+            commands.entity(entity).insert(InCluster(cluster));
+        }
+    }
+}
+
+/// Moves every cluster as a rigid body: its balls share the cluster's momentum and angular
+/// momentum, and are put back at their offsets around its center.
+#[allow(clippy::type_complexity)]
+#[cfg_attr(feature = "rapier", allow(dead_code))]
+pub fn solve_clusters(
+    mut cluster_query: Query<(Entity, &mut Cluster)>,
+    mut ball_query: Query<(&mut Transform, &mut Velocity, &mut Spin, &Radius), With<InCluster>>,
+    mut commands: Commands,
+) {
+    for (cluster_entity, mut cluster) in &mut cluster_query {
+        // Balls can be popped, merged away or destroyed while stuck.
+        cluster
+            .members
+            .retain(|&(entity, _)| ball_query.contains(entity));
+        if cluster.members.len() < 2 {
+            for &(entity, _) in &cluster.members {
+                commands.entity(entity).remove::<InCluster>();
+            }
+            commands.entity(cluster_entity).despawn();
+            continue;
+        }
+
+        let mut bodies = Vec::with_capacity(cluster.members.len());
+        for &(entity, offset) in &cluster.members {
+            let Ok((transform, velocity, _, radius)) = ball_query.get(entity) else {
+                continue;
+            };
+            bodies.push((
+                entity,
+                offset,
+                transform.translation.truncate(),
+                velocity.0,
+                mass(radius),
+            ));
+        }
+        let total_mass: f32 = bodies.iter().map(|&(_, _, _, _, mass)| mass).sum();
+        let center = center_of_mass(
+            bodies
+                .iter()
+                .map(|&(_, _, position, _, mass)| (position, mass)),
+        );
+        // Offsets are relative to the center of mass when the cluster formed, which moves if a
+        // ball has gone missing since.
+        let rest_center =
+            center_of_mass(bodies.iter().map(|&(_, offset, _, _, mass)| (offset, mass)));
+        let velocity = bodies
+            .iter()
+            .map(|&(_, _, _, velocity, mass)| velocity * mass)
+            .sum::<Vec2>()
+            / total_mass;
+
+        // The rotation that best lines the offsets up with where the balls actually are.
+        let (mut cos, mut sin) = (0.0, 0.0);
+        let (mut angular_momentum, mut inertia) = (0.0, 0.0);
+        for &(_, offset, position, ball_velocity, mass) in &bodies {
+            let (rest, actual) = (offset - rest_center, position - center);
+            cos += mass * rest.dot(actual);
+            sin += mass * rest.perp_dot(actual);
+            angular_momentum += mass * actual.perp_dot(ball_velocity - velocity);
+            inertia += mass * actual.length_squared();
+        }
+        let rotation = Vec2::from_angle(sin.atan2(cos));
+        let angular_velocity = if inertia > 0.0 {
+            angular_momentum / inertia
+        } else {
+            0.0
+        };
+
+        for (entity, offset, _, _, _) in bodies {
+            let Ok((mut transform, mut ball_velocity, mut spin, _)) = ball_query.get_mut(entity)
+            else {
+                continue;
+            };
+            let arm = rotation.rotate(offset - rest_center);
+            transform.translation = (center + arm).extend(transform.translation.z);
+            ball_velocity.0 = velocity + angular_velocity * arm.perp();
+            spin.0 = angular_velocity;
+        }
+    }
+}
+
+/// Mass goes with area, same as when merging.
+fn mass(radius: &Radius) -> f32 {
+    radius.0.powi(2)
+}
+
+fn center_of_mass(points: impl Iterator<Item = (Vec2, f32)>) -> Vec2 {
+    let (weighted, total) = points.fold((Vec2::ZERO, 0.0), |(weighted, total), (point, mass)| {
+        (weighted + point * mass, total + mass)
+    });
+    if total > 0.0 {
+        weighted / total
+    } else {
+        Vec2::ZERO
+    }
+}
@@ -0,0 +1,357 @@
+use bevy::{prelude::*, utils::HashMap, window::ReceivedCharacter};
+
+use crate::{
+    ball_assets::BallAssets,
+    cage::{Cage, InCage},
+    free_spawn_position,
+    keybindings::{Action, Keybindings},
+    palette::BallPalette,
+    rng::SimRng,
+    settings::{Integrator, Settings},
+    spawn_ball,
+    theme::{HudText, Theme},
+    Ball, GravityField, Radius, ResetEvent, BALL_RADIUS,
+};
+
+// How many lines of earlier input and output stay on screen.
+const CONSOLE_LINES: usize = 10;
+const CONSOLE_FONT_SIZE: f32 = 14.0;
+const CONSOLE_BACKGROUND_COLOR: Color = Color::rgba(0.0, 0.0, 0.0, 0.8);
+const CONSOLE_PADDING: Val = Val::Px(6.0);
+const CONSOLE_HELP: &str =
+    "Commands: spawn <count>, gravity <y> or gravity <x> <y>, clear, reset, seed <number>, \
+     integrator <euler|verlet>, help";
+
+/// The console opened with the backquote key, for typing commands instead of using the keyboard
+/// shortcuts.
+#[derive(Resource, Default)]
+pub struct Console {
+    open: bool,
+    input: String,
+    /// The commands typed so far, and what they printed.
+    lines: Vec<String>,
+}
+
+impl Console {
+    fn print(&mut self, line: impl Into<String>) {
+        self.lines.push(line.into());
+        let excess = self.lines.len().saturating_sub(CONSOLE_LINES);
+        self.lines.drain(..excess);
+    }
+}
+
+/// A command typed into the console, run by [`run_console_commands`].
+#[derive(Event)]
+pub enum ConsoleCommand {
+    /// Adds this many balls, spread over the cages like [`Action::AddBall`] does.
+    Spawn(usize),
+    Gravity(Vec2),
+    /// Removes every ball, without putting any back.
+    Clear,
+    Reset,
+    /// Reseeds the simulation RNG and resets, so the run can be repeated.
+    Seed(u64),
+    Integrator(Integrator),
+    /// Lists the commands.
+    Help,
+}
+
+impl ConsoleCommand {
+    fn parse(line: &str) -> Result<Option<Self>, String> {
+        let mut words = line.split_whitespace();
+        let Some(name) = words.next() else {
+            return Ok(None);
+        };
+        let numbers: Vec<&str> = words.collect();
+        let number = |index: usize| -> Result<f32, String> {
+            let word = numbers
+                .get(index)
+                .ok_or_else(|| format!("{name} needs a number"))?;
+            // NaN and infinity parse, but would spread to every ball they touch.
+            word.parse()
+                .ok()
+                .filter(|number: &f32| number.is_finite())
+                .ok_or_else(|| format!("{word:?} isn't a number"))
+        };
+        let command = match name {
+            "spawn" => {
+                let count = numbers.first().ok_or("spawn needs a count")?;
+                ConsoleCommand::Spawn(
+                    count
+                        .parse()
+                        .map_err(|_| format!("{count:?} isn't a count"))?,
+                )
+            }
+            // A single number is straight up or down, like the default gravity.
+            "gravity" if numbers.len() == 1 => ConsoleCommand::Gravity(Vec2::new(0.0, number(0)?)),
+            "gravity" => ConsoleCommand::Gravity(Vec2::new(number(0)?, number(1)?)),
+            "clear" => ConsoleCommand::Clear,
+            "reset" => ConsoleCommand::Reset,
+            "seed" => {
+                let seed = numbers.first().ok_or("seed needs a number")?;
+                ConsoleCommand::Seed(
+                    seed.parse()
+                        .map_err(|_| format!("{seed:?} isn't a whole number"))?,
+                )
+            }
+            "integrator" => match numbers.first().copied() {
+                Some("euler") => ConsoleCommand::Integrator(Integrator::Euler),
+                Some("verlet") => ConsoleCommand::Integrator(Integrator::Verlet),
+                Some(other) => return Err(format!("{other:?} isn't euler or verlet")),
+                None => return Err("integrator needs euler or verlet".to_string()),
+            },
+            "help" => ConsoleCommand::Help,
+            _ => return Err(format!("Unknown command {name:?}. Type help for a list.")),
+        };
+        Ok(Some(command))
+    }
+}
+
+/// The console's box along the top of the screen.
+#[derive(Component)]
+pub struct ConsoleWindow;
+
+#[derive(Component)]
+pub struct ConsoleText;
+
+pub fn spawn_console(mut commands: Commands, theme: Res<Theme>) {
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    top: Val::Px(0.0),
+                    width: Val::Percent(100.0),
+                    padding: UiRect::all(CONSOLE_PADDING),
+                    ..Default::default()
+                },
+                background_color: CONSOLE_BACKGROUND_COLOR.into(),
+                visibility: Visibility::Hidden,
+                // In front of the HUD and menus.
+                z_index: ZIndex::Global(1),
+                ..Default::default()
+            },
+            ConsoleWindow,
+        ))
+        .with_children(|parent| {
+            let style = TextStyle {
+                font_size: CONSOLE_FONT_SIZE,
+                color: theme.hud,
+                ..Default::default()
+            };
+            parent.spawn((TextBundle::from_section("", style), ConsoleText, HudText));
+        });
+}
+
+/// Opens and closes the console with the backquote key, and takes the typing while it's open.
+/// Runs before anything else reads the keyboard, and hides every key from it while the console is
+/// open, so typing a command doesn't also set off the keyboard shortcuts.
+pub fn type_in_console(
+    mut console: ResMut<Console>,
+    mut keyboard_input: ResMut<ButtonInput<KeyCode>>,
+    keybindings: Res<Keybindings>,
+    mut character_events: EventReader<ReceivedCharacter>,
+    mut command_events: EventWriter<ConsoleCommand>,
+) {
+    let toggled = keybindings.just_pressed(&keyboard_input, Action::ToggleConsole);
+    if toggled {
+        console.open = !console.open;
+    }
+    // The character of the key that opened the console isn't part of the command.
+    if !console.open || toggled {
+        character_events.clear();
+        if toggled {
+            keyboard_input.reset_all();
+        }
+        return;
+    }
+
+    for event in character_events.read() {
+        let typed = event
+            .char
+            .chars()
+            .filter(|character| !character.is_control());
+        console.input.extend(typed);
+    }
+    if keyboard_input.just_pressed(KeyCode::Backspace) {
+        console.input.pop();
+    }
+    if keyboard_input.just_pressed(KeyCode::Enter) {
+        let line = std::mem::take(&mut console.input);
+        console.print(format!("> {line}"));
+        match ConsoleCommand::parse(&line) {
+            Ok(Some(command)) => {
+                command_events.send(command);
+            }
+            Ok(None) => {}
+            Err(message) => console.print(message),
+        }
+    }
+    if keyboard_input.just_pressed(KeyCode::Escape) {
+        console.open = false;
+    }
+    keyboard_input.reset_all();
+}
+
+pub fn show_console(
+    console: Res<Console>,
+    mut window_query: Query<&mut Visibility, With<ConsoleWindow>>,
+    mut text_query: Query<&mut Text, With<ConsoleText>>,
+) {
+    if !console.is_changed() {
+        return;
+    }
+    for mut visibility in &mut window_query {
+        *visibility = if console.open {
+            Visibility::Inherited
+        } else {
+            Visibility::Hidden
+        };
+    }
+    for mut text in &mut text_query {
+        let mut value = console.lines.join("\n");
+        if !value.is_empty() {
+            value.push('\n');
+        }
+        value.push_str(&format!("> {}_", console.input));
+        text.sections[0].value = value;
+    }
+}
+
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+pub fn run_console_commands(
+    mut command_events: EventReader<ConsoleCommand>,
+    mut console: ResMut<Console>,
+    ball_query: Query<(Entity, &Transform, &Radius, &InCage), With<Ball>>,
+    cage_query: Query<(Entity, &Cage, &Transform)>,
+    mut gravity_field: ResMut<GravityField>,
+    mut reset_events: EventWriter<ResetEvent>,
+    mut commands: Commands,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut ball_assets: ResMut<BallAssets>,
+    mut rng: ResMut<SimRng>,
+    palette: Res<BallPalette>,
+    mut settings: ResMut<Settings>,
+) {
+    for command in command_events.read() {
+        match *command {
+            ConsoleCommand::Spawn(count) => {
+                let cages: Vec<_> = cage_query.iter().collect();
+                if cages.is_empty() {
+                    console.print("There are no cages to spawn into");
+                    continue;
+                }
+                // The balls spawned so far are only added at the end of the frame, so they're
+                // kept track of here to keep new ones from landing on top of them.
+                let mut others: HashMap<Entity, Vec<(Vec2, f32)>> = HashMap::new();
+                for (_, transform, radius, in_cage) in &ball_query {
+                    others
+                        .entry(in_cage.0)
+                        .or_default()
+                        .push((transform.translation.truncate(), radius.0));
+                }
+                // Never more than the cap on balls, which the oldest would be despawned for
+                // anyway.
+                let count = count.min(settings.max_balls);
+                let mut spawned = 0;
+                for index in 0..count {
+                    let (entity, cage, cage_transform) = cages[index % cages.len()];
+                    let others = others.entry(entity).or_default();
+                    // Once there's no room left, searching for it again is only slow.
+                    let Some(position) =
+                        free_spawn_position(&mut rng, cage, cage_transform, others)
+                    else {
+                        break;
+                    };
+                    spawn_ball(
+                        &mut commands,
+                        &mut materials,
+                        &mut ball_assets,
+                        &mut rng,
+                        &palette,
+                        &settings,
+                        entity,
+                        position,
+                    );
+                    others.push((position, BALL_RADIUS / 2.0));
+                    spawned += 1;
+                }
+                console.print(format!("Spawned {spawned} balls"));
+            }
+            ConsoleCommand::Gravity(gravity) => {
+                gravity_field.0 = gravity;
+                console.print(format!("Gravity is now ({}, {})", gravity.x, gravity.y));
+            }
+            ConsoleCommand::Clear => {
+                for (entity, ..) in &ball_query {
+                    commands.entity(entity).despawn();
+                }
+                console.print("Cleared the balls");
+            }
+            ConsoleCommand::Reset => {
+                reset_events.send(ResetEvent);
+                console.print("Reset the balls");
+            }
+            ConsoleCommand::Seed(seed) => {
+                *rng = SimRng::from_seed(seed);
+                reset_events.send(ResetEvent);
+                console.print(format!("Reseeded with {seed} and reset"));
+            }
+            ConsoleCommand::Integrator(integrator) => {
+                settings.integrator = integrator;
+                console.print(format!("Integrating with {integrator:?}"));
+            }
+            ConsoleCommand::Help => console.print(CONSOLE_HELP),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn empty_line_is_no_command() {
+        assert!(matches!(ConsoleCommand::parse(""), Ok(None)));
+        assert!(matches!(ConsoleCommand::parse("   "), Ok(None)));
+    }
+
+    #[test]
+    fn unknown_command_is_an_error() {
+        assert!(ConsoleCommand::parse("jump").is_err());
+    }
+
+    #[test]
+    fn help_is_a_command() {
+        assert!(matches!(
+            ConsoleCommand::parse("help"),
+            Ok(Some(ConsoleCommand::Help))
+        ));
+    }
+
+    #[test]
+    fn single_gravity_number_points_straight_down_or_up() {
+        assert!(matches!(
+            ConsoleCommand::parse("gravity 1"),
+            Ok(Some(ConsoleCommand::Gravity(gravity))) if gravity == Vec2::new(0.0, 1.0)
+        ));
+    }
+
+    #[test]
+    fn two_gravity_numbers_are_x_and_y() {
+        assert!(matches!(
+            ConsoleCommand::parse("gravity 1 2"),
+            Ok(Some(ConsoleCommand::Gravity(gravity))) if gravity == Vec2::new(1.0, 2.0)
+        ));
+    }
+
+    #[test]
+    fn gravity_rejects_nan() {
+        assert!(ConsoleCommand::parse("gravity NaN").is_err());
+    }
+
+    #[test]
+    fn seed_rejects_non_numbers() {
+        assert!(ConsoleCommand::parse("seed x").is_err());
+    }
+}
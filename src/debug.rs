@@ -0,0 +1,88 @@
+use bevy::prelude::*;
+
+use crate::{
+    keybindings::{Action, Actions},
+    settings::Settings,
+    Ball, CageCollisionEvent, OtherCollisionEvent, Radius, Velocity,
+};
+
+const VELOCITY_COLOR: Color = Color::rgb(0.2, 0.9, 0.3);
+const RADIUS_COLOR: Color = Color::rgb(0.9, 0.9, 0.9);
+const NORMAL_COLOR: Color = Color::rgb(0.9, 0.3, 0.2);
+// Seconds of velocity each arrow reaches ahead.
+const VELOCITY_SCALE: f32 = 0.1;
+const NORMAL_LENGTH: f32 = 20.0;
+// Contacts only last a single step, so they're kept around for this many seconds to be visible.
+const CONTACT_LIFETIME: f32 = 0.3;
+
+/// Recent contact points and their normals, with how many seconds ago they happened.
+#[derive(Resource, Default)]
+pub struct RecentContacts(Vec<(Vec2, Vec2, f32)>);
+
+/// Turns the debug overlay on and off with F1.
+pub fn toggle_debug_overlay(
+    actions: Actions,
+    mut settings: ResMut<Settings>,
+    mut contacts: ResMut<RecentContacts>,
+) {
+    if actions.just_pressed(Action::ToggleDebugOverlay) {
+        settings.debug_overlay = !settings.debug_overlay;
+        contacts.0.clear();
+    }
+}
+
+pub fn record_contacts(
+    mut wall_collision_events: EventReader<CageCollisionEvent>,
+    mut ball_collision_events: EventReader<OtherCollisionEvent>,
+    mut contacts: ResMut<RecentContacts>,
+    settings: Res<Settings>,
+    time: Res<Time>,
+) {
+    if !settings.debug_overlay {
+        wall_collision_events.clear();
+        ball_collision_events.clear();
+        return;
+    }
+    for (_, _, age) in &mut contacts.0 {
+        *age += time.delta_seconds();
+    }
+    contacts.0.retain(|&(_, _, age)| age < CONTACT_LIFETIME);
+    contacts.0.extend(
+        wall_collision_events
+            .read()
+            .map(|event| (event.point, event.normal, 0.0)),
+    );
+    contacts.0.extend(
+        ball_collision_events
+            .read()
+            .map(|event| (event.point, event.normal, 0.0)),
+    );
+}
+
+/// Draws every ball's velocity and collision radius, and the normals of recent contacts.
+#[allow(clippy::type_complexity)]
+pub fn draw_debug_overlay(
+    mut gizmos: Gizmos,
+    ball_query: Query<(&Transform, &Velocity, &Radius), With<Ball>>,
+    contacts: Res<RecentContacts>,
+    settings: Res<Settings>,
+) {
+    if !settings.debug_overlay {
+        return;
+    }
+    for (transform, velocity, radius) in &ball_query {
+        let position = transform.translation.truncate();
+        gizmos.circle_2d(position, radius.0, RADIUS_COLOR);
+        if velocity.0 != Vec2::ZERO {
+            gizmos.arrow_2d(
+                position,
+                position + velocity.0 * VELOCITY_SCALE,
+                VELOCITY_COLOR,
+            );
+        }
+    }
+    for &(point, normal, age) in &contacts.0 {
+        let colour = NORMAL_COLOR.with_a(1.0 - age / CONTACT_LIFETIME);
+        gizmos.arrow_2d(point, point + normal * NORMAL_LENGTH, colour);
+    }
+}
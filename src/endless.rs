@@ -0,0 +1,110 @@
+use bevy::prelude::*;
+
+use crate::{menu::GameMode, score::Score, settings::Settings, GravityField};
+
+/// How endless mode gets harder: every parameter ramps from its starting value to its final
+/// one over `ramp_time`, then stays there.
+#[derive(Resource)]
+pub struct DifficultyCurve {
+    /// In seconds.
+    pub ramp_time: f32,
+    /// Strength of the gravity field, which keeps its direction.
+    pub gravity: (f32, f32),
+    pub spawn_chance: (f32, f32),
+    pub ball_speed: (f32, f32),
+}
+
+impl Default for DifficultyCurve {
+    fn default() -> Self {
+        Self {
+            ramp_time: 180.0,
+            gravity: (300.0, 900.0),
+            spawn_chance: (0.1, 0.5),
+            ball_speed: (200.0, 500.0),
+        }
+    }
+}
+
+impl DifficultyCurve {
+    /// How far along the ramp a run is after `elapsed` seconds, from 0.0 to 1.0.
+    fn progress(&self, elapsed: f32) -> f32 {
+        (elapsed / self.ramp_time).clamp(0.0, 1.0)
+    }
+}
+
+/// How long the current endless run has lasted, in seconds.
+#[derive(Resource, Default)]
+pub struct EndlessRun {
+    pub elapsed: f32,
+    /// What the ramped values were before endless mode took them over, put back when another
+    /// mode is started.
+    base: Option<BaseDifficulty>,
+}
+
+struct BaseDifficulty {
+    gravity: Vec2,
+    spawn_chance: f32,
+    ball_speed: f32,
+}
+
+/// Makes an endless run harder the longer it's survived, and scores it by the time survived.
+pub fn ramp_difficulty(
+    mode: Res<GameMode>,
+    curve: Res<DifficultyCurve>,
+    mut run: ResMut<EndlessRun>,
+    mut settings: ResMut<Settings>,
+    mut gravity_field: ResMut<GravityField>,
+    mut score: ResMut<Score>,
+    time: Res<Time>,
+) {
+    if *mode != GameMode::Endless {
+        return;
+    }
+    if run.base.is_none() {
+        run.base = Some(BaseDifficulty {
+            gravity: gravity_field.0,
+            spawn_chance: settings.spawn_chance,
+            ball_speed: settings.ball_speed,
+        });
+    }
+    run.elapsed += time.delta_seconds();
+    // Steps up once a second rather than every frame, so the settings aren't changed constantly.
+    let progress = curve.progress(run.elapsed.floor());
+    let ramp = |(start, end): (f32, f32)| start + (end - start) * progress;
+
+    let direction = gravity_field.try_normalize().unwrap_or(Vec2::NEG_Y);
+    let gravity = direction * ramp(curve.gravity);
+    // Renormalising the direction isn't exact, so a tiny difference doesn't count as a change.
+    if !gravity_field.abs_diff_eq(gravity, 1e-3) {
+        gravity_field.0 = gravity;
+    }
+    let (spawn_chance, ball_speed) = (ramp(curve.spawn_chance), ramp(curve.ball_speed));
+    if settings.spawn_chance != spawn_chance || settings.ball_speed != ball_speed {
+        settings.spawn_chance = spawn_chance;
+        settings.ball_speed = ball_speed;
+    }
+
+    let seconds = run.elapsed as u64;
+    if score.0 != seconds {
+        score.0 = seconds;
+    }
+}
+
+/// Puts back the values endless mode ramped once another mode is started, so they don't carry
+/// over into it.
+pub fn restore_difficulty(
+    mode: Res<GameMode>,
+    mut run: ResMut<EndlessRun>,
+    mut settings: ResMut<Settings>,
+    mut gravity_field: ResMut<GravityField>,
+) {
+    if *mode == GameMode::Endless {
+        return;
+    }
+    let Some(base) = run.base.take() else {
+        return;
+    };
+    gravity_field.0 = base.gravity;
+    settings.spawn_chance = base.spawn_chance;
+    settings.ball_speed = base.ball_speed;
+}
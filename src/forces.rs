@@ -0,0 +1,128 @@
+use std::f32::consts::TAU;
+
+use bevy::prelude::*;
+
+use crate::{command_line_value, Acceleration, Ball, Gravity, Sleeping, Velocity};
+
+const VORTEX_ARG: &str = "--vortex";
+const NOISE_ARG: &str = "--noise";
+// How far apart the swirls of the noise field are, in pixels.
+const NOISE_SCALE: f32 = 80.0;
+// How quickly the noise field changes, in radians of phase per second.
+const NOISE_SPEED: f32 = 0.5;
+
+/// An extra force on the balls, applied along with gravity each fixed step. Registered with
+/// [`RegisterForceGenerator::register_force_generator`].
+///
+/// Like gravity, it's scaled by each ball's [`Gravity`], so ghosts and heavy balls react to it
+/// the same way. It should only depend on its arguments, so runs stay repeatable from their seed.
+pub trait ForceGenerator: Send + Sync + 'static {
+    /// Shown in the log when it's registered.
+    fn name(&self) -> &str;
+
+    /// The acceleration on a ball at `position` moving at `velocity`, `time` seconds into the
+    /// simulation.
+    fn acceleration(&self, position: Vec2, velocity: Vec2, time: f32) -> Vec2;
+}
+
+/// Every force that's been registered, in the order they were.
+#[derive(Resource, Default)]
+pub struct ForceGenerators(Vec<Box<dyn ForceGenerator>>);
+
+pub trait RegisterForceGenerator {
+    fn register_force_generator(&mut self, generator: impl ForceGenerator) -> &mut Self;
+}
+
+impl RegisterForceGenerator for App {
+    fn register_force_generator(&mut self, generator: impl ForceGenerator) -> &mut Self {
+        info!("Registered the {} force", generator.name());
+        self.world
+            .get_resource_or_insert_with(ForceGenerators::default)
+            .0
+            .push(Box::new(generator));
+        self
+    }
+}
+
+/// Swirls the balls around a point, at the same speed however far away they are. Positive
+/// strengths turn counter-clockwise.
+pub struct Vortex {
+    pub center: Vec2,
+    pub strength: f32,
+}
+
+impl ForceGenerator for Vortex {
+    fn name(&self) -> &str {
+        "vortex"
+    }
+
+    fn acceleration(&self, position: Vec2, _velocity: Vec2, _time: f32) -> Vec2 {
+        (position - self.center).perp().normalize_or_zero() * self.strength
+    }
+}
+
+/// Pushes the balls in directions that vary smoothly over space and time, like turbulent air.
+pub struct NoiseField {
+    pub strength: f32,
+}
+
+impl ForceGenerator for NoiseField {
+    fn name(&self) -> &str {
+        "noise field"
+    }
+
+    fn acceleration(&self, position: Vec2, _velocity: Vec2, time: f32) -> Vec2 {
+        // A few sine waves at odd angles to each other, which is smooth and never repeats
+        // visibly, without needing a noise library.
+        let point = position / NOISE_SCALE;
+        let phase = time * NOISE_SPEED;
+        let turn = (point.x + phase).sin() * (point.y * 1.3 - phase * 0.7).cos()
+            + 0.5 * (point.x * 0.6 + point.y * 1.7 + phase * 1.3).sin();
+        Vec2::from_angle(turn * TAU) * self.strength
+    }
+}
+
+/// Registers the built-in forces asked for on the command line, with `--vortex <strength>` and
+/// `--noise <strength>`.
+pub fn register_forces_from_args(app: &mut App) {
+    let strength = |flag| {
+        let value = command_line_value(flag)?;
+        match value.parse::<f32>() {
+            Ok(strength) => Some(strength),
+            Err(error) => {
+                warn!("Ignoring {flag} {value:?}: {error}");
+                None
+            }
+        }
+    };
+    if let Some(strength) = strength(VORTEX_ARG) {
+        app.register_force_generator(Vortex {
+            center: Vec2::ZERO,
+            strength,
+        });
+    }
+    if let Some(strength) = strength(NOISE_ARG) {
+        app.register_force_generator(NoiseField { strength });
+    }
+}
+
+#[allow(clippy::type_complexity)]
+pub fn apply_force_generators(
+    mut query: Query<
+        (&Transform, &Velocity, &mut Acceleration, &Gravity),
+        (With<Ball>, Without<Sleeping>),
+    >,
+    generators: Res<ForceGenerators>,
+    time: Res<Time>,
+) {
+    if generators.0.is_empty() {
+        return;
+    }
+    let elapsed = time.elapsed_seconds();
+    for (transform, velocity, mut acceleration, gravity) in &mut query {
+        let position = transform.translation.truncate();
+        for generator in &generators.0 {
+            acceleration.0 += generator.acceleration(position, velocity.0, elapsed) * gravity.0;
+        }
+    }
+}
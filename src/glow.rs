@@ -0,0 +1,73 @@
+use bevy::{
+    core_pipeline::{bloom::BloomSettings, tonemapping::Tonemapping},
+    prelude::*,
+};
+
+use crate::{
+    ball_assets::BallAssets,
+    keybindings::{Action, Actions},
+    settings::Settings,
+    Ball, BallColor,
+};
+
+/// Turns the glowing look on and off with Q.
+pub fn toggle_glow(actions: Actions, mut settings: ResMut<Settings>) {
+    if actions.just_pressed(Action::ToggleGlow) {
+        settings.glow_enabled = !settings.glow_enabled;
+    }
+}
+
+/// Switches the camera to HDR with bloom while glowing, so colours brighter than white bleed
+/// into their surroundings.
+pub fn update_glow_camera(
+    mut camera_query: Query<(Entity, &mut Camera, &mut Tonemapping)>,
+    mut commands: Commands,
+    settings: Res<Settings>,
+) {
+    if !settings.is_changed() {
+        return;
+    }
+    for (entity, mut camera, mut tonemapping) in &mut camera_query {
+        if camera.hdr == settings.glow_enabled {
+            continue;
+        }
+        camera.hdr = settings.glow_enabled;
+        if settings.glow_enabled {
+            *tonemapping = Tonemapping::TonyMcMapface;
+            commands.entity(entity).insert(BloomSettings::default());
+        } else {
+            *tonemapping = Tonemapping::None;
+            commands.entity(entity).remove::<BloomSettings>();
+        }
+    }
+}
+
+/// Keeps ball materials at their colour, scaled up past white while glowing.
+#[allow(clippy::type_complexity)]
+pub fn brighten_balls(
+    mut ball_query: Query<(Ref<BallColor>, &mut Handle<ColorMaterial>), With<Ball>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut ball_assets: ResMut<BallAssets>,
+    settings: Res<Settings>,
+) {
+    let brightness = if settings.glow_enabled {
+        settings.glow_intensity
+    } else {
+        1.0
+    };
+    for (colour, mut material) in &mut ball_query {
+        if !colour.is_changed() && !settings.is_changed() {
+            continue;
+        }
+        let [r, g, b, _] = colour.0.as_rgba_f32();
+        // Keep the alpha, which may be fading the ball out.
+        ball_assets.restyle(&mut materials, &mut material, |material| {
+            material.color = Color::rgba(
+                r * brightness,
+                g * brightness,
+                b * brightness,
+                material.color.a(),
+            );
+        });
+    }
+}
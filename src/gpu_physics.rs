@@ -0,0 +1,523 @@
+use std::{
+    f32::consts::{PI, TAU},
+    sync::{Arc, Mutex},
+};
+
+use bevy::{
+    core::FrameCount,
+    diagnostic::{FrameTimeDiagnosticsPlugin, LogDiagnosticsPlugin},
+    prelude::*,
+    render::{
+        camera::ScalingMode,
+        extract_resource::{ExtractResource, ExtractResourcePlugin},
+        graph::CameraDriverLabel,
+        render_graph::{self, RenderGraph, RenderLabel},
+        render_resource::{
+            binding_types::{storage_buffer_read_only_sized, storage_buffer_sized, uniform_buffer},
+            BindGroup, BindGroupEntries, BindGroupLayout, BindGroupLayoutEntries, Buffer,
+            BufferDescriptor, BufferUsages, CachedComputePipelineId, ComputePassDescriptor,
+            ComputePipeline, ComputePipelineDescriptor, Maintain, MapMode, PipelineCache,
+            ShaderStages, ShaderType, StorageBuffer, UniformBuffer,
+        },
+        renderer::{RenderContext, RenderDevice, RenderQueue},
+        Render, RenderApp, RenderSet,
+    },
+    sprite::MaterialMesh2dBundle,
+};
+use rand::Rng;
+
+use crate::{
+    command_line_value,
+    rng::{self, SimRng},
+    theme::Theme,
+    GRAVITY,
+};
+
+const GPU_PHYSICS_ARG: &str = "--gpu-physics";
+const DEFAULT_BALLS: usize = 100_000;
+const SHADER_PATH: &str = "shaders/gpu_physics.wgsl";
+const BALL_RADIUS: f32 = 2.0;
+// Lower than usual, so the crowd settles instead of fizzing.
+const RESTITUTION: f32 = 0.5;
+// The balls start on a grid this far apart, filling the cage from the bottom.
+const START_SPACING: f32 = 2.5 * BALL_RADIUS;
+const CAGE_AREA_PER_BALL: f32 = 9.0 * BALL_RADIUS * BALL_RADIUS;
+const CAGE_COLOR: Color = Color::rgb(0.9, 0.9, 0.9);
+// In pixels per second.
+const START_SPEED: f32 = 50.0;
+// Different colours the balls are drawn in, each shared by many of them.
+const COLOURS: usize = 16;
+// Fixed steps run on the GPU each frame, splitting the frame's time between them.
+const SUBSTEPS: u32 = 4;
+// Longer frames are slowed down rather than taken in bigger steps.
+const MAX_FRAME_TIME: f32 = 1.0 / 30.0;
+// Matches the shader's `@workgroup_size` and `CELL_CAPACITY`.
+const WORKGROUP_SIZE: u32 = 64;
+const CELL_CAPACITY: u64 = 8;
+
+/// How many balls `--gpu-physics [balls]` asked for, if it was passed at all.
+pub fn gpu_physics_args() -> Option<usize> {
+    if !std::env::args().any(|arg| arg == GPU_PHYSICS_ARG) {
+        return None;
+    }
+    let balls = command_line_value(GPU_PHYSICS_ARG)
+        .and_then(|balls| balls.parse().ok())
+        .unwrap_or(DEFAULT_BALLS);
+    Some(balls)
+}
+
+/// Drops `balls` small balls into a single round cage and moves and collides them in a compute
+/// shader, reading their positions back to draw them a frame later, without waiting for the GPU.
+///
+/// An experiment in how many balls the GPU can handle, so none of the usual controls, forces,
+/// ball kinds or sounds are there. Collisions are found with a uniform grid instead of testing
+/// every pair.
+pub fn run(balls: usize) {
+    let cage_radius = (balls as f32 * CAGE_AREA_PER_BALL / PI).sqrt();
+    let mut rng = SimRng::from_seed(rng::seed_from_args());
+    let limit = cage_radius - BALL_RADIUS;
+    let rows = (2.0 * limit / START_SPACING) as i32;
+    let states: Vec<_> = (0..=rows)
+        .flat_map(|row| (0..=rows).map(move |column| (row, column)))
+        .map(|(row, column)| {
+            Vec2::new(column as f32, row as f32) * START_SPACING - Vec2::splat(limit)
+        })
+        .filter(|position| position.length() <= limit)
+        .take(balls)
+        .map(|position| {
+            let velocity = Vec2::from_angle(rng.gen_range(0.0..TAU)) * START_SPEED;
+            Vec4::new(position.x, position.y, velocity.x, velocity.y)
+        })
+        .collect();
+    info!(
+        "Simulating {} balls on the GPU in a cage of radius {cage_radius:.0}",
+        states.len()
+    );
+
+    App::new()
+        .insert_resource(ClearColor(Theme::DARK.background))
+        .add_plugins((
+            DefaultPlugins,
+            FrameTimeDiagnosticsPlugin,
+            LogDiagnosticsPlugin::default(),
+            GpuPhysicsPlugin {
+                states,
+                cage_radius,
+            },
+        ))
+        .run();
+}
+
+struct GpuPhysicsPlugin {
+    /// Where every ball starts, and how fast it's going.
+    states: Vec<Vec4>,
+    cage_radius: f32,
+}
+
+impl Plugin for GpuPhysicsPlugin {
+    fn build(&self, app: &mut App) {
+        let latest = LatestStates(Arc::new(Mutex::new(self.states.clone())));
+        app.insert_resource(GpuCage(self.cage_radius))
+            .insert_resource(latest.clone())
+            .init_resource::<StepDelta>()
+            .add_plugins(ExtractResourcePlugin::<StepDelta>::default())
+            .add_systems(Startup, spawn_gpu_balls)
+            .add_systems(Update, (update_step_delta, move_gpu_balls, draw_gpu_cage));
+
+        let render_app = app.sub_app_mut(RenderApp);
+        render_app.insert_resource(latest).add_systems(
+            Render,
+            (
+                prepare_params.in_set(RenderSet::PrepareResources),
+                read_back_states.in_set(RenderSet::Cleanup),
+            ),
+        );
+        let mut render_graph = render_app.world.resource_mut::<RenderGraph>();
+        render_graph.add_node(GpuPhysicsLabel, GpuPhysicsNode);
+        render_graph.add_node_edge(GpuPhysicsLabel, CameraDriverLabel);
+    }
+
+    // The render device only exists once every plugin is built.
+    fn finish(&self, app: &mut App) {
+        let render_app = app.sub_app_mut(RenderApp);
+        render_app.init_resource::<GpuPhysicsPipelines>();
+        let world = &render_app.world;
+        let buffers = GpuPhysicsBuffers::new(
+            world.resource::<RenderDevice>(),
+            world.resource::<RenderQueue>(),
+            &world.resource::<GpuPhysicsPipelines>().layout,
+            &self.states,
+            self.cage_radius,
+        );
+        render_app.insert_resource(buffers);
+    }
+}
+
+/// Which ball's state this entity is drawn from.
+#[derive(Component)]
+struct GpuBall(usize);
+
+/// The radius of the one cage.
+#[derive(Resource)]
+struct GpuCage(f32);
+
+/// The states last read back from the GPU, taken by the main world once it has drawn them.
+#[derive(Resource, Clone)]
+struct LatestStates(Arc<Mutex<Vec<Vec4>>>);
+
+/// How far each of this frame's substeps advances the balls, in seconds.
+#[derive(Resource, Clone, Default, ExtractResource)]
+struct StepDelta(f32);
+
+/// Laid out like the shader's `Params`.
+#[derive(ShaderType, Clone, Copy, Default)]
+struct GpuPhysicsParams {
+    gravity: Vec2,
+    delta: f32,
+    cage_radius: f32,
+    ball_radius: f32,
+    restitution: f32,
+    count: u32,
+    grid_size: u32,
+    cell_size: f32,
+}
+
+fn spawn_gpu_balls(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    latest: Res<LatestStates>,
+    cage: Res<GpuCage>,
+) {
+    let mut camera = Camera2dBundle::default();
+    camera.projection.scaling_mode = ScalingMode::AutoMin {
+        min_width: 2.2 * cage.0,
+        min_height: 2.2 * cage.0,
+    };
+    commands.spawn(camera);
+
+    let mesh = meshes.add(Circle {
+        radius: BALL_RADIUS,
+    });
+    let colours: Vec<_> = (0..COLOURS)
+        .map(|i| materials.add(Color::hsl(i as f32 * 360.0 / COLOURS as f32, 0.7, 0.6)))
+        .collect();
+    let states = latest.0.lock().unwrap().clone();
+    commands.spawn_batch(states.into_iter().enumerate().map(move |(index, state)| {
+        (
+            MaterialMesh2dBundle {
+                mesh: mesh.clone().into(),
+                material: colours[index % COLOURS].clone(),
+                transform: Transform::from_translation(state.xy().extend(1.0)),
+                ..Default::default()
+            },
+            GpuBall(index),
+        )
+    }));
+}
+
+fn update_step_delta(mut delta: ResMut<StepDelta>, time: Res<Time>) {
+    delta.0 = time.delta_seconds().min(MAX_FRAME_TIME) / SUBSTEPS as f32;
+}
+
+fn move_gpu_balls(latest: Res<LatestStates>, mut ball_query: Query<(&GpuBall, &mut Transform)>) {
+    let states = std::mem::take(&mut *latest.0.lock().unwrap());
+    // Nothing new has been read back since the last frame.
+    if states.is_empty() {
+        return;
+    }
+    ball_query.par_iter_mut().for_each(|(ball, mut transform)| {
+        transform.translation = states[ball.0].xy().extend(1.0);
+    });
+}
+
+fn draw_gpu_cage(mut gizmos: Gizmos, cage: Res<GpuCage>) {
+    gizmos
+        .circle_2d(Vec2::ZERO, cage.0, CAGE_COLOR)
+        .segments(128);
+}
+
+#[derive(Resource)]
+struct GpuPhysicsPipelines {
+    layout: BindGroupLayout,
+    clear_grid: CachedComputePipelineId,
+    fill_grid: CachedComputePipelineId,
+    move_balls: CachedComputePipelineId,
+}
+
+impl FromWorld for GpuPhysicsPipelines {
+    fn from_world(world: &mut World) -> Self {
+        let layout = world.resource::<RenderDevice>().create_bind_group_layout(
+            "gpu_physics_layout",
+            &BindGroupLayoutEntries::sequential(
+                ShaderStages::COMPUTE,
+                (
+                    uniform_buffer::<GpuPhysicsParams>(false),
+                    // The states at the start of the step.
+                    storage_buffer_read_only_sized(false, None),
+                    // The states at the end of it.
+                    storage_buffer_sized(false, None),
+                    // How many balls are in each grid cell.
+                    storage_buffer_sized(false, None),
+                    // Which balls those are.
+                    storage_buffer_sized(false, None),
+                ),
+            ),
+        );
+        let shader = world.resource::<AssetServer>().load(SHADER_PATH);
+        let pipeline_cache = world.resource::<PipelineCache>();
+        let queue = |entry_point: &'static str| {
+            pipeline_cache.queue_compute_pipeline(ComputePipelineDescriptor {
+                label: Some(entry_point.into()),
+                layout: vec![layout.clone()],
+                push_constant_ranges: Vec::new(),
+                shader: shader.clone(),
+                shader_defs: Vec::new(),
+                entry_point: entry_point.into(),
+            })
+        };
+        let clear_grid = queue("clear_grid");
+        let fill_grid = queue("fill_grid");
+        let move_balls = queue("move_balls");
+        Self {
+            layout,
+            clear_grid,
+            fill_grid,
+            move_balls,
+        }
+    }
+}
+
+impl GpuPhysicsPipelines {
+    /// The three pipelines of a step, in order, once they've all compiled.
+    fn get<'a>(&self, pipeline_cache: &'a PipelineCache) -> Option<[&'a ComputePipeline; 3]> {
+        Some([
+            pipeline_cache.get_compute_pipeline(self.clear_grid)?,
+            pipeline_cache.get_compute_pipeline(self.fill_grid)?,
+            pipeline_cache.get_compute_pipeline(self.move_balls)?,
+        ])
+    }
+}
+
+#[derive(Resource)]
+struct GpuPhysicsBuffers {
+    params: UniformBuffer<GpuPhysicsParams>,
+    states_in: StorageBuffer<Vec<Vec4>>,
+    states_out: StorageBuffer<Vec<Vec4>>,
+    /// Two, so one can be copied into while the other is still being mapped and read.
+    readbacks: [Readback; 2],
+    bind_group: BindGroup,
+    balls: u32,
+    cells: u32,
+}
+
+/// A buffer the states are copied to after the last substep, to be read back.
+struct Readback {
+    buffer: Buffer,
+    /// Shared with the callback that says when the buffer's been mapped.
+    state: Arc<Mutex<ReadbackState>>,
+}
+
+/// Where a [`Readback`] is in being copied into, mapped and read. Each copy is numbered by the
+/// frame it was made in, so an older one finishing late doesn't overwrite a newer one.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum ReadbackState {
+    Idle,
+    Copied(u32),
+    Mapping(u32),
+    Mapped(u32),
+}
+
+impl GpuPhysicsBuffers {
+    fn new(
+        render_device: &RenderDevice,
+        render_queue: &RenderQueue,
+        layout: &BindGroupLayout,
+        states: &[Vec4],
+        cage_radius: f32,
+    ) -> Self {
+        // Cells as wide as a ball, so touching balls are always in neighbouring cells.
+        let cell_size = 2.0 * BALL_RADIUS;
+        let grid_size = (2.0 * cage_radius / cell_size).ceil() as u32 + 1;
+        let cells = grid_size * grid_size;
+
+        let mut params = UniformBuffer::from(GpuPhysicsParams {
+            gravity: GRAVITY,
+            delta: 0.0,
+            cage_radius,
+            ball_radius: BALL_RADIUS,
+            restitution: RESTITUTION,
+            count: states.len() as u32,
+            grid_size,
+            cell_size,
+        });
+        params.write_buffer(render_device, render_queue);
+        let storage = |label: &str| {
+            let mut buffer = StorageBuffer::from(states.to_vec());
+            buffer.set_label(Some(label));
+            buffer.add_usages(BufferUsages::COPY_SRC | BufferUsages::COPY_DST);
+            buffer.write_buffer(render_device, render_queue);
+            buffer
+        };
+        let states_in = storage("gpu_physics_states_in");
+        let states_out = storage("gpu_physics_states_out");
+        let cell_counts = render_device.create_buffer(&BufferDescriptor {
+            label: Some("gpu_physics_cell_counts"),
+            size: cells as u64 * 4,
+            usage: BufferUsages::STORAGE,
+            mapped_at_creation: false,
+        });
+        let cell_balls = render_device.create_buffer(&BufferDescriptor {
+            label: Some("gpu_physics_cell_balls"),
+            size: cells as u64 * CELL_CAPACITY * 4,
+            usage: BufferUsages::STORAGE,
+            mapped_at_creation: false,
+        });
+        let readback = || Readback {
+            buffer: render_device.create_buffer(&BufferDescriptor {
+                label: Some("gpu_physics_readback"),
+                size: states_in.buffer().unwrap().size(),
+                usage: BufferUsages::MAP_READ | BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            }),
+            state: Arc::new(Mutex::new(ReadbackState::Idle)),
+        };
+        let bind_group = render_device.create_bind_group(
+            "gpu_physics_bind_group",
+            layout,
+            &BindGroupEntries::sequential((
+                params.binding().unwrap(),
+                states_in.binding().unwrap(),
+                states_out.binding().unwrap(),
+                cell_counts.as_entire_binding(),
+                cell_balls.as_entire_binding(),
+            )),
+        );
+
+        Self {
+            params,
+            states_in,
+            states_out,
+            readbacks: [readback(), readback()],
+            bind_group,
+            balls: states.len() as u32,
+            cells,
+        }
+    }
+}
+
+fn prepare_params(
+    mut buffers: ResMut<GpuPhysicsBuffers>,
+    delta: Res<StepDelta>,
+    render_device: Res<RenderDevice>,
+    render_queue: Res<RenderQueue>,
+) {
+    buffers.params.get_mut().delta = delta.0;
+    buffers.params.write_buffer(&render_device, &render_queue);
+}
+
+#[derive(Debug, Hash, PartialEq, Eq, Clone, RenderLabel)]
+struct GpuPhysicsLabel;
+
+/// Runs this frame's substeps, before anything is drawn.
+struct GpuPhysicsNode;
+
+impl render_graph::Node for GpuPhysicsNode {
+    fn run(
+        &self,
+        _graph: &mut render_graph::RenderGraphContext,
+        render_context: &mut RenderContext,
+        world: &World,
+    ) -> Result<(), render_graph::NodeRunError> {
+        let pipeline_cache = world.resource::<PipelineCache>();
+        let Some([clear_grid, fill_grid, move_balls]) =
+            world.resource::<GpuPhysicsPipelines>().get(pipeline_cache)
+        else {
+            return Ok(());
+        };
+        let buffers = world.resource::<GpuPhysicsBuffers>();
+        let states_in = buffers.states_in.buffer().unwrap();
+        let states_out = buffers.states_out.buffer().unwrap();
+
+        let encoder = render_context.command_encoder();
+        for _ in 0..SUBSTEPS {
+            for (pipeline, invocations) in [
+                (clear_grid, buffers.cells),
+                (fill_grid, buffers.balls),
+                (move_balls, buffers.balls),
+            ] {
+                let mut pass = encoder.begin_compute_pass(&ComputePassDescriptor::default());
+                pass.set_bind_group(0, &buffers.bind_group, &[]);
+                pass.set_pipeline(pipeline);
+                pass.dispatch_workgroups(invocations.div_ceil(WORKGROUP_SIZE), 1, 1);
+            }
+            encoder.copy_buffer_to_buffer(states_out, 0, states_in, 0, states_in.size());
+        }
+        // Skipped while both are still being read, which only happens if the CPU falls behind.
+        let frame = world.resource::<FrameCount>().0;
+        for readback in &buffers.readbacks {
+            let mut state = readback.state.lock().unwrap();
+            if *state == ReadbackState::Idle {
+                encoder.copy_buffer_to_buffer(states_in, 0, &readback.buffer, 0, states_in.size());
+                *state = ReadbackState::Copied(frame);
+                break;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Starts mapping the states copied this frame, and hands the newest ones that have finished
+/// mapping to the main world. Never waits for the GPU, so the balls are drawn a frame or so behind
+/// the simulation.
+fn read_back_states(
+    buffers: Res<GpuPhysicsBuffers>,
+    render_device: Res<RenderDevice>,
+    latest: Res<LatestStates>,
+    mut newest_read: Local<Option<u32>>,
+) {
+    // Runs the callbacks of any mappings that have finished.
+    render_device.poll(Maintain::Poll);
+
+    for readback in &buffers.readbacks {
+        let state = *readback.state.lock().unwrap();
+        match state {
+            ReadbackState::Copied(frame) => {
+                *readback.state.lock().unwrap() = ReadbackState::Mapping(frame);
+                let shared_state = readback.state.clone();
+                readback
+                    .buffer
+                    .slice(..)
+                    .map_async(MapMode::Read, move |result| {
+                        *shared_state.lock().unwrap() = match result {
+                            Ok(()) => ReadbackState::Mapped(frame),
+                            Err(error) => {
+                                warn!("Couldn't read the ball states back: {error}");
+                                ReadbackState::Idle
+                            }
+                        };
+                    });
+            }
+            ReadbackState::Mapped(frame) => {
+                if newest_read.map_or(true, |newest| frame > newest) {
+                    *newest_read = Some(frame);
+                    let bytes = readback.buffer.slice(..).get_mapped_range();
+                    let states = bytes
+                        .chunks_exact(16)
+                        .map(|state| {
+                            let float = |i: usize| {
+                                f32::from_le_bytes(state[i * 4..i * 4 + 4].try_into().unwrap())
+                            };
+                            Vec4::new(float(0), float(1), float(2), float(3))
+                        })
+                        .collect();
+                    drop(bytes);
+                    *latest.0.lock().unwrap() = states;
+                }
+                readback.buffer.unmap();
+                *readback.state.lock().unwrap() = ReadbackState::Idle;
+            }
+            ReadbackState::Idle | ReadbackState::Mapping(_) => {}
+        }
+    }
+}
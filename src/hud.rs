@@ -0,0 +1,327 @@
+use std::time::Duration;
+
+use bevy::{
+    diagnostic::{
+        Diagnostic, DiagnosticId, Diagnostics, DiagnosticsStore, FrameTimeDiagnosticsPlugin,
+    },
+    prelude::*,
+    // Unlike std's, works on the web too.
+    utils::Instant,
+};
+
+use crate::{
+    keybindings::{key_name, Action, Actions, Keybindings},
+    menu::GameMode,
+    players::PlayerScores,
+    score::{LastCombo, Score},
+    theme::{HudText, Theme},
+    Ball,
+};
+
+/// Milliseconds spent in fixed updates each frame, however many steps that frame took.
+pub const FIXED_UPDATE_TIME: DiagnosticId =
+    DiagnosticId::from_u128(0x5f0c_2b1e_8d4a_4c7e_9a63_21d7_0be4_f915);
+
+const HUD_FONT_SIZE: f32 = 16.0;
+const HUD_MARGIN: Val = Val::Px(8.0);
+// Far enough down to sit just below the ball counter.
+const SCORE_TOP: Val = Val::Px(28.0);
+const COMBO_TOP: Val = Val::Px(48.0);
+// Seconds the combo counter stays up after the last chained hit.
+const COMBO_SHOW_TIME: f32 = 1.0;
+// Flashes per second.
+const COMBO_FLASH_RATE: f32 = 4.0;
+// Smaller than the rest, so every binding fits on screen.
+const HELP_FONT_SIZE: f32 = 12.0;
+
+/// Time spent in fixed updates so far this frame.
+#[derive(Resource, Default)]
+pub struct FixedUpdateTimer {
+    step_started: Option<Instant>,
+    total: Duration,
+}
+
+/// The FPS, frame time and physics time shown with F3.
+#[derive(Component)]
+pub struct PerformanceOverlay;
+
+/// The text showing the [`Score`].
+#[derive(Component)]
+pub struct ScoreDisplay;
+
+/// The text flashing the [`LastCombo`] while it's recent.
+#[derive(Component)]
+pub struct ComboDisplay;
+
+/// The list of keybindings shown with H.
+#[derive(Component)]
+pub struct HelpOverlay;
+
+/// How many balls have been spawned since startup, including ones that are gone again.
+#[derive(Resource, Default)]
+pub struct SpawnedBalls(pub usize);
+
+/// The text showing how many balls there are.
+#[derive(Component)]
+pub struct BallCounter;
+
+pub fn spawn_ball_counter(mut commands: Commands, theme: Res<Theme>) {
+    let style = TextStyle {
+        font_size: HUD_FONT_SIZE,
+        color: theme.hud,
+        ..Default::default()
+    };
+    commands.spawn((
+        TextBundle::from_section("", style).with_style(Style {
+            position_type: PositionType::Absolute,
+            top: HUD_MARGIN,
+            left: HUD_MARGIN,
+            ..Default::default()
+        }),
+        BallCounter,
+        HudText,
+    ));
+}
+
+pub fn count_spawned_balls(ball_query: Query<(), Added<Ball>>, mut spawned: ResMut<SpawnedBalls>) {
+    let added = ball_query.iter().count();
+    if added > 0 {
+        spawned.0 += added;
+    }
+}
+
+pub fn update_ball_counter(
+    ball_query: Query<(), With<Ball>>,
+    mut counter_query: Query<&mut Text, With<BallCounter>>,
+    spawned: Res<SpawnedBalls>,
+) {
+    let alive = ball_query.iter().count();
+    for mut text in &mut counter_query {
+        let value = format!("Balls: {alive}  (spawned: {})", spawned.0);
+        // Only touch the text when it changes, so it isn't laid out again every frame.
+        if text.sections[0].value != value {
+            text.sections[0].value = value;
+        }
+    }
+}
+
+pub fn spawn_score_display(mut commands: Commands, theme: Res<Theme>) {
+    let style = TextStyle {
+        font_size: HUD_FONT_SIZE,
+        color: theme.hud,
+        ..Default::default()
+    };
+    commands.spawn((
+        TextBundle::from_section("", style).with_style(Style {
+            position_type: PositionType::Absolute,
+            top: SCORE_TOP,
+            left: HUD_MARGIN,
+            ..Default::default()
+        }),
+        ScoreDisplay,
+        HudText,
+    ));
+}
+
+pub fn update_score_display(
+    mut display_query: Query<&mut Text, With<ScoreDisplay>>,
+    score: Res<Score>,
+    player_scores: Res<PlayerScores>,
+    mode: Res<GameMode>,
+) {
+    if !score.is_changed() && !player_scores.is_changed() && !mode.is_changed() {
+        return;
+    }
+    for mut text in &mut display_query {
+        text.sections[0].value = match *mode {
+            GameMode::Classic => format!("Score: {}", score.0),
+            GameMode::Endless => format!("Survived: {} s", score.0),
+            GameMode::TwoPlayer => format!(
+                "Player 1: {}  Player 2: {}",
+                player_scores.0[0], player_scores.0[1]
+            ),
+        };
+    }
+}
+
+pub fn spawn_combo_display(mut commands: Commands, theme: Res<Theme>) {
+    let style = TextStyle {
+        font_size: HUD_FONT_SIZE,
+        color: theme.hud_accent,
+        ..Default::default()
+    };
+    commands.spawn((
+        TextBundle::from_section("", style).with_style(Style {
+            position_type: PositionType::Absolute,
+            top: COMBO_TOP,
+            left: HUD_MARGIN,
+            ..Default::default()
+        }),
+        ComboDisplay,
+    ));
+}
+
+pub fn update_combo_display(
+    mut display_query: Query<&mut Text, With<ComboDisplay>>,
+    last_combo: Res<LastCombo>,
+    theme: Res<Theme>,
+    time: Res<Time>,
+) {
+    let since = time.elapsed_seconds() - last_combo.at;
+    for mut text in &mut display_query {
+        let section = &mut text.sections[0];
+        if last_combo.multiplier < 2 || since > COMBO_SHOW_TIME {
+            if !section.value.is_empty() {
+                section.value.clear();
+            }
+            continue;
+        }
+        section.value = format!("Combo x{}", last_combo.multiplier);
+        // Flashes between full and half brightness, fading out towards the end.
+        let flash = 0.75 + 0.25 * (since * COMBO_FLASH_RATE * std::f32::consts::TAU).cos();
+        section.style.color = theme
+            .hud_accent
+            .with_a(flash * (1.0 - since / COMBO_SHOW_TIME));
+    }
+}
+
+pub fn fixed_update_diagnostic() -> Diagnostic {
+    Diagnostic::new(FIXED_UPDATE_TIME, "fixed_update_time", 20).with_suffix("ms")
+}
+
+pub fn start_fixed_update_timer(mut timer: ResMut<FixedUpdateTimer>) {
+    timer.step_started = Some(Instant::now());
+}
+
+pub fn stop_fixed_update_timer(mut timer: ResMut<FixedUpdateTimer>) {
+    if let Some(started) = timer.step_started.take() {
+        timer.total += started.elapsed();
+    }
+}
+
+pub fn measure_fixed_update_time(
+    mut diagnostics: Diagnostics,
+    mut timer: ResMut<FixedUpdateTimer>,
+) {
+    let total = std::mem::take(&mut timer.total);
+    diagnostics.add_measurement(FIXED_UPDATE_TIME, || total.as_secs_f64() * 1000.0);
+}
+
+pub fn spawn_performance_overlay(mut commands: Commands, theme: Res<Theme>) {
+    let style = TextStyle {
+        font_size: HUD_FONT_SIZE,
+        color: theme.hud,
+        ..Default::default()
+    };
+    commands.spawn((
+        TextBundle {
+            visibility: Visibility::Hidden,
+            ..TextBundle::from_section("", style)
+                .with_style(Style {
+                    position_type: PositionType::Absolute,
+                    top: HUD_MARGIN,
+                    right: HUD_MARGIN,
+                    ..Default::default()
+                })
+                .with_text_justify(JustifyText::Right)
+        },
+        PerformanceOverlay,
+        HudText,
+    ));
+}
+
+/// Shows and hides the performance overlay with F3.
+pub fn toggle_performance_overlay(
+    actions: Actions,
+    mut overlay_query: Query<&mut Visibility, With<PerformanceOverlay>>,
+) {
+    if !actions.just_pressed(Action::TogglePerformanceOverlay) {
+        return;
+    }
+    for mut visibility in &mut overlay_query {
+        *visibility = match *visibility {
+            Visibility::Hidden => Visibility::Inherited,
+            _ => Visibility::Hidden,
+        };
+    }
+}
+
+pub fn update_performance_overlay(
+    mut overlay_query: Query<(&mut Text, &Visibility), With<PerformanceOverlay>>,
+    diagnostics: Res<DiagnosticsStore>,
+) {
+    let smoothed = |id| {
+        diagnostics
+            .get(id)
+            .and_then(|diagnostic| diagnostic.smoothed())
+            .unwrap_or(0.0)
+    };
+    for (mut text, visibility) in &mut overlay_query {
+        if *visibility == Visibility::Hidden {
+            continue;
+        }
+        text.sections[0].value = format!(
+            "FPS: {:.0}\nFrame: {:.1} ms\nPhysics: {:.1} ms",
+            smoothed(FrameTimeDiagnosticsPlugin::FPS),
+            smoothed(FrameTimeDiagnosticsPlugin::FRAME_TIME),
+            smoothed(FIXED_UPDATE_TIME),
+        );
+    }
+}
+
+pub fn spawn_help_overlay(mut commands: Commands, theme: Res<Theme>) {
+    let style = TextStyle {
+        font_size: HELP_FONT_SIZE,
+        color: theme.hud,
+        ..Default::default()
+    };
+    commands.spawn((
+        TextBundle {
+            visibility: Visibility::Hidden,
+            ..TextBundle::from_section("", style).with_style(Style {
+                position_type: PositionType::Absolute,
+                bottom: HUD_MARGIN,
+                right: HUD_MARGIN,
+                ..Default::default()
+            })
+        },
+        HelpOverlay,
+        HudText,
+    ));
+}
+
+/// Shows and hides the keybindings with H.
+pub fn toggle_help_overlay(
+    actions: Actions,
+    mut overlay_query: Query<&mut Visibility, With<HelpOverlay>>,
+) {
+    if !actions.just_pressed(Action::ToggleHelp) {
+        return;
+    }
+    for mut visibility in &mut overlay_query {
+        *visibility = match *visibility {
+            Visibility::Hidden => Visibility::Inherited,
+            _ => Visibility::Hidden,
+        };
+    }
+}
+
+/// Lists every bound action, so the help follows the keybindings when they change.
+pub fn update_help_overlay(
+    mut overlay_query: Query<&mut Text, With<HelpOverlay>>,
+    keybindings: Res<Keybindings>,
+) {
+    if !keybindings.is_changed() {
+        return;
+    }
+    let lines: Vec<String> = Action::ALL
+        .iter()
+        .filter_map(|action| {
+            let key = keybindings.get(action)?;
+            Some(format!("{}  {}", key_name(*key), action.description()))
+        })
+        .collect();
+    for mut text in &mut overlay_query {
+        text.sections[0].value = lines.join("\n");
+    }
+}
@@ -0,0 +1,215 @@
+use bevy::{
+    prelude::*,
+    render::{
+        mesh::Indices,
+        render_asset::RenderAssetUsages,
+        render_resource::{AsBindGroup, PrimitiveTopology, ShaderRef, ShaderType},
+        view::NoFrustumCulling,
+    },
+    sprite::{Material2d, Material2dPlugin, MaterialMesh2dBundle, Mesh2dHandle},
+};
+
+use crate::{ball_assets::BallAssets, Ball};
+
+const SHADER_PATH: &str = "shaders/ball_instances.wgsl";
+// Above this many balls, they're all drawn with one mesh instead of one by one.
+const INSTANCING_THRESHOLD: usize = 2000;
+// They're only drawn one by one again once there are this many fewer, so a count hovering
+// around the threshold doesn't keep switching back and forth.
+const INSTANCING_HYSTERESIS: usize = 200;
+// Instances the mesh has room for to begin with. It doubles whenever that runs out.
+const INITIAL_CAPACITY: usize = 4096;
+
+/// Draws every ball with a single mesh once there are too many to draw one by one. The mesh has a
+/// quad for each ball, which the shader looks up in a storage buffer by its vertex index and moves
+/// into place.
+pub struct BallInstancingPlugin;
+
+impl Plugin for BallInstancingPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins(Material2dPlugin::<BallInstancesMaterial>::default())
+            .init_resource::<BallInstancing>()
+            .add_systems(Startup, spawn_ball_instances)
+            .add_systems(
+                PostUpdate,
+                (switch_ball_rendering, update_ball_instances).chain(),
+            );
+    }
+}
+
+/// Whether the balls are drawn with the single mesh.
+#[derive(Resource, Default)]
+pub struct BallInstancing {
+    active: bool,
+    /// How many balls the instanced mesh has quads for.
+    capacity: usize,
+}
+
+/// The entity that draws every ball at once.
+#[derive(Component)]
+pub struct BallInstances;
+
+/// Moves one of the mesh's quads onto each of `instances`, and rounds it off. The spare ones at
+/// the end are given no radius.
+///
+/// Only colours are copied over, so textured balls lose their texture while it's in use.
+#[derive(Asset, TypePath, AsBindGroup, Clone, Default)]
+pub struct BallInstancesMaterial {
+    #[storage(0, read_only)]
+    instances: Vec<BallInstance>,
+}
+
+impl Material2d for BallInstancesMaterial {
+    fn vertex_shader() -> ShaderRef {
+        SHADER_PATH.into()
+    }
+
+    fn fragment_shader() -> ShaderRef {
+        SHADER_PATH.into()
+    }
+}
+
+/// Laid out like the shader's `BallInstance`.
+#[derive(ShaderType, Clone, Copy, Default)]
+struct BallInstance {
+    colour: Vec4,
+    position: Vec2,
+    radius: f32,
+}
+
+/// A mesh of `quads` separate quads, all from -1 to 1, to be moved into place by the shader.
+fn quads_mesh(quads: usize) -> Mesh {
+    let corners = [[-1.0, -1.0], [1.0, -1.0], [1.0, 1.0], [-1.0, 1.0]];
+    let positions: Vec<_> = (0..quads)
+        .flat_map(|_| corners.map(|[x, y]| [x, y, 0.0]))
+        .collect();
+    let uvs: Vec<_> = (0..quads)
+        .flat_map(|_| corners.map(|[x, y]| [(x + 1.0) / 2.0, (1.0 - y) / 2.0]))
+        .collect();
+    // The 2D pipeline expects normals, even though nothing is lit.
+    let normals = vec![[0.0, 0.0, 1.0]; positions.len()];
+    let indices = (0..quads as u32)
+        .flat_map(|quad| [0, 1, 2, 0, 2, 3].map(|corner| quad * 4 + corner))
+        .collect();
+    Mesh::new(
+        PrimitiveTopology::TriangleList,
+        RenderAssetUsages::RENDER_WORLD,
+    )
+    .with_inserted_attribute(Mesh::ATTRIBUTE_POSITION, positions)
+    .with_inserted_attribute(Mesh::ATTRIBUTE_NORMAL, normals)
+    .with_inserted_attribute(Mesh::ATTRIBUTE_UV_0, uvs)
+    .with_inserted_indices(Indices::U32(indices))
+}
+
+fn spawn_ball_instances(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<BallInstancesMaterial>>,
+    mut instancing: ResMut<BallInstancing>,
+) {
+    instancing.capacity = INITIAL_CAPACITY;
+    commands.spawn((
+        MaterialMesh2dBundle {
+            mesh: meshes.add(quads_mesh(INITIAL_CAPACITY)).into(),
+            material: materials.add(BallInstancesMaterial {
+                instances: vec![BallInstance::default(); INITIAL_CAPACITY],
+            }),
+            transform: Transform::from_xyz(0.0, 0.0, 1.0),
+            visibility: Visibility::Hidden,
+            ..Default::default()
+        },
+        BallInstances,
+        // The mesh's bounds say nothing about where the balls are.
+        NoFrustumCulling,
+    ));
+}
+
+/// Switches between drawing the balls one by one and all at once as the count crosses
+/// [`INSTANCING_THRESHOLD`]. The balls' own meshes are taken away while they're drawn at once.
+fn switch_ball_rendering(
+    mut instancing: ResMut<BallInstancing>,
+    ball_query: Query<Entity, With<Ball>>,
+    new_ball_query: Query<Entity, Added<Ball>>,
+    mut instances_query: Query<&mut Visibility, With<BallInstances>>,
+    mut commands: Commands,
+    ball_assets: Res<BallAssets>,
+) {
+    let balls = ball_query.iter().count();
+    let active = if instancing.active {
+        balls + INSTANCING_HYSTERESIS > INSTANCING_THRESHOLD
+    } else {
+        balls > INSTANCING_THRESHOLD
+    };
+
+    if active == instancing.active {
+        if active {
+            for entity in &new_ball_query {
+                commands.entity(entity).remove::<Mesh2dHandle>();
+            }
+        }
+        return;
+    }
+    instancing.active = active;
+    info!(
+        "Drawing {balls} balls {}",
+        if active { "all at once" } else { "one by one" }
+    );
+    for entity in &ball_query {
+        if active {
+            commands.entity(entity).remove::<Mesh2dHandle>();
+        } else {
+            commands
+                .entity(entity)
+                .insert(Mesh2dHandle(ball_assets.mesh.clone()));
+        }
+    }
+    for mut visibility in &mut instances_query {
+        *visibility = if active {
+            Visibility::Inherited
+        } else {
+            Visibility::Hidden
+        };
+    }
+}
+
+/// Copies where every visible ball is, how big it is and what colour, to be drawn at once.
+#[allow(clippy::type_complexity)]
+fn update_ball_instances(
+    mut instancing: ResMut<BallInstancing>,
+    ball_query: Query<(&Transform, &Handle<ColorMaterial>, &Visibility), With<Ball>>,
+    instances_query: Query<(&Handle<BallInstancesMaterial>, &Mesh2dHandle), With<BallInstances>>,
+    colour_materials: Res<Assets<ColorMaterial>>,
+    mut instance_materials: ResMut<Assets<BallInstancesMaterial>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+) {
+    if !instancing.active {
+        return;
+    }
+    let Ok((material, mesh)) = instances_query.get_single() else {
+        return;
+    };
+    let mut instances: Vec<_> = ball_query
+        .iter()
+        .filter(|(_, _, visibility)| **visibility != Visibility::Hidden)
+        .map(|(transform, material, _)| {
+            let colour = colour_materials
+                .get(material)
+                .map_or(Color::WHITE, |material| material.color);
+            BallInstance {
+                colour: Vec4::from(colour.as_linear_rgba_f32()),
+                position: transform.translation.truncate(),
+                // The ball mesh is a circle of radius 0.5, scaled up by the transform.
+                radius: transform.scale.x / 2.0,
+            }
+        })
+        .collect();
+
+    if instances.len() > instancing.capacity {
+        instancing.capacity = instances.len().next_power_of_two();
+        meshes.insert(mesh.0.id(), quads_mesh(instancing.capacity));
+    }
+    instances.resize(instancing.capacity, BallInstance::default());
+    if let Some(material) = instance_materials.get_mut(material) {
+        material.instances = instances;
+    }
+}
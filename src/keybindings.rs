@@ -0,0 +1,435 @@
+use std::{fs, io::ErrorKind};
+
+use bevy::{ecs::system::SystemParam, prelude::*, utils::HashMap};
+use serde::Deserialize;
+
+// Relative to the working directory.
+const KEYBINDINGS_PATH: &str = "keybindings.ron";
+
+/// Something the player can do with a single key or gamepad button.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Deserialize)]
+pub enum Action {
+    /// Clears every ball and starts each cage off with a single one.
+    Reset,
+    /// Adds a ball to the middle of every cage.
+    AddBall,
+    SpawnBurst,
+    FireCannons,
+    /// Held to turn the cannons.
+    AimCannonsLeft,
+    AimCannonsRight,
+    /// Player one fires with this in two-player mode, instead of [`Action::FireCannons`].
+    PlayerOneFire,
+    PlayerTwoAimLeft,
+    PlayerTwoAimRight,
+    PlayerTwoFire,
+    PlaceSpawner,
+    /// Shift turns the attractor into a repulsor.
+    PlaceGravityWell,
+    /// Held to turn gravity.
+    TiltGravityLeft,
+    TiltGravityRight,
+    ResetGravity,
+    /// Held to resize the cages.
+    ShrinkCages,
+    GrowCages,
+    SpawnCage,
+    CycleCageShape,
+    ToggleCageGap,
+    ToggleNestedCages,
+    ToggleBreakableCages,
+    ToggleShrinkingCage,
+    ToggleObstacles,
+    TogglePegField,
+    SpawnRandomKind,
+    SpawnNormal,
+    SpawnHeavy,
+    SpawnGhost,
+    SpawnBouncy,
+    ToggleColourCharge,
+    ToggleMerging,
+    ToggleSplitting,
+    ToggleStickyBalls,
+    /// Gives balls spawned from then on a number of hits they survive, or takes it away.
+    ToggleHitPoints,
+    ToggleColourShift,
+    ToggleTrails,
+    ToggleSpriteBalls,
+    ToggleGlow,
+    CyclePalette,
+    CycleBackground,
+    CycleTheme,
+    ToggleMute,
+    Pause,
+    /// Stops only the physics, leaving everything else running.
+    PausePhysics,
+    /// Advances the frozen physics by a single fixed step.
+    StepPhysics,
+    /// Held to run the last few seconds backwards.
+    Rewind,
+    SaveSnapshot,
+    /// Replaces every ball, and the settings, with the saved ones.
+    LoadSnapshot,
+    /// Steps down through the time scales.
+    SlowDown,
+    SpeedUp,
+    /// Undoes any zooming and panning.
+    ResetCamera,
+    Screenshot,
+    /// Starts or stops recording a clip.
+    ToggleRecording,
+    /// Turns screen shake off, however strong it's set.
+    ToggleReducedMotion,
+    /// Opens the console, which takes all typing until it's closed again.
+    ToggleConsole,
+    ToggleHelp,
+    ToggleDebugOverlay,
+    ToggleSettingsPanel,
+    TogglePerformanceOverlay,
+}
+
+impl Action {
+    /// Every action, in the order the help overlay lists them.
+    pub const ALL: [Action; 60] = [
+        Action::Reset,
+        Action::AddBall,
+        Action::SpawnBurst,
+        Action::FireCannons,
+        Action::AimCannonsLeft,
+        Action::AimCannonsRight,
+        Action::PlayerOneFire,
+        Action::PlayerTwoAimLeft,
+        Action::PlayerTwoAimRight,
+        Action::PlayerTwoFire,
+        Action::PlaceSpawner,
+        Action::PlaceGravityWell,
+        Action::TiltGravityLeft,
+        Action::TiltGravityRight,
+        Action::ResetGravity,
+        Action::ShrinkCages,
+        Action::GrowCages,
+        Action::SpawnCage,
+        Action::CycleCageShape,
+        Action::ToggleCageGap,
+        Action::ToggleNestedCages,
+        Action::ToggleBreakableCages,
+        Action::ToggleShrinkingCage,
+        Action::ToggleObstacles,
+        Action::TogglePegField,
+        Action::SpawnRandomKind,
+        Action::SpawnNormal,
+        Action::SpawnHeavy,
+        Action::SpawnGhost,
+        Action::SpawnBouncy,
+        Action::ToggleColourCharge,
+        Action::ToggleMerging,
+        Action::ToggleSplitting,
+        Action::ToggleStickyBalls,
+        Action::ToggleHitPoints,
+        Action::ToggleColourShift,
+        Action::ToggleTrails,
+        Action::ToggleSpriteBalls,
+        Action::ToggleGlow,
+        Action::CyclePalette,
+        Action::CycleBackground,
+        Action::CycleTheme,
+        Action::ToggleMute,
+        Action::Pause,
+        Action::PausePhysics,
+        Action::StepPhysics,
+        Action::Rewind,
+        Action::SaveSnapshot,
+        Action::LoadSnapshot,
+        Action::SlowDown,
+        Action::SpeedUp,
+        Action::ResetCamera,
+        Action::Screenshot,
+        Action::ToggleRecording,
+        Action::ToggleReducedMotion,
+        Action::ToggleConsole,
+        Action::ToggleHelp,
+        Action::ToggleDebugOverlay,
+        Action::ToggleSettingsPanel,
+        Action::TogglePerformanceOverlay,
+    ];
+
+    /// What the action does, as shown in the help overlay.
+    pub fn description(self) -> &'static str {
+        match self {
+            Action::Reset => "Reset the balls",
+            Action::AddBall => "Add a ball to every cage",
+            Action::SpawnBurst => "Spawn a burst of balls",
+            Action::FireCannons => "Fire the cannons",
+            Action::AimCannonsLeft => "Turn the cannons left",
+            Action::AimCannonsRight => "Turn the cannons right",
+            Action::PlayerOneFire => "Fire player one's cannon (two players)",
+            Action::PlayerTwoAimLeft => "Turn player two's cannon left (two players)",
+            Action::PlayerTwoAimRight => "Turn player two's cannon right (two players)",
+            Action::PlayerTwoFire => "Fire player two's cannon (two players)",
+            Action::PlaceSpawner => "Place a spawner at the cursor",
+            Action::PlaceGravityWell => "Place an attractor at the cursor (Shift: repulsor)",
+            Action::TiltGravityLeft => "Tilt gravity left",
+            Action::TiltGravityRight => "Tilt gravity right",
+            Action::ResetGravity => "Reset gravity",
+            Action::ShrinkCages => "Shrink the cages",
+            Action::GrowCages => "Grow the cages",
+            Action::SpawnCage => "Place a cage at the cursor",
+            Action::CycleCageShape => "Change the cage shape",
+            Action::ToggleCageGap => "Open or close a gap in the cages",
+            Action::ToggleNestedCages => "Toggle nested cages",
+            Action::ToggleBreakableCages => "Toggle breakable cage walls",
+            Action::ToggleShrinkingCage => "Toggle shrinking cages",
+            Action::ToggleObstacles => "Toggle moving obstacles",
+            Action::TogglePegField => "Toggle pegs",
+            Action::SpawnRandomKind => "Spawn balls of random kinds",
+            Action::SpawnNormal => "Spawn normal balls",
+            Action::SpawnHeavy => "Spawn heavy balls",
+            Action::SpawnGhost => "Spawn ghost balls",
+            Action::SpawnBouncy => "Spawn bouncy balls",
+            Action::ToggleColourCharge => "Toggle colour charge",
+            Action::ToggleMerging => "Toggle merging",
+            Action::ToggleSplitting => "Toggle splitting",
+            Action::ToggleStickyBalls => "Toggle sticky balls",
+            Action::ToggleHitPoints => "Toggle hit points",
+            Action::ToggleColourShift => "Toggle colour shifting",
+            Action::ToggleTrails => "Toggle trails",
+            Action::ToggleSpriteBalls => "Toggle sprite balls",
+            Action::ToggleGlow => "Toggle glow",
+            Action::CyclePalette => "Change the palette",
+            Action::CycleBackground => "Change the background",
+            Action::CycleTheme => "Change the colour theme",
+            Action::ToggleMute => "Mute or unmute",
+            Action::Pause => "Pause",
+            Action::PausePhysics => "Freeze or unfreeze the physics",
+            Action::StepPhysics => "Step the frozen physics once",
+            Action::Rewind => "Rewind (hold)",
+            Action::SaveSnapshot => "Save a snapshot of the balls",
+            Action::LoadSnapshot => "Load the saved snapshot",
+            Action::SlowDown => "Slow the simulation down",
+            Action::SpeedUp => "Speed the simulation up",
+            Action::ResetCamera => "Reset the camera",
+            Action::Screenshot => "Save a screenshot",
+            Action::ToggleRecording => "Start or stop recording a clip",
+            Action::ToggleReducedMotion => "Toggle reduced motion",
+            Action::ToggleConsole => "Open or close the console",
+            Action::ToggleHelp => "Show or hide this help",
+            Action::ToggleDebugOverlay => "Toggle the debug overlay",
+            Action::ToggleSettingsPanel => "Toggle the settings panel",
+            Action::TogglePerformanceOverlay => "Toggle the performance overlay",
+        }
+    }
+}
+
+/// Which key triggers each [`Action`]. The defaults can be changed in [`KEYBINDINGS_PATH`].
+#[derive(Resource, Deref, DerefMut)]
+pub struct Keybindings(pub HashMap<Action, KeyCode>);
+
+impl Default for Keybindings {
+    fn default() -> Self {
+        Self(HashMap::from_iter([
+            (Action::Reset, KeyCode::KeyR),
+            (Action::AddBall, KeyCode::Space),
+            (Action::SpawnBurst, KeyCode::KeyB),
+            (Action::FireCannons, KeyCode::Enter),
+            (Action::AimCannonsLeft, KeyCode::KeyA),
+            (Action::AimCannonsRight, KeyCode::KeyD),
+            (Action::PlayerOneFire, KeyCode::KeyW),
+            (Action::PlayerTwoAimLeft, KeyCode::ArrowLeft),
+            (Action::PlayerTwoAimRight, KeyCode::ArrowRight),
+            (Action::PlayerTwoFire, KeyCode::ArrowUp),
+            (Action::PlaceSpawner, KeyCode::KeyS),
+            (Action::PlaceGravityWell, KeyCode::KeyG),
+            (Action::TiltGravityLeft, KeyCode::ArrowLeft),
+            (Action::TiltGravityRight, KeyCode::ArrowRight),
+            (Action::ResetGravity, KeyCode::ArrowDown),
+            (Action::ShrinkCages, KeyCode::BracketLeft),
+            (Action::GrowCages, KeyCode::BracketRight),
+            (Action::SpawnCage, KeyCode::KeyN),
+            (Action::CycleCageShape, KeyCode::KeyO),
+            (Action::ToggleCageGap, KeyCode::KeyE),
+            (Action::ToggleNestedCages, KeyCode::KeyI),
+            (Action::ToggleBreakableCages, KeyCode::KeyW),
+            (Action::ToggleShrinkingCage, KeyCode::KeyK),
+            (Action::ToggleObstacles, KeyCode::KeyX),
+            (Action::TogglePegField, KeyCode::KeyL),
+            (Action::SpawnRandomKind, KeyCode::Digit0),
+            (Action::SpawnNormal, KeyCode::Digit1),
+            (Action::SpawnHeavy, KeyCode::Digit2),
+            (Action::SpawnGhost, KeyCode::Digit3),
+            (Action::SpawnBouncy, KeyCode::Digit4),
+            (Action::ToggleColourCharge, KeyCode::KeyC),
+            (Action::ToggleMerging, KeyCode::KeyU),
+            (Action::ToggleSplitting, KeyCode::KeyY),
+            (Action::ToggleStickyBalls, KeyCode::KeyZ),
+            (Action::ToggleHitPoints, KeyCode::KeyF),
+            (Action::ToggleColourShift, KeyCode::KeyJ),
+            (Action::ToggleTrails, KeyCode::KeyT),
+            (Action::ToggleSpriteBalls, KeyCode::KeyV),
+            (Action::ToggleGlow, KeyCode::KeyQ),
+            (Action::CyclePalette, KeyCode::Tab),
+            (Action::ToggleMute, KeyCode::KeyM),
+            (Action::Pause, KeyCode::Escape),
+            (Action::PausePhysics, KeyCode::KeyP),
+            (Action::StepPhysics, KeyCode::Period),
+            (Action::Rewind, KeyCode::Backspace),
+            (Action::SaveSnapshot, KeyCode::F5),
+            (Action::LoadSnapshot, KeyCode::F9),
+            (Action::SlowDown, KeyCode::Minus),
+            (Action::SpeedUp, KeyCode::Equal),
+            (Action::ResetCamera, KeyCode::Home),
+            (Action::Screenshot, KeyCode::F12),
+            (Action::ToggleRecording, KeyCode::F10),
+            (Action::ToggleReducedMotion, KeyCode::F4),
+            (Action::CycleBackground, KeyCode::F6),
+            (Action::CycleTheme, KeyCode::F7),
+            (Action::ToggleConsole, KeyCode::Backquote),
+            (Action::ToggleHelp, KeyCode::KeyH),
+            (Action::ToggleDebugOverlay, KeyCode::F1),
+            (Action::ToggleSettingsPanel, KeyCode::F2),
+            (Action::TogglePerformanceOverlay, KeyCode::F3),
+        ]))
+    }
+}
+
+impl Keybindings {
+    pub fn just_pressed(&self, keyboard_input: &ButtonInput<KeyCode>, action: Action) -> bool {
+        self.get(&action)
+            .is_some_and(|&key| keyboard_input.just_pressed(key))
+    }
+
+    pub fn pressed(&self, keyboard_input: &ButtonInput<KeyCode>, action: Action) -> bool {
+        self.get(&action)
+            .is_some_and(|&key| keyboard_input.pressed(key))
+    }
+}
+
+/// Which button on a gamepad triggers each [`Action`]. Any connected gamepad can be used. The
+/// defaults can be changed in [`KEYBINDINGS_PATH`].
+#[derive(Resource, Deref, DerefMut)]
+pub struct GamepadBindings(pub HashMap<Action, GamepadButtonType>);
+
+impl Default for GamepadBindings {
+    fn default() -> Self {
+        Self(HashMap::from_iter([
+            (Action::AddBall, GamepadButtonType::South),
+            (Action::Reset, GamepadButtonType::East),
+            (Action::SpawnBurst, GamepadButtonType::West),
+            (Action::FireCannons, GamepadButtonType::North),
+            (Action::SlowDown, GamepadButtonType::LeftTrigger2),
+            (Action::SpeedUp, GamepadButtonType::RightTrigger2),
+            (Action::ResetGravity, GamepadButtonType::DPadDown),
+            (Action::CyclePalette, GamepadButtonType::DPadUp),
+            (Action::Pause, GamepadButtonType::Start),
+            (Action::ToggleHelp, GamepadButtonType::Select),
+        ]))
+    }
+}
+
+/// The bindings in [`KEYBINDINGS_PATH`], each replacing the default for its action. Actions it
+/// leaves out keep their default key or button. For example:
+///
+/// ```ron
+/// (
+///     keys: { AddBall: Numpad0, Reset: Backspace },
+///     gamepad: { Pause: Mode },
+/// )
+/// ```
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct KeybindingsConfig {
+    keys: HashMap<Action, KeyCode>,
+    gamepad: HashMap<Action, GamepadButtonType>,
+}
+
+/// Rebinds the actions given in [`KEYBINDINGS_PATH`], if there is one.
+pub fn load_keybindings(
+    mut keybindings: ResMut<Keybindings>,
+    mut gamepad_bindings: ResMut<GamepadBindings>,
+) {
+    let contents = match fs::read_to_string(KEYBINDINGS_PATH) {
+        Ok(contents) => contents,
+        Err(error) if error.kind() == ErrorKind::NotFound => return,
+        Err(error) => {
+            warn!("Couldn't read {KEYBINDINGS_PATH}: {error}");
+            return;
+        }
+    };
+    match ron::from_str::<KeybindingsConfig>(&contents) {
+        Ok(config) => {
+            keybindings.extend(config.keys);
+            gamepad_bindings.extend(config.gamepad);
+        }
+        Err(error) => warn!("Couldn't parse {KEYBINDINGS_PATH}: {error}"),
+    }
+}
+
+/// Whether the player is doing each [`Action`], from the keyboard or any gamepad. Systems ask this
+/// rather than looking at the keys themselves, so every action works with either.
+#[derive(SystemParam)]
+pub struct Actions<'w> {
+    keyboard_input: Res<'w, ButtonInput<KeyCode>>,
+    keybindings: Res<'w, Keybindings>,
+    gamepads: Res<'w, Gamepads>,
+    gamepad_input: Res<'w, ButtonInput<GamepadButton>>,
+    gamepad_axes: Res<'w, Axis<GamepadAxis>>,
+    gamepad_bindings: Res<'w, GamepadBindings>,
+}
+
+impl Actions<'_> {
+    pub fn just_pressed(&self, action: Action) -> bool {
+        self.keybindings.just_pressed(&self.keyboard_input, action)
+            || self
+                .gamepad_buttons(action)
+                .any(|button| self.gamepad_input.just_pressed(button))
+    }
+
+    pub fn pressed(&self, action: Action) -> bool {
+        self.keybindings.pressed(&self.keyboard_input, action)
+            || self
+                .gamepad_buttons(action)
+                .any(|button| self.gamepad_input.pressed(button))
+    }
+
+    /// How far the left stick of any gamepad is pushed sideways, from -1 (left) to 1 (right).
+    pub fn stick_x(&self) -> f32 {
+        self.gamepads
+            .iter()
+            .filter_map(|gamepad| {
+                self.gamepad_axes
+                    .get(GamepadAxis::new(gamepad, GamepadAxisType::LeftStickX))
+            })
+            .find(|&x| x != 0.0)
+            .unwrap_or(0.0)
+    }
+
+    /// The button bound to `action` on every connected gamepad.
+    fn gamepad_buttons(&self, action: Action) -> impl Iterator<Item = GamepadButton> + '_ {
+        let button_type = self.gamepad_bindings.get(&action).copied();
+        self.gamepads.iter().filter_map(move |gamepad| {
+            button_type.map(|button_type| GamepadButton::new(gamepad, button_type))
+        })
+    }
+}
+
+/// A short, readable name for a key, like "R" rather than "KeyR".
+pub fn key_name(key: KeyCode) -> String {
+    let name = format!("{key:?}");
+    match key {
+        KeyCode::ArrowLeft => "Left".to_string(),
+        KeyCode::ArrowRight => "Right".to_string(),
+        KeyCode::ArrowUp => "Up".to_string(),
+        KeyCode::ArrowDown => "Down".to_string(),
+        KeyCode::BracketLeft => "[".to_string(),
+        KeyCode::BracketRight => "]".to_string(),
+        KeyCode::Escape => "Esc".to_string(),
+        KeyCode::Minus => "-".to_string(),
+        KeyCode::Equal => "=".to_string(),
+        KeyCode::Period => ".".to_string(),
+        KeyCode::Backquote => "`".to_string(),
+        _ => name
+            .strip_prefix("Key")
+            .or_else(|| name.strip_prefix("Digit"))
+            .unwrap_or(&name)
+            .to_string(),
+    }
+}
@@ -0,0 +1,130 @@
+use bevy::prelude::*;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    ball_assets::BallAssets,
+    keybindings::{Action, Actions},
+    rng::SimRng,
+    settings::Settings,
+    Ball, BallColor,
+};
+
+const HEAVY_MASS_SCALE: f32 = 4.0;
+// Heavy balls keep this fraction of the usual restitution.
+const HEAVY_RESTITUTION_SCALE: f32 = 0.5;
+// Heavy balls are drawn at this fraction of their colour's brightness.
+const HEAVY_DARKEN: f32 = 0.5;
+const GHOST_ALPHA: f32 = 0.35;
+// How far towards white bouncy balls are drawn.
+const BOUNCY_LIGHTEN: f32 = 0.4;
+
+/// How a ball behaves when it hits things.
+#[derive(
+    Component, Reflect, Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize,
+)]
+#[reflect(Component)]
+pub enum BallKind {
+    #[default]
+    Normal,
+    /// Weighs more than its size suggests and doesn't bounce as much. Drawn darker.
+    Heavy,
+    /// Passes through other balls, but not walls or obstacles. Drawn see-through.
+    Ghost,
+    /// Always perfectly elastic, whatever [`Settings::restitution`] is. Drawn lighter.
+    Bouncy,
+}
+
+impl BallKind {
+    pub const ALL: [BallKind; 4] = [
+        BallKind::Normal,
+        BallKind::Heavy,
+        BallKind::Ghost,
+        BallKind::Bouncy,
+    ];
+
+    fn random(rng: &mut SimRng) -> Self {
+        Self::ALL[rng.gen_range(0..Self::ALL.len())]
+    }
+
+    /// Multiplier on the ball's mass, which otherwise goes with its area.
+    pub fn mass_scale(self) -> f32 {
+        match self {
+            BallKind::Heavy => HEAVY_MASS_SCALE,
+            _ => 1.0,
+        }
+    }
+
+    /// The restitution this kind of ball bounces with, given the configured one.
+    pub fn restitution(self, restitution: f32) -> f32 {
+        match self {
+            BallKind::Heavy => restitution * HEAVY_RESTITUTION_SCALE,
+            BallKind::Bouncy => 1.0,
+            BallKind::Normal | BallKind::Ghost => restitution,
+        }
+    }
+
+    pub fn collides_with_balls(self) -> bool {
+        self != BallKind::Ghost
+    }
+}
+
+/// Picks the kind of balls spawned from then on: 1 to 4 for normal, heavy, ghost or bouncy,
+/// or 0 for a random kind per ball.
+pub fn select_spawn_kind(actions: Actions, mut settings: ResMut<Settings>) {
+    let kind = if actions.just_pressed(Action::SpawnRandomKind) {
+        None
+    } else if actions.just_pressed(Action::SpawnNormal) {
+        Some(BallKind::Normal)
+    } else if actions.just_pressed(Action::SpawnHeavy) {
+        Some(BallKind::Heavy)
+    } else if actions.just_pressed(Action::SpawnGhost) {
+        Some(BallKind::Ghost)
+    } else if actions.just_pressed(Action::SpawnBouncy) {
+        Some(BallKind::Bouncy)
+    } else {
+        return;
+    };
+    settings.spawn_kind = kind;
+    info!(
+        "Spawning {}",
+        kind.map_or("random".to_string(), |kind| format!("{kind:?}"))
+    );
+}
+
+/// Balls spawn as [`BallKind::Normal`]. This swaps in the kind from the settings, and changes
+/// their colour to match.
+#[allow(clippy::type_complexity)]
+pub fn choose_ball_kind(
+    mut ball_query: Query<(&mut BallKind, &mut BallColor, &mut Handle<ColorMaterial>), Added<Ball>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut ball_assets: ResMut<BallAssets>,
+    mut rng: ResMut<SimRng>,
+    settings: Res<Settings>,
+) {
+    for (mut kind, mut colour, mut material) in &mut ball_query {
+        *kind = settings
+            .spawn_kind
+            .unwrap_or_else(|| BallKind::random(&mut rng));
+        match *kind {
+            BallKind::Normal => {}
+            BallKind::Heavy => {
+                let [r, g, b, _] = colour.0.as_rgba_f32();
+                colour.0 = Color::rgb(r * HEAVY_DARKEN, g * HEAVY_DARKEN, b * HEAVY_DARKEN);
+            }
+            BallKind::Ghost => {
+                ball_assets.restyle(&mut materials, &mut material, |material| {
+                    material.color.set_a(GHOST_ALPHA);
+                });
+            }
+            BallKind::Bouncy => {
+                let [r, g, b, _] = colour.0.as_rgba_f32();
+                let lighten = |channel: f32| channel + (1.0 - channel) * BOUNCY_LIGHTEN;
+                colour.0 = Color::rgb(lighten(r), lighten(g), lighten(b));
+            }
+        }
+        ball_assets.restyle(&mut materials, &mut material, |material| {
+            material.color = colour.0.with_a(material.color.a());
+        });
+    }
+}
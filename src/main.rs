@@ -1,98 +1,1057 @@
-use bevy::{prelude::*, sprite::MaterialMesh2dBundle};
+use std::{
+    collections::VecDeque,
+    f32::consts::{PI, TAU},
+    time::Duration,
+};
+
+use arena::{Arena, ArenaLoader};
+use audio::{AudioSettings, CollisionSound, SoundCooldowns};
+use ball_assets::BallAssets;
+use bevy::{
+    asset::AssetMetaCheck,
+    audio::AddAudioSource,
+    diagnostic::{Diagnostics, FrameTimeDiagnosticsPlugin, RegisterDiagnostic},
+    input::InputSystem,
+    prelude::*,
+    sprite::MaterialMesh2dBundle,
+    tasks::ComputeTaskPool,
+    transform::TransformSystem,
+    utils::{HashMap, HashSet},
+    window::{PrimaryWindow, WindowResized},
+};
+use cage::{BallEscapedEvent, Cage, InCage, NestedIn, ShrinkingCage, CAGE_RADIUS};
+use cluster::InCluster;
+use endless::EndlessRun;
+use keybindings::{Action, Actions, Keybindings};
+use kind::BallKind;
+use menu::AppState;
+use palette::BallPalette;
+use players::PlayerScores;
+use powerup::{ActiveEffects, PowerUp};
+use rand::Rng;
+use rng::SimRng;
+use score::Score;
+use settings::{BallAppearance, BurstPattern, Integrator, Settings};
+use theme::Theme;
+
+mod achievements;
+mod arena;
+mod audio;
+mod background;
+mod ball_assets;
+mod benchmark;
+mod cage;
+mod camera;
+mod cannon;
+mod cluster;
+mod console;
+mod debug;
+mod endless;
+mod forces;
+mod glow;
+mod gpu_physics;
+mod hud;
+// WebGL2 has no storage buffers, so the web build always draws the balls one by one.
+#[cfg(not(target_arch = "wasm32"))]
+mod instancing;
+mod keybindings;
+mod kind;
+mod menu;
+mod metrics;
+mod network;
+mod obstacle;
+mod palette;
+mod panel;
+mod particle;
+mod players;
+mod powerup;
+#[cfg(feature = "rapier")]
+mod rapier;
+mod recording;
+mod replay;
+mod rng;
+mod score;
+mod screenshot;
+#[cfg(feature = "scripting")]
+mod scripting;
+mod settings;
+mod shake;
+mod snapshot;
+mod spawner;
+mod stats;
+mod theme;
+mod time_control;
+mod tone;
+mod touch;
 
 const BALL_RADIUS: f32 = 10.0;
-const BALL_STARTING_SPEED: f32 = 200.0;
-const BALL_GRAVITY: f32 = -300.;
+// How strongly balls are pulled by the global gravity field.
+const BALL_GRAVITY_SCALE: f32 = 1.0;
+// Fraction of velocity lost per second, which also gives balls a terminal velocity.
+const BALL_DRAG: f32 = 0.1;
+
+// The element of web/index.html the game is drawn into.
+const WEB_CANVAS: &str = "#bevy-balls";
+
+const GRAVITY: Vec2 = Vec2::new(0.0, -300.0);
+// In radians per second.
+const GRAVITY_TILT_SPEED: f32 = 1.5;
+const GRAVITY_INDICATOR_POSITION: Vec2 = Vec2::new(-CAGE_RADIUS - 60.0, CAGE_RADIUS);
+const GRAVITY_INDICATOR_LENGTH: f32 = 40.0;
+const GRAVITY_INDICATOR_COLOR: Color = Color::rgb(1.0, 0.8, 0.2);
+
+const GRAVITY_WELL_RADIUS: f32 = 6.0;
+const GRAVITY_WELL_STRENGTH: f32 = 500_000.0;
+const GRAVITY_WELL_FALLOFF: f32 = 2.0;
+// Keeps the force from blowing up when a ball passes right over a well.
+const GRAVITY_WELL_MIN_DISTANCE: f32 = 20.0;
+const ATTRACTOR_COLOR: Color = Color::rgb(0.6, 0.3, 1.0);
+const REPULSOR_COLOR: Color = Color::rgb(1.0, 0.4, 0.2);
+
+const CHARGE_MIN_DISTANCE: f32 = 10.0;
+
+// In seconds.
+const ENERGY_LOG_INTERVAL: f32 = 1.0;
+
+// Launch speed, in pixels per second, for every pixel the mouse is dragged.
+const LAUNCH_SPEED_PER_PIXEL: f32 = 4.0;
+// Shorter drags count as a plain click, which keeps the random starting direction.
+const LAUNCH_MIN_DRAG: f32 = 5.0;
+const LAUNCH_PREVIEW_COLOR: Color = Color::rgb(0.4, 0.9, 0.4);
 
-const CAGE_COLOR: Color = Color::rgb(1.0, 1.0, 1.0);
-const CAGE_RADIUS: f32 = 100.0;
-// Since the collision math does not actually use this value, it's completely visual.
-const CAGE_WALL_THICKNESS: f32 = 2.0;
+// Random spawn positions are retried this many times before giving up on a ball.
+const SPAWN_PLACEMENT_ATTEMPTS: u32 = 50;
 
-const BACKGROUND_COLOR: Color = Color::rgb(0.1, 0.1, 0.1);
+// Balls within this many pixels of each other's radius count as the same size for merging.
+const MERGE_SIZE_TOLERANCE: f32 = 0.01;
+
+// Balls smaller than this don't split any further.
+const SPLIT_MIN_RADIUS: f32 = 2.0;
+// How fast the pieces of a split ball fly apart, in pixels per second.
+const SPLIT_SPREAD_SPEED: f32 = 80.0;
+
+// Plays the collision sound higher for popping balls.
+const POP_SOUND_SPEED: f32 = 1.8;
+// How many hits balls survive once hit points are turned on with F.
+const BALL_HIT_POINTS: u32 = 5;
+
+// How quickly a grabbed ball closes the distance to the cursor, per second.
+const GRAB_STIFFNESS: f32 = 15.0;
+// In pixels per second.
+const GRAB_MAX_SPEED: f32 = 2000.0;
+
+// Balls with a lifetime fade out over this many seconds before despawning.
+const LIFETIME_FADE: f32 = 1.0;
+
+// Balls slower than this for `SLEEP_STEPS` fixed steps in a row are put to sleep.
+const SLEEP_SPEED: f32 = 15.0;
+const SLEEP_STEPS: u32 = 60;
 
 fn main() {
-    App::new()
-        .add_event::<CageCollisionEvent>()
+    if let Some((balls, steps)) = benchmark::benchmark_args() {
+        benchmark::run(balls, steps);
+        return;
+    }
+    if let Some(balls) = gpu_physics::gpu_physics_args() {
+        gpu_physics::run(balls);
+        return;
+    }
+
+    let mut app = App::new();
+    app.add_event::<CageCollisionEvent>()
         .add_event::<OtherCollisionEvent>()
-        .add_systems(Startup, setup)
+        .add_event::<BallEscapedEvent>()
+        .add_event::<BallsMergedEvent>()
+        .add_event::<console::ConsoleCommand>()
+        .add_event::<BallDestroyedEvent>()
+        .add_event::<ResetEvent>()
+        .add_event::<achievements::AchievementUnlockedEvent>()
+        .init_state::<AppState>()
+        .init_asset::<Arena>()
+        .init_asset_loader::<ArenaLoader>()
         .add_systems(
-            FixedUpdate,
-            (apply_gravity, apply_velocity, collide_cage, collide_others).chain(),
+            Startup,
+            (
+                // The sound paths come from the audio settings, and the camera is fitted to the
+                // window once it's spawned.
+                (
+                    audio::load_audio_settings,
+                    setup,
+                    camera::fit_camera_to_window,
+                )
+                    .chain(),
+                arena::load_arenas,
+                audio::setup_music,
+                hud::spawn_ball_counter,
+                hud::spawn_performance_overlay,
+                hud::spawn_help_overlay,
+                hud::spawn_score_display,
+                hud::spawn_combo_display,
+                achievements::load_achievements,
+                achievements::spawn_toast_container,
+                rng::log_seed,
+                replay::start_replay_from_args,
+                background::spawn_background,
+                stats::open_stats_log,
+                network::start_network_from_args,
+                console::spawn_console,
+                keybindings::load_keybindings,
+            ),
+        )
+        .add_systems(
+            Update,
+            (
+                audio::unlock_audio,
+                (
+                    audio::play_collision_sound,
+                    audio::play_merge_sound,
+                    (
+                        audio::choose_music_track.run_if(state_changed::<AppState>),
+                        audio::switch_music_track,
+                        audio::fade_music,
+                    )
+                        .chain(),
+                )
+                    .run_if(audio::audio_unlocked),
+                (audio::toggle_mute, audio::save_audio_settings).chain(),
+            ),
         )
         .add_systems(
             Update,
             (
-                play_collision_sound,
-                bevy::window::close_on_esc,
-                spawn_ball_on_space,
+                // The second player aims with the arrow keys.
+                tilt_gravity.run_if(not(players::two_player)),
+                draw_gravity_indicator,
+                place_gravity_well,
+                toggle_colour_charge,
+                toggle_merging,
+                toggle_splitting,
+                toggle_trails,
+                toggle_sprite_balls,
+                palette::cycle_palette,
+                toggle_colour_shift,
+                shift_colours_on_collision.before(glow::brighten_balls),
+                (
+                    glow::toggle_glow,
+                    glow::update_glow_camera,
+                    glow::brighten_balls,
+                )
+                    .chain(),
+                (add_appearance, update_ball_textures).chain(),
+                (record_trails, draw_trails).chain(),
+                log_energy,
+                obstacle::toggle_obstacles,
+                obstacle::toggle_peg_field,
+                (
+                    debug::toggle_debug_overlay,
+                    debug::record_contacts,
+                    debug::draw_debug_overlay,
+                )
+                    .chain(),
+            ),
+        )
+        .add_systems(
+            Update,
+            (
+                (request_reset, reset_balls).chain(),
+                add_ball,
+                spawn_burst,
+                (launch_ball_on_drag, pop_ball_on_click)
+                    .chain()
+                    .run_if(not(panel::pointer_over_panel)),
+                (particle::spawn_collision_sparks, particle::update_particles).chain(),
+                grab_ball,
+                draw_launch_preview,
                 maybe_spawn_ball,
+                // S sits among the first player's WASD keys.
+                spawner::place_spawner.run_if(not(players::two_player)),
+                spawner::run_spawners,
+                (stamp_spawn_time, despawn_oldest_balls).chain(),
+                (add_lifetime, age_balls).chain(),
+                (
+                    toggle_hit_points,
+                    add_hit_points,
+                    damage_balls,
+                    burst_destroyed_balls,
+                )
+                    .chain(),
+                cluster::toggle_sticky_balls,
+                cluster::add_stickiness,
+                kind::select_spawn_kind,
+                kind::choose_ball_kind,
+                cannon::aim_cannons,
+                cannon::fire_cannons,
+                cannon::draw_cannons,
+            )
+                .run_if(in_state(AppState::Running)),
+        )
+        .add_systems(
+            Update,
+            (
+                touch::track_touches,
+                touch::long_press_to_pop,
+                touch::tap_to_spawn,
+            )
+                .chain()
+                .run_if(in_state(AppState::Running)),
+        )
+        .add_systems(
+            Update,
+            (
+                cage::resize_cage,
+                cage::update_cage_meshes,
+                cage::cycle_cage_shape,
+                cage::toggle_cage_gap,
+                cage::toggle_nested_cages,
+                // The first player fires with W.
+                cage::toggle_breakable_cages.run_if(not(players::two_player)),
+                cage::update_cage_damage,
+                cage::spawn_cage_at_cursor,
+                cage::toggle_shrinking_cage,
+                cage::shrink_cage,
+                cage::check_cage_pressure,
+                // After a restart's reset, so it doesn't see the old balls and end the run again.
+                cage::end_run_when_saturated.after(reset_balls),
+                endless::ramp_difficulty,
+            )
+                .run_if(in_state(AppState::Running)),
+        )
+        .add_systems(
+            Update,
+            (
+                powerup::spawn_power_ups,
+                powerup::collect_power_ups,
+                powerup::update_effects,
+                powerup::despawn_power_ups_on_reset,
+            )
+                .chain()
+                .run_if(in_state(AppState::Running)),
+        )
+        .add_systems(
+            Update,
+            (
+                achievements::track_stats,
+                achievements::unlock_achievements,
+                achievements::save_achievements
+                    .run_if(on_event::<achievements::AchievementUnlockedEvent>()),
+            )
+                .chain()
+                .run_if(in_state(AppState::Running)),
+        )
+        .add_systems(OnExit(AppState::Running), achievements::save_achievements)
+        .add_systems(
+            Update,
+            (
+                time_control::toggle_physics_pause,
+                time_control::step_physics.run_if(in_state(AppState::Running)),
+                time_control::rewind.run_if(in_state(AppState::Running)),
+                time_control::change_time_scale,
+                time_control::apply_time_scale.after(powerup::update_effects),
+            )
+                .chain(),
+        )
+        .add_systems(
+            Update,
+            (achievements::show_toasts, achievements::expire_toasts),
+        )
+        .add_systems(
+            Update,
+            (
+                menu::toggle_pause,
+                players::arrange_player_cages.before(reset_balls),
+                menu::handle_menu_buttons.run_if(not(in_state(AppState::Running))),
+                endless::restore_difficulty
+                    .after(menu::handle_menu_buttons)
+                    .run_if(resource_changed::<menu::GameMode>),
+                panel::toggle_settings_panel,
+                (panel::drag_sliders, panel::update_sliders).chain(),
+            ),
+        )
+        .add_systems(OnEnter(AppState::Menu), menu::spawn_title_screen)
+        .add_systems(OnExit(AppState::Menu), menu::despawn_menu_screen)
+        .add_systems(OnEnter(AppState::Paused), menu::spawn_pause_menu)
+        .add_systems(OnExit(AppState::Paused), menu::despawn_menu_screen)
+        .add_systems(OnEnter(AppState::GameOver), menu::spawn_game_over_screen)
+        .add_systems(OnExit(AppState::GameOver), menu::despawn_menu_screen)
+        .add_systems(
+            Update,
+            replay::record_keyframes
+                .after(reset_balls)
+                .run_if(in_state(AppState::Running)),
+        )
+        .add_systems(OnEnter(AppState::GameOver), replay::save_replay)
+        .add_systems(OnEnter(AppState::Replay), replay::start_playback)
+        .add_systems(
+            Update,
+            replay::play_replay
+                .run_if(in_state(AppState::Replay).and_then(resource_exists::<replay::Playback>)),
+        )
+        .add_systems(OnExit(AppState::Replay), replay::stop_playback)
+        .add_systems(OnEnter(AppState::Online), network::start_client)
+        .add_systems(
+            Update,
+            (
+                network::run_server.run_if(resource_exists::<network::NetworkServer>),
+                network::run_client.run_if(
+                    in_state(AppState::Online).and_then(resource_exists::<network::NetworkClient>),
+                ),
             ),
         )
-        .insert_resource(ClearColor(BACKGROUND_COLOR))
-        .add_plugins(DefaultPlugins)
-        .run();
+        .add_systems(
+            OnExit(AppState::Online),
+            (replay::stop_playback, network::stop_client),
+        )
+        .add_systems(
+            Update,
+            (snapshot::save_snapshot, snapshot::load_snapshot)
+                .chain()
+                .run_if(in_state(AppState::Running)),
+        )
+        .add_systems(
+            Update,
+            (
+                camera::fit_camera_to_window.run_if(on_event::<WindowResized>()),
+                camera::zoom_camera,
+                touch::pinch_to_zoom,
+                camera::pan_camera,
+                camera::reset_camera,
+            )
+                .chain(),
+        )
+        .add_systems(
+            Update,
+            (
+                screenshot::take_screenshot,
+                (recording::toggle_recording, recording::capture_clip_frames).chain(),
+                (shake::toggle_reduced_motion, shake::add_screen_shake).chain(),
+                (
+                    background::cycle_background,
+                    background::show_background,
+                    background::animate_gradient,
+                    background::move_stars,
+                )
+                    .chain(),
+                (
+                    theme::cycle_theme,
+                    theme::apply_background_theme,
+                    theme::apply_hud_theme,
+                )
+                    .chain(),
+            ),
+        )
+        .add_systems(PostUpdate, cage::apply_cage_theme)
+        .add_systems(Update, stats::write_stats)
+        .add_systems(PreUpdate, shake::unshake_camera)
+        .add_systems(PreUpdate, console::type_in_console.after(InputSystem))
+        .add_systems(
+            Update,
+            (console::run_console_commands, console::show_console).chain(),
+        )
+        .add_systems(
+            PostUpdate,
+            shake::shake_camera.before(TransformSystem::TransformPropagate),
+        )
+        .add_systems(Last, ball_assets::prune_materials)
+        .add_systems(FixedFirst, hud::start_fixed_update_timer)
+        .add_systems(FixedLast, hud::stop_fixed_update_timer)
+        .add_systems(
+            Update,
+            (
+                (hud::count_spawned_balls, hud::update_ball_counter).chain(),
+                (
+                    hud::measure_fixed_update_time,
+                    hud::toggle_performance_overlay,
+                    hud::update_performance_overlay,
+                )
+                    .chain(),
+                (hud::toggle_help_overlay, hud::update_help_overlay),
+                (
+                    score::add_combos,
+                    score::score_collisions,
+                    score::score_escapes,
+                    hud::update_score_display,
+                    hud::update_combo_display,
+                )
+                    .chain(),
+            ),
+        )
+        .insert_resource(GravityField(GRAVITY))
+        .init_resource::<Settings>()
+        .init_resource::<Keybindings>()
+        .init_resource::<keybindings::GamepadBindings>()
+        .init_resource::<Theme>()
+        .init_resource::<BallPalette>()
+        .init_resource::<AudioSettings>()
+        .init_resource::<SoundCooldowns>()
+        .init_resource::<audio::AudioUnlocked>()
+        .init_resource::<camera::CameraZoom>()
+        .init_resource::<recording::ClipRecorder>()
+        .init_resource::<shake::ScreenShake>()
+        .init_resource::<hud::SpawnedBalls>()
+        .init_resource::<score::Score>()
+        .init_resource::<score::LastCombo>()
+        .init_resource::<menu::GameMode>()
+        .init_resource::<endless::DifficultyCurve>()
+        .init_resource::<EndlessRun>()
+        .init_resource::<players::PlayerScores>()
+        .init_resource::<powerup::PowerUpTimer>()
+        .init_resource::<ActiveEffects>()
+        .init_resource::<achievements::Achievements>()
+        .init_resource::<hud::FixedUpdateTimer>()
+        .init_resource::<debug::RecentContacts>()
+        .init_resource::<time_control::PhysicsPaused>()
+        .init_resource::<time_control::RewindBuffer>()
+        .init_resource::<replay::ReplayRecorder>()
+        .init_resource::<replay::ReplayFile>()
+        .init_resource::<Wind>()
+        .init_resource::<EnergyStats>()
+        .init_resource::<stats::StatsLog>()
+        .init_resource::<forces::ForceGenerators>()
+        .init_resource::<console::Console>()
+        .init_resource::<LaunchDrag>()
+        .init_resource::<touch::TouchGestures>()
+        .init_resource::<ShrinkingCage>()
+        .insert_resource(EnergyLogTimer(Timer::from_seconds(
+            ENERGY_LOG_INTERVAL,
+            TimerMode::Repeating,
+        )))
+        // Web servers answer requests for the `.meta` files, which don't exist, with errors.
+        .insert_resource(AssetMetaCheck::Never)
+        .add_plugins((
+            DefaultPlugins.set(WindowPlugin {
+                primary_window: Some(Window {
+                    title: "Bevy Balls".to_string(),
+                    // On the web, draws into the page's canvas and follows its size.
+                    canvas: Some(WEB_CANVAS.to_string()),
+                    fit_canvas_to_parent: true,
+                    ..Default::default()
+                }),
+                ..Default::default()
+            }),
+            FrameTimeDiagnosticsPlugin,
+            metrics::MetricsPlugin,
+        ))
+        .register_diagnostic(hud::fixed_update_diagnostic())
+        // Needs the audio output set up by `DefaultPlugins` to be played.
+        .add_audio_source::<tone::Tone>()
+        .add_audio_source::<tone::Pad>()
+        // After `DefaultPlugins`, so a bad seed's warning gets logged.
+        .insert_resource(SimRng::from_seed(rng::seed_from_args()))
+        .register_type::<Ball>()
+        .register_type::<Velocity>()
+        .register_type::<BallColor>()
+        .register_type::<Radius>()
+        .register_type::<Spin>()
+        .register_type::<Acceleration>()
+        .register_type::<Gravity>()
+        .register_type::<GravityField>()
+        .register_type::<GravityWell>()
+        .register_type::<Drag>()
+        .register_type::<Hp>()
+        .register_type::<BallKind>();
+
+    // Everything that accelerates the balls, before they move.
+    let accelerate_balls = (
+        wake_on_gravity_change,
+        apply_gravity,
+        apply_gravity_wells,
+        forces::apply_force_generators,
+        apply_wind,
+        apply_colour_charge,
+        apply_drag,
+        pull_grabbed_balls,
+    )
+        .chain();
+
+    #[cfg(not(feature = "rapier"))]
+    app.add_systems(
+        FixedUpdate,
+        (
+            accelerate_balls,
+            apply_velocity,
+            cluster::solve_clusters,
+            apply_spin,
+            cage::rotate_cages,
+            cage::follow_cursor,
+            obstacle::carry_obstacles,
+            obstacle::move_obstacles,
+            cage::collide_cage,
+            cage::detect_escaped_balls,
+            cage::catch_escaped_balls,
+            cage::transfer_through_portals,
+            obstacle::collide_obstacles,
+            collide_others,
+            cluster::stick_balls,
+            merge_balls,
+            split_balls,
+            update_sleeping,
+            track_energy,
+            time_control::record_ball_states,
+        )
+            .chain()
+            .run_if(in_state(AppState::Running).and_then(time_control::physics_running)),
+    );
+
+    // Rapier moves the balls and bounces them off each other and the cage walls instead.
+    #[cfg(feature = "rapier")]
+    app.add_plugins(rapier::RapierBackendPlugin).add_systems(
+        FixedUpdate,
+        (
+            (accelerate_balls, rapier::apply_accelerations)
+                .chain()
+                .before(bevy_rapier2d::plugin::PhysicsSet::SyncBackend),
+            (
+                rapier::send_collision_events,
+                rapier::read_velocities,
+                merge_balls,
+                split_balls,
+                track_energy,
+                time_control::record_ball_states,
+            )
+                .chain()
+                .after(bevy_rapier2d::plugin::PhysicsSet::Writeback),
+        )
+            .run_if(in_state(AppState::Running).and_then(time_control::physics_running)),
+    );
+
+    #[cfg(not(target_arch = "wasm32"))]
+    app.add_plugins(instancing::BallInstancingPlugin);
+
+    #[cfg(feature = "scripting")]
+    app.add_plugins(scripting::ScriptingPlugin);
+
+    #[cfg(feature = "inspector")]
+    app.add_plugins(bevy_inspector_egui::quick::WorldInspectorPlugin::new());
+
+    forces::register_forces_from_args(&mut app);
+
+    app.run();
 }
 
-#[derive(Component)]
+#[derive(Component, Reflect)]
+#[reflect(Component)]
 struct Ball;
 
-#[derive(Component, Deref, DerefMut)]
+#[derive(Component, Reflect, Deref, DerefMut)]
+#[reflect(Component)]
 struct Velocity(Vec2);
 
+#[derive(Component, Reflect)]
+#[reflect(Component)]
+struct BallColor(Color);
+
+/// The radius balls collide with. Their transform is scaled to match.
+#[derive(Component, Reflect)]
+#[reflect(Component)]
+struct Radius(f32);
+
+/// How fast a ball turns, in radians per second. Positive is counter-clockwise.
+#[derive(Component, Reflect, Default)]
+#[reflect(Component)]
+struct Spin(f32);
+
+/// How a single ball is drawn, starting out as [`Settings::ball_appearance`].
 #[derive(Component)]
+struct Appearance(BallAppearance);
+
+/// Accumulates the accelerations from all forces during a fixed step, consumed by [`apply_velocity`].
+#[derive(Component, Reflect, Default, Deref, DerefMut)]
+#[reflect(Component)]
+struct Acceleration(Vec2);
+
+/// Multiplier on the global [`GravityField`] for this entity.
+#[derive(Component, Reflect)]
+#[reflect(Component)]
 struct Gravity(f32);
 
+/// The gravitational acceleration applied to everything with a [`Gravity`] component.
+#[derive(Resource, Reflect, Deref, DerefMut)]
+#[reflect(Resource)]
+struct GravityField(Vec2);
+
+/// Attracts everything with a [`Gravity`] component, or repels it if `strength` is negative.
+#[derive(Component, Reflect)]
+#[reflect(Component)]
+struct GravityWell {
+    strength: f32,
+    /// The power of the distance the force is divided by, `2.0` being an inverse-square law.
+    falloff: f32,
+}
+
+#[derive(Resource)]
+struct Wind {
+    gust: f32,
+    gust_target: f32,
+    gust_timer: Timer,
+}
+
+impl Default for Wind {
+    fn default() -> Self {
+        Self {
+            gust: 0.0,
+            gust_target: 0.0,
+            gust_timer: Timer::from_seconds(0.0, TimerMode::Repeating),
+        }
+    }
+}
+
+#[derive(Component, Reflect)]
+#[reflect(Component)]
+struct Drag(f32);
+
 #[derive(Component)]
 struct Collision;
 
+/// Number of consecutive fixed steps this ball has been slower than [`SLEEP_SPEED`].
+#[derive(Component, Default)]
+struct RestingSteps(u32);
+
+/// When a ball was spawned, measured from startup.
+#[derive(Component)]
+struct SpawnedAt(Duration);
+
+/// Despawns a ball once it runs out.
+#[derive(Component)]
+struct Lifetime(Timer);
+
+/// How many more collisions a ball can take. It's destroyed when this reaches zero.
+#[derive(Component, Reflect)]
+#[reflect(Component)]
+struct Hp(u32);
+
+/// Where a ball has been over the last few frames, oldest first.
+#[derive(Component, Default)]
+struct Trail(VecDeque<Vec2>);
+
+/// Marks a ball held with the right mouse button.
+#[derive(Component)]
+struct Grabbed;
+
+/// Marks a ball that has settled. Sleeping balls aren't moved until something hits them.
+#[derive(Component)]
+struct Sleeping;
+
 #[derive(Event)]
 struct CageCollisionEvent {
-    #[allow(dead_code)]
     entity: Entity,
+    /// How fast the ball was moving into the wall.
+    impact_speed: f32,
+    /// Where the ball touched the wall.
+    point: Vec2,
+    /// Points away from the wall, towards the ball.
+    normal: Vec2,
 }
 
 #[derive(Event)]
 struct OtherCollisionEvent {
-    #[allow(dead_code)]
     self_entity: Entity,
-    #[allow(dead_code)]
     other_entity: Entity,
+    /// How fast the two were moving towards each other along the contact normal.
+    impact_speed: f32,
+    /// Where the two touched.
+    point: Vec2,
+    /// Points away from the other entity, towards the ball.
+    normal: Vec2,
+}
+
+/// Asks for every ball to be cleared. Sent by [`Action::Reset`], the menus and the console.
+#[derive(Event)]
+struct ResetEvent;
+
+/// A ball ran out of [`Hp`] and was destroyed.
+#[derive(Event)]
+struct BallDestroyedEvent {
+    position: Vec2,
+    colour: Color,
+}
+
+/// Two balls of the same size touched and became one.
+#[derive(Event)]
+struct BallsMergedEvent {
+    /// The ball that's left, grown to the size of both.
+    merged: Entity,
 }
 
 #[derive(Resource)]
-struct CollisionSound(Handle<AudioSource>);
+struct BallTexture(Handle<Image>);
+
+/// Where a drag-to-launch started, and the cage the ball will be spawned in.
+#[derive(Resource, Default)]
+struct LaunchDrag {
+    start: Option<(Vec2, Entity)>,
+}
 
+/// Total energy of all balls, assuming unit mass, as of the last fixed step.
+///
+/// Potential energy is measured relative to the cage center and only includes the [`GravityField`].
+#[derive(Resource, Default)]
+struct EnergyStats {
+    kinetic: f32,
+    potential: f32,
+}
+
+impl EnergyStats {
+    fn total(&self) -> f32 {
+        self.kinetic + self.potential
+    }
+}
+
+#[derive(Resource)]
+struct EnergyLogTimer(Timer);
+
+#[allow(clippy::too_many_arguments)]
 fn spawn_ball(
+    commands: &mut Commands,
+    materials: &mut ResMut<Assets<ColorMaterial>>,
+    ball_assets: &mut BallAssets,
+    rng: &mut SimRng,
+    palette: &BallPalette,
+    settings: &Settings,
+    cage: Entity,
+    position: Vec2,
+) -> Entity {
+    let colour = palette.random_colour(rng);
+    spawn_sized_ball(
+        commands,
+        materials,
+        ball_assets,
+        rng,
+        cage,
+        position,
+        colour,
+        BALL_RADIUS / 2.0,
+        settings.ball_speed,
+    )
+}
+
+#[allow(clippy::too_many_arguments)]
+fn spawn_sized_ball(
+    commands: &mut Commands,
+    materials: &mut ResMut<Assets<ColorMaterial>>,
+    ball_assets: &mut BallAssets,
+    rng: &mut SimRng,
+    cage: Entity,
+    position: Vec2,
+    colour: Color,
+    radius: f32,
+    speed: f32,
+) -> Entity {
+    let starting_direction = Vec2::new(rng.gen_range(-1.0..1.0), rng.gen_range(-1.0..1.0));
+
+    commands
+        .spawn((
+            MaterialMesh2dBundle {
+                mesh: ball_assets.mesh.clone().into(),
+                material: ball_assets.material(materials, colour),
+                transform: Transform {
+                    translation: position.extend(1.0),
+                    scale: Vec3::new(radius * 2.0, radius * 2.0, 1.0),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            Ball,
+            BallColor(colour),
+            Velocity(starting_direction.normalize() * speed),
+            Radius(radius),
+            Spin::default(),
+            BallKind::default(),
+            Acceleration::default(),
+            Gravity(BALL_GRAVITY_SCALE),
+            Drag(BALL_DRAG),
+            Collision,
+            RestingSteps::default(),
+            Trail::default(),
+            InCage(cage),
+        ))
+        .id()
+}
+
+fn stamp_spawn_time(
+    ball_query: Query<Entity, Added<Ball>>,
+    mut commands: Commands,
+    time: Res<Time>,
+) {
+    for entity in &ball_query {
+        commands.entity(entity).insert(SpawnedAt(time.elapsed()));
+    }
+}
+
+fn add_lifetime(
+    ball_query: Query<Entity, Added<Ball>>,
+    mut commands: Commands,
+    settings: Res<Settings>,
+) {
+    let Some(lifetime) = settings.ball_lifetime else {
+        return;
+    };
+    for entity in &ball_query {
+        commands
+            .entity(entity)
+            .insert(Lifetime(Timer::from_seconds(lifetime, TimerMode::Once)));
+    }
+}
+
+/// Makes balls spawned from then on survive only [`BALL_HIT_POINTS`] hits, or any number, with F.
+fn toggle_hit_points(actions: Actions, mut settings: ResMut<Settings>) {
+    if actions.just_pressed(Action::ToggleHitPoints) {
+        settings.ball_hit_points = match settings.ball_hit_points {
+            Some(_) => None,
+            None => Some(BALL_HIT_POINTS),
+        };
+    }
+}
+
+fn add_hit_points(
+    ball_query: Query<Entity, Added<Ball>>,
+    mut commands: Commands,
+    settings: Res<Settings>,
+) {
+    let Some(hit_points) = settings.ball_hit_points else {
+        return;
+    };
+    for entity in &ball_query {
+        commands.entity(entity).insert(Hp(hit_points));
+    }
+}
+
+/// Takes a hit point off every ball that hit something, and destroys the ones that run out.
+#[allow(clippy::type_complexity)]
+fn damage_balls(
+    mut wall_collision_events: EventReader<CageCollisionEvent>,
+    mut ball_collision_events: EventReader<OtherCollisionEvent>,
+    mut ball_query: Query<(&mut Hp, &Transform, &BallColor), With<Ball>>,
+    mut destroyed_events: EventWriter<BallDestroyedEvent>,
+    mut commands: Commands,
+) {
+    let hits = wall_collision_events
+        .read()
+        .map(|event| event.entity)
+        .chain(ball_collision_events.read().map(|event| event.self_entity));
+    for entity in hits {
+        let Ok((mut hp, transform, colour)) = ball_query.get_mut(entity) else {
+            continue;
+        };
+        // Already destroyed by an earlier hit this frame.
+        if hp.0 == 0 {
+            continue;
+        }
+        hp.0 -= 1;
+        if hp.0 > 0 {
+            continue;
+        }
+
+        commands.entity(entity).despawn();
+        destroyed_events.send(BallDestroyedEvent {
+            position: transform.translation.truncate(),
+            colour: colour.0,
+        });
+    }
+}
+
+/// Bursts destroyed balls into particles, with a pop.
+fn burst_destroyed_balls(
+    mut destroyed_events: EventReader<BallDestroyedEvent>,
+    mut commands: Commands,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    sound: Res<CollisionSound>,
+    audio_settings: Res<AudioSettings>,
+) {
+    let mut destroyed = false;
+    for event in destroyed_events.read() {
+        particle::spawn_particle_burst(
+            &mut commands,
+            &mut materials,
+            &mut meshes,
+            event.position,
+            event.colour,
+        );
+        destroyed = true;
+    }
+    // One pop a frame, however many balls went.
+    if destroyed {
+        audio::play_sound(
+            &mut commands,
+            &sound.with_speed(POP_SOUND_SPEED),
+            1.0,
+            &audio_settings,
+        );
+    }
+}
+
+/// Fades balls out over the last [`LIFETIME_FADE`] seconds of their lifetime, then despawns them.
+#[allow(clippy::type_complexity)]
+fn age_balls(
+    mut ball_query: Query<(Entity, &mut Lifetime, &mut Handle<ColorMaterial>), With<Ball>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut ball_assets: ResMut<BallAssets>,
+    mut commands: Commands,
+    time: Res<Time>,
+) {
+    for (entity, mut lifetime, mut material) in &mut ball_query {
+        if lifetime.0.tick(time.delta()).finished() {
+            commands.entity(entity).despawn();
+            continue;
+        }
+        let remaining = lifetime.0.remaining_secs();
+        if remaining < LIFETIME_FADE {
+            ball_assets.restyle(&mut materials, &mut material, |material| {
+                material.color.set_a(remaining / LIFETIME_FADE);
+            });
+        }
+    }
+}
+
+/// Keeps long runs from grinding to a halt by despawning the oldest balls over the cap.
+fn despawn_oldest_balls(
+    ball_query: Query<(Entity, &SpawnedAt), With<Ball>>,
+    mut commands: Commands,
+    settings: Res<Settings>,
+) {
+    let excess = ball_query.iter().len().saturating_sub(settings.max_balls);
+    if excess == 0 {
+        return;
+    }
+
+    let mut balls: Vec<(Entity, Duration)> = ball_query
+        .iter()
+        .map(|(entity, spawned_at)| (entity, spawned_at.0))
+        .collect();
+    balls.sort_by_key(|(_, spawned_at)| *spawned_at);
+    for (entity, _) in balls.into_iter().take(excess) {
+        commands.entity(entity).despawn();
+    }
+}
+
+fn spawn_gravity_well(
     commands: &mut Commands,
     materials: &mut ResMut<Assets<ColorMaterial>>,
     meshes: &mut ResMut<Assets<Mesh>>,
+    position: Vec2,
+    strength: f32,
 ) {
-    let colour = Color::rgb(
-        rand::random::<f32>(),
-        rand::random::<f32>(),
-        rand::random::<f32>(),
-    );
-    let starting_direction = Vec2::new(
-        rand::random::<f32>() * 2.0 - 1.0,
-        rand::random::<f32>() * 2.0 - 1.0,
-    );
+    let colour = if strength < 0.0 {
+        REPULSOR_COLOR
+    } else {
+        ATTRACTOR_COLOR
+    };
 
     commands.spawn((
         MaterialMesh2dBundle {
-            mesh: meshes.add(Circle::default()).into(),
+            mesh: meshes
+                .add(Circle {
+                    radius: GRAVITY_WELL_RADIUS,
+                })
+                .into(),
             material: materials.add(colour),
-            transform: Transform {
-                translation: Vec3::new(0.0, 0.0, 1.0),
-                scale: Vec3::new(BALL_RADIUS, BALL_RADIUS, 1.0),
-                ..Default::default()
-            },
+            transform: Transform::from_translation(position.extend(0.5)),
             ..Default::default()
         },
-        Ball,
-        Velocity(starting_direction.normalize() * BALL_STARTING_SPEED),
-        Gravity(BALL_GRAVITY),
-        Collision,
+        GravityWell {
+            strength,
+            falloff: GRAVITY_WELL_FALLOFF,
+        },
     ));
 }
 
@@ -101,182 +1060,1235 @@ fn setup(
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<ColorMaterial>>,
     asset_server: Res<AssetServer>,
+    audio_settings: Res<AudioSettings>,
 ) {
     commands.spawn(Camera2dBundle::default());
 
-    let ball_collision_sound = asset_server.load("sounds/wall_collision.ogg");
-    commands.insert_resource(CollisionSound(ball_collision_sound));
-
-    // Cage outside
-    commands.spawn((MaterialMesh2dBundle {
-        mesh: meshes
-            .add(Circle {
-                radius: CAGE_RADIUS + CAGE_WALL_THICKNESS,
-            })
-            .into(),
-        material: materials.add(CAGE_COLOR),
-        ..Default::default()
-    },));
-
-    // Cage inside
-    commands.spawn((MaterialMesh2dBundle {
-        mesh: meshes
-            .add(Circle {
-                radius: CAGE_RADIUS,
-            })
-            .into(),
-        transform: Transform {
-            translation: Vec3::new(0.0, 0.0, 0.1),
-            ..Default::default()
-        },
-        material: materials.add(BACKGROUND_COLOR),
-        ..Default::default()
-    },));
+    commands.insert_resource(CollisionSound::new(
+        asset_server.load(&audio_settings.wall_sound_path),
+        asset_server.load(&audio_settings.ball_sound_path),
+    ));
+    commands.insert_resource(BallTexture(asset_server.load("sprites/ball.png")));
+    commands.insert_resource(BallAssets::new(&mut meshes));
+
+    let cage = cage::spawn_cage(
+        &mut commands,
+        &mut materials,
+        &mut meshes,
+        Vec3::ZERO,
+        Cage::new(CAGE_RADIUS),
+    );
+    commands.spawn((cannon::Cannon::default(), InCage(cage)));
+}
+
+/// Moves the balls by their velocity and the step's acceleration, using the [`Integrator`] from
+/// the settings.
+#[allow(clippy::type_complexity)]
+#[cfg_attr(feature = "rapier", allow(dead_code))]
+fn apply_velocity(
+    mut query: Query<(&mut Transform, &mut Velocity, &mut Acceleration), Without<Sleeping>>,
+    settings: Res<Settings>,
+    time: Res<Time>,
+) {
+    let delta = time.delta_seconds();
+    for (mut transform, mut velocity, mut acceleration) in &mut query {
+        let displacement = match settings.integrator {
+            // Updating the velocity first (semi-implicit Euler) keeps orbits and bounces from
+            // slowly gaining energy.
+            Integrator::Euler => {
+                velocity.0 += acceleration.0 * delta;
+                velocity.0 * delta
+            }
+            Integrator::Verlet => {
+                let displacement = velocity.0 * delta + 0.5 * acceleration.0 * delta * delta;
+                velocity.0 += acceleration.0 * delta;
+                displacement
+            }
+        };
+        transform.translation += displacement.extend(0.0);
+        acceleration.0 = Vec2::ZERO;
+    }
 }
 
-fn apply_velocity(mut query: Query<(&mut Transform, &Velocity)>, time: Res<Time>) {
-    for (mut transform, velocity) in &mut query {
-        transform.translation.x += velocity.x * time.delta_seconds();
-        transform.translation.y += velocity.y * time.delta_seconds();
+fn apply_gravity(
+    mut query: Query<(&mut Acceleration, &Gravity), Without<Sleeping>>,
+    gravity_field: Res<GravityField>,
+    effects: Res<ActiveEffects>,
+) {
+    let field = if effects.is_active(PowerUp::AntiGravity) {
+        -gravity_field.0
+    } else {
+        gravity_field.0
+    };
+    for (mut acceleration, gravity) in &mut query {
+        acceleration.0 += field * gravity.0;
     }
 }
 
-fn apply_gravity(mut query: Query<(&mut Velocity, &Gravity)>, time: Res<Time>) {
-    for (mut velocity, gravity) in &mut query {
-        velocity.y += gravity.0 * time.delta_seconds();
+#[allow(clippy::type_complexity)]
+fn apply_gravity_wells(
+    mut query: Query<(&Transform, &mut Acceleration, &Gravity), Without<Sleeping>>,
+    well_query: Query<(&Transform, &GravityWell)>,
+) {
+    for (transform, mut acceleration, gravity) in &mut query {
+        let position = transform.translation.truncate();
+        for (well_transform, well) in &well_query {
+            let offset = well_transform.translation.truncate() - position;
+            let distance = offset.length().max(GRAVITY_WELL_MIN_DISTANCE);
+            let strength = well.strength / distance.powf(well.falloff);
+            acceleration.0 += offset.normalize_or_zero() * strength * gravity.0;
+        }
     }
 }
 
-fn collide_cage(
-    mut ball_query: Query<(Entity, &mut Transform, &mut Velocity, &Collision)>,
-    mut collision_events: EventWriter<CageCollisionEvent>,
+fn apply_wind(
+    mut query: Query<&mut Acceleration, (With<Ball>, Without<Sleeping>)>,
+    mut wind: ResMut<Wind>,
+    mut rng: ResMut<SimRng>,
+    settings: Res<Settings>,
+    time: Res<Time>,
 ) {
-    for (entity, mut ball_transform, mut ball_velocity, _) in &mut ball_query {
-        let mut ball_position = ball_transform.translation.truncate();
-        let ball_radius = BALL_RADIUS;
+    // A bad interval keeps whatever the timer had, rather than panicking.
+    let interval = Duration::try_from_secs_f32(settings.wind_gust_interval)
+        .unwrap_or(wind.gust_timer.duration());
+    wind.gust_timer.set_duration(interval);
+    if wind.gust_timer.tick(time.delta()).just_finished() {
+        wind.gust_target = rng.gen_range(-1.0..1.0) * settings.wind_gust_strength;
+    }
+    // Ease towards the target so gusts build up and die down instead of snapping.
+    let gust = wind.gust;
+    wind.gust = gust + (wind.gust_target - gust) * (1.0 - (-time.delta_seconds()).exp());
 
-        let cage_position = Vec2::ZERO;
-        let cage_radius = CAGE_RADIUS;
+    let strength = settings.wind_strength + wind.gust;
+    if strength == 0.0 {
+        return;
+    }
+    for mut acceleration in &mut query {
+        acceleration.x += strength;
+    }
+}
 
-        let distance = ball_position.distance(cage_position);
-        if distance + (ball_radius / 2.0) > cage_radius {
-            let normal = (cage_position - ball_position).normalize();
-            ball_velocity.0 = {
-                let velocity = ball_velocity.0;
-                velocity - 2.0 * velocity.dot(normal) * normal
-            };
+fn colour_vector(colour: Color) -> Vec3 {
+    let [r, g, b, _] = colour.as_rgba_f32();
+    Vec3::new(r, g, b)
+}
+
+#[allow(clippy::type_complexity)]
+fn apply_colour_charge(
+    mut query: Query<
+        (
+            Entity,
+            &Transform,
+            &mut Acceleration,
+            &BallColor,
+            Has<Sleeping>,
+        ),
+        With<Ball>,
+    >,
+    settings: Res<Settings>,
+) {
+    if !settings.charge_enabled {
+        return;
+    }
 
-            let overlap = ball_radius / 2.0 + distance - cage_radius;
-            ball_position += overlap * normal;
-            ball_transform.translation = ball_position.extend(ball_transform.translation.z);
+    let charges: Vec<(Entity, Vec2, Vec3)> = query
+        .iter()
+        .map(|(entity, transform, _, colour, _)| {
+            (
+                entity,
+                transform.translation.truncate(),
+                colour_vector(colour.0),
+            )
+        })
+        .collect();
+    for (entity, transform, mut acceleration, colour, sleeping) in &mut query {
+        if sleeping {
+            continue;
+        }
+        let position = transform.translation.truncate();
+        let colour = colour_vector(colour.0);
 
-            collision_events.send(CageCollisionEvent { entity });
+        for (other_entity, other_position, other_colour) in charges.iter() {
+            if *other_entity == entity {
+                continue;
+            }
+            let offset = position - *other_position;
+            let distance = offset.length().max(CHARGE_MIN_DISTANCE);
+            // 1.0 for identical colours, 0.0 for opposite corners of the colour cube.
+            let similarity = 1.0 - colour.distance(*other_colour) / 3.0_f32.sqrt();
+            // Positive pushes this ball away from the other one.
+            let strength =
+                settings.charge_strength * (similarity * 2.0 - 1.0) / (distance * distance);
+            acceleration.0 += offset.normalize_or_zero() * strength;
         }
     }
 }
 
+fn apply_drag(mut query: Query<(&mut Velocity, &Drag), Without<Sleeping>>, time: Res<Time>) {
+    for (mut velocity, drag) in &mut query {
+        velocity.0 *= (-drag.0 * time.delta_seconds()).exp();
+    }
+}
+
+/// A ball's state at the start of the ball-to-ball collision pass, which every contact is worked
+/// out from.
+struct BallBody {
+    entity: Entity,
+    position: Vec2,
+    velocity: Vec2,
+    radius: f32,
+    kind: BallKind,
+    sleeping: bool,
+    in_cage: InCage,
+    in_cluster: Option<InCluster>,
+}
+
+/// Everything a ball's contacts do to it and the balls it touched, applied once they've all been
+/// found.
+struct BallContacts {
+    entity: Entity,
+    velocity: Vec2,
+    /// How far the ball is pushed out of the balls it overlaps.
+    displacement: Vec2,
+    events: Vec<OtherCollisionEvent>,
+    /// Sleeping balls that were hit hard enough to wake up.
+    woken: Vec<Entity>,
+}
+
+/// Finds every contact in parallel, from a snapshot of the balls, then applies them all.
+#[allow(clippy::type_complexity)]
+#[cfg_attr(feature = "rapier", allow(dead_code))]
 fn collide_others(
-    mut ball_query: Query<(Entity, &mut Transform, &mut Velocity, &Collision), With<Ball>>,
+    mut commands: Commands,
+    mut ball_query: Query<
+        (
+            Entity,
+            &mut Transform,
+            &mut Velocity,
+            &Radius,
+            &BallKind,
+            &Collision,
+            Has<Sleeping>,
+            &InCage,
+            Option<&InCluster>,
+        ),
+        With<Ball>,
+    >,
     mut collision_events: EventWriter<OtherCollisionEvent>,
+    mut diagnostics: Diagnostics,
+    settings: Res<Settings>,
 ) {
-    let ball_positions: Vec<(Entity, Vec2)> = ball_query
+    let bodies: Vec<BallBody> = ball_query
         .iter()
-        .map(|(entity, transform, _, _)| (entity, transform.translation.truncate()))
+        .map(
+            |(entity, transform, velocity, radius, kind, _, sleeping, in_cage, in_cluster)| {
+                BallBody {
+                    entity,
+                    position: transform.translation.truncate(),
+                    velocity: velocity.0,
+                    radius: radius.0,
+                    kind: *kind,
+                    sleeping,
+                    in_cage: *in_cage,
+                    in_cluster: in_cluster.copied(),
+                }
+            },
+        )
         .collect();
-    for (entity, mut ball_transform, mut ball_velocity, _) in &mut ball_query {
-        let ball_position = ball_transform.translation.truncate();
-        let ball_radius = BALL_RADIUS;
 
-        for (other_entity, other_position) in ball_positions.iter() {
-            if ball_position == *other_position {
-                continue;
-            }
-            let other_radius = BALL_RADIUS;
-
-            let distance = ball_position.distance(*other_position);
-            if distance < (ball_radius / 2.) + (other_radius / 2.) {
-                let normal = (*other_position - ball_position).normalize();
-                ball_velocity.0 = {
-                    let velocity = ball_velocity.0;
-                    velocity - 2.0 * velocity.dot(normal) * normal
-                };
-
-                let overlap = (ball_radius / 2.) + (other_radius / 2.) - distance;
-                ball_transform.translation -= overlap * normal.extend(0.0);
-
-                collision_events.send(OtherCollisionEvent {
-                    self_entity: entity,
-                    other_entity: *other_entity,
-                });
+    let task_pool = ComputeTaskPool::get();
+    let chunk_size = bodies.len().div_ceil(task_pool.thread_num()).max(1);
+    let contacts: Vec<Vec<BallContacts>> = task_pool.scope(|scope| {
+        for chunk in bodies.chunks(chunk_size) {
+            let bodies = &bodies;
+            let restitution = settings.restitution;
+            scope.spawn(async move {
+                chunk
+                    .iter()
+                    .filter_map(|body| find_contacts(body, bodies, restitution))
+                    .collect()
+            });
+        }
+    });
+
+    diagnostics.add_measurement(metrics::BROAD_PHASE_PAIRS, || {
+        let mut per_cage: HashMap<Entity, usize> = HashMap::new();
+        for body in bodies.iter().filter(|body| body.kind.collides_with_balls()) {
+            *per_cage.entry(body.in_cage.0).or_default() += 1;
+        }
+        bodies
+            .iter()
+            .filter(|body| !body.sleeping && body.kind.collides_with_balls())
+            .map(|body| per_cage[&body.in_cage.0] - 1)
+            .sum::<usize>() as f64
+    });
+    diagnostics.add_measurement(metrics::NARROW_PHASE_HITS, || {
+        contacts
+            .iter()
+            .flatten()
+            .map(|ball| ball.events.len())
+            .sum::<usize>() as f64
+    });
+
+    for contacts in contacts.into_iter().flatten() {
+        if let Ok((_, mut transform, mut velocity, ..)) = ball_query.get_mut(contacts.entity) {
+            velocity.0 = contacts.velocity;
+            transform.translation += contacts.displacement.extend(0.0);
+        }
+        for entity in contacts.woken {
+            commands.entity(entity).remove::<Sleeping>();
+        }
+        collision_events.send_batch(contacts.events);
+    }
+}
+
+/// The contacts of a single ball with all the others, or `None` if it isn't touching any.
+fn find_contacts(body: &BallBody, bodies: &[BallBody], restitution: f32) -> Option<BallContacts> {
+    // Sleeping balls only get hit, they don't move themselves. Ghosts pass right through.
+    if body.sleeping || !body.kind.collides_with_balls() {
+        return None;
+    }
+    let ball_mass = body.radius.powi(2) * body.kind.mass_scale();
+    let restitution = body.kind.restitution(restitution);
+    let mut contacts = BallContacts {
+        entity: body.entity,
+        velocity: body.velocity,
+        displacement: Vec2::ZERO,
+        events: Vec::new(),
+        woken: Vec::new(),
+    };
+
+    for other in bodies {
+        if body.position == other.position
+            || body.in_cage != other.in_cage
+            || !other.kind.collides_with_balls()
+        {
+            continue;
+        }
+        // Balls stuck together are held in place by their cluster instead.
+        if body.in_cluster.is_some() && body.in_cluster == other.in_cluster {
+            continue;
+        }
+
+        let distance = body.position.distance(other.position);
+        if distance >= body.radius + other.radius {
+            continue;
+        }
+        if other.sleeping && contacts.velocity.length() > SLEEP_SPEED {
+            contacts.woken.push(other.entity);
+        }
+
+        let normal = (other.position - body.position).normalize();
+        let impact_speed = (contacts.velocity - other.velocity).dot(normal).max(0.0);
+        // 1.0 for balls of the same mass. A heavier ball barely deflects off a lighter one.
+        let other_mass = other.radius.powi(2) * other.kind.mass_scale();
+        let mass_ratio = 2.0 * other_mass / (ball_mass + other_mass);
+        let velocity = contacts.velocity;
+        contacts.velocity =
+            velocity - (1.0 + restitution) * mass_ratio * velocity.dot(normal) * normal;
+
+        let overlap = body.radius + other.radius - distance;
+        contacts.displacement -= overlap * normal;
+
+        contacts.events.push(OtherCollisionEvent {
+            self_entity: body.entity,
+            other_entity: other.entity,
+            impact_speed,
+            point: body.position + normal * body.radius,
+            normal: -normal,
+        });
+    }
+
+    (!contacts.events.is_empty()).then_some(contacts)
+}
+
+/// Merges touching balls of the same size into one, conserving their area and momentum. The
+/// merged ball's colour is a mix of both.
+#[allow(clippy::type_complexity)]
+fn merge_balls(
+    mut collision_events: EventReader<OtherCollisionEvent>,
+    mut ball_query: Query<
+        (
+            &mut Transform,
+            &mut Velocity,
+            &mut Radius,
+            &mut BallColor,
+            &mut Handle<ColorMaterial>,
+        ),
+        With<Ball>,
+    >,
+    mut merged_events: EventWriter<BallsMergedEvent>,
+    mut commands: Commands,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut ball_assets: ResMut<BallAssets>,
+    settings: Res<Settings>,
+) {
+    if !settings.merge_enabled {
+        collision_events.clear();
+        return;
+    }
+
+    // Both balls report the same collision, and a ball can only be merged away once.
+    let mut absorbed = HashSet::new();
+    for event in collision_events.read() {
+        let (entity, other_entity) = (event.self_entity, event.other_entity);
+        if absorbed.contains(&entity) || absorbed.contains(&other_entity) {
+            continue;
+        }
+        let Ok([(mut transform, mut velocity, mut radius, mut colour, mut material), other]) =
+            ball_query.get_many_mut([entity, other_entity])
+        else {
+            continue;
+        };
+        let (other_transform, other_velocity, other_radius, other_colour, _) = other;
+        if (radius.0 - other_radius.0).abs() > MERGE_SIZE_TOLERANCE {
+            continue;
+        }
+
+        // Mass goes with area, so weighting by it conserves momentum.
+        let area = radius.0.powi(2);
+        let other_area = other_radius.0.powi(2);
+        let total_area = area + other_area;
+        velocity.0 = (velocity.0 * area + other_velocity.0 * other_area) / total_area;
+        let position = (transform.translation.truncate() * area
+            + other_transform.translation.truncate() * other_area)
+            / total_area;
+        transform.translation = position.extend(transform.translation.z);
+        radius.0 = total_area.sqrt();
+        transform.scale = Vec3::new(radius.0 * 2.0, radius.0 * 2.0, 1.0);
+        let blended = (colour_vector(colour.0) * area + colour_vector(other_colour.0) * other_area)
+            / total_area;
+        colour.0 = Color::rgb_from_array(blended.to_array());
+        // Keep the alpha, which may be fading the ball out.
+        ball_assets.restyle(&mut materials, &mut material, |material| {
+            material.color = colour.0.with_a(material.color.a());
+        });
+
+        commands.entity(other_entity).despawn();
+        absorbed.insert(other_entity);
+        merged_events.send(BallsMergedEvent { merged: entity });
+    }
+}
+
+/// Breaks balls into [`Settings::split_count`] smaller ones when they hit something faster than
+/// [`Settings::split_speed`], conserving their total area and momentum.
+#[allow(clippy::type_complexity)]
+fn split_balls(
+    mut collision_events: EventReader<OtherCollisionEvent>,
+    ball_query: Query<(&Transform, &Velocity, &Radius, &BallColor, &InCage), With<Ball>>,
+    mut commands: Commands,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut ball_assets: ResMut<BallAssets>,
+    mut rng: ResMut<SimRng>,
+    settings: Res<Settings>,
+) {
+    if !settings.split_enabled || settings.split_count < 2 {
+        collision_events.clear();
+        return;
+    }
+
+    let mut split = HashSet::new();
+    for event in collision_events.read() {
+        if event.impact_speed < settings.split_speed || split.contains(&event.self_entity) {
+            continue;
+        }
+        let Ok((transform, velocity, radius, colour, in_cage)) = ball_query.get(event.self_entity)
+        else {
+            continue;
+        };
+        let piece_radius = radius.0 / (settings.split_count as f32).sqrt();
+        if piece_radius < SPLIT_MIN_RADIUS {
+            continue;
+        }
+
+        let center = transform.translation.truncate();
+        // Far enough apart that neighbouring pieces only just touch.
+        let spread = piece_radius / (PI / settings.split_count as f32).sin();
+        let first_angle = rng.gen::<f32>() * TAU;
+        for i in 0..settings.split_count {
+            // The pieces fly apart evenly in every direction, so the momentum adds up the same.
+            let direction =
+                Vec2::from_angle(first_angle + i as f32 * TAU / settings.split_count as f32);
+            let piece = spawn_sized_ball(
+                &mut commands,
+                &mut materials,
+                &mut ball_assets,
+                &mut rng,
+                in_cage.0,
+                center + direction * spread,
+                colour.0,
+                piece_radius,
+                settings.ball_speed,
+            );
+            commands
+                .entity(piece)
+                .insert(Velocity(velocity.0 + direction * SPLIT_SPREAD_SPEED));
+        }
+        commands.entity(event.self_entity).despawn();
+        split.insert(event.self_entity);
+    }
+}
+
+#[cfg_attr(feature = "rapier", allow(dead_code))]
+fn apply_spin(mut query: Query<(&mut Transform, &Spin), Without<Sleeping>>, time: Res<Time>) {
+    for (mut transform, spin) in &mut query {
+        transform.rotate_z(spin.0 * time.delta_seconds());
+    }
+}
+
+#[allow(clippy::type_complexity)]
+#[cfg_attr(feature = "rapier", allow(dead_code))]
+fn update_sleeping(
+    mut commands: Commands,
+    mut query: Query<
+        (Entity, &mut Velocity, &mut RestingSteps),
+        (Without<Sleeping>, Without<Grabbed>, Without<InCluster>),
+    >,
+) {
+    for (entity, mut velocity, mut resting_steps) in &mut query {
+        if velocity.length() < SLEEP_SPEED {
+            resting_steps.0 += 1;
+            if resting_steps.0 >= SLEEP_STEPS {
+                velocity.0 = Vec2::ZERO;
+                resting_steps.0 = 0;
+                commands.entity(entity).insert(Sleeping);
             }
+        } else {
+            resting_steps.0 = 0;
         }
     }
 }
 
+/// Sleeping balls would otherwise stay put when the gravity they settled under changes.
+fn wake_on_gravity_change(
+    mut commands: Commands,
+    gravity_field: Res<GravityField>,
+    query: Query<Entity, With<Sleeping>>,
+) {
+    if gravity_field.is_changed() {
+        for entity in &query {
+            commands.entity(entity).remove::<Sleeping>();
+        }
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn track_energy(
+    query: Query<(&Transform, &Velocity, &Gravity), With<Ball>>,
+    gravity_field: Res<GravityField>,
+    mut energy: ResMut<EnergyStats>,
+) {
+    let mut kinetic = 0.0;
+    let mut potential = 0.0;
+    for (transform, velocity, gravity) in &query {
+        kinetic += 0.5 * velocity.length_squared();
+        potential -= gravity_field.0.dot(transform.translation.truncate()) * gravity.0;
+    }
+    energy.kinetic = kinetic;
+    energy.potential = potential;
+}
+
+fn log_energy(energy: Res<EnergyStats>, mut timer: ResMut<EnergyLogTimer>, time: Res<Time>) {
+    if timer.0.tick(time.delta()).just_finished() {
+        info!(
+            "energy: kinetic {:.0}, potential {:.0}, total {:.0}",
+            energy.kinetic,
+            energy.potential,
+            energy.total()
+        );
+    }
+}
+
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
 fn maybe_spawn_ball(
     mut commands: Commands,
     mut collision_events: EventReader<CageCollisionEvent>,
+    ball_query: Query<(&Transform, &Radius, &InCage), With<Ball>>,
+    cage_query: Query<(&Cage, &Transform)>,
     mut materials: ResMut<Assets<ColorMaterial>>,
-    mut meshes: ResMut<Assets<Mesh>>,
+    mut ball_assets: ResMut<BallAssets>,
+    mut rng: ResMut<SimRng>,
+    palette: Res<BallPalette>,
+    settings: Res<Settings>,
 ) {
-    if !collision_events.is_empty() {
-        collision_events.clear();
-        if (rand::random::<f32>() * 100.0) < 10.0 {
-            spawn_ball(&mut commands, &mut materials, &mut meshes);
+    // The new ball goes into the same cage as the ball that hit the wall.
+    let Some(event) = collision_events.read().last() else {
+        return;
+    };
+    if rng.gen::<f32>() < settings.spawn_chance {
+        let Ok((_, _, in_cage)) = ball_query.get(event.entity) else {
+            return;
+        };
+        let Ok((cage, cage_transform)) = cage_query.get(in_cage.0) else {
+            return;
+        };
+        let others = ball_positions_in(&ball_query, in_cage.0);
+        let Some(position) = free_spawn_position(&mut rng, cage, cage_transform, &others) else {
+            return;
+        };
+        spawn_ball(
+            &mut commands,
+            &mut materials,
+            &mut ball_assets,
+            &mut rng,
+            &palette,
+            &settings,
+            in_cage.0,
+            position,
+        );
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn ball_positions_in(
+    ball_query: &Query<(&Transform, &Radius, &InCage), With<Ball>>,
+    cage: Entity,
+) -> Vec<(Vec2, f32)> {
+    ball_query
+        .iter()
+        .filter(|(_, _, in_cage)| in_cage.0 == cage)
+        .map(|(transform, radius, _)| (transform.translation.truncate(), radius.0))
+        .collect()
+}
+
+/// A random spot inside the cage where a new ball overlaps neither the wall nor any of the balls
+/// in `others`, or `None` if the cage looks full.
+fn free_spawn_position(
+    rng: &mut SimRng,
+    cage: &Cage,
+    cage_transform: &Transform,
+    others: &[(Vec2, f32)],
+) -> Option<Vec2> {
+    let center = cage_transform.translation.truncate();
+    (0..SPAWN_PLACEMENT_ATTEMPTS)
+        .map(|_| {
+            let offset = Vec2::new(rng.gen_range(-1.0..1.0), rng.gen_range(-1.0..1.0));
+            // Twice the radius to reach the ends of the wider shapes.
+            center + offset * cage.radius * 2.0
+        })
+        .find(|&position| {
+            cage.contains(cage_transform, position, BALL_RADIUS / 2.0)
+                && others
+                    .iter()
+                    .all(|(other, radius)| other.distance(position) >= radius + BALL_RADIUS / 2.0)
+        })
+}
+
+/// Turns Suika-style merging of same-sized balls on and off with U.
+fn toggle_merging(actions: Actions, mut settings: ResMut<Settings>) {
+    if actions.just_pressed(Action::ToggleMerging) {
+        settings.merge_enabled = !settings.merge_enabled;
+    }
+}
+
+/// Turns splitting balls on hard impacts on and off with Y.
+fn toggle_splitting(actions: Actions, mut settings: ResMut<Settings>) {
+    if actions.just_pressed(Action::ToggleSplitting) {
+        settings.split_enabled = !settings.split_enabled;
+    }
+}
+
+/// Turns ball trails on and off with T.
+fn toggle_trails(
+    actions: Actions,
+    mut trail_query: Query<&mut Trail>,
+    mut settings: ResMut<Settings>,
+) {
+    if actions.just_pressed(Action::ToggleTrails) {
+        settings.trails_enabled = !settings.trails_enabled;
+        // Don't draw a line back to wherever the ball was when trails were last on.
+        for mut trail in &mut trail_query {
+            trail.0.clear();
         }
     }
 }
 
-// fn remove_colliding_balls(
-//     mut commands: Commands,
-//     mut collision_events: EventReader<OtherCollisionEvent>,
-// ) {
-//     if !collision_events.is_empty() {
-//         collision_events.read().for_each(|event| {
-//             commands.entity(event.self_entity).despawn();
-//         });
-//     }
-// }
+fn record_trails(mut ball_query: Query<(&Transform, &mut Trail)>, settings: Res<Settings>) {
+    if !settings.trails_enabled {
+        return;
+    }
+    for (transform, mut trail) in &mut ball_query {
+        trail.0.push_back(transform.translation.truncate());
+        while trail.0.len() > settings.trail_length {
+            trail.0.pop_front();
+        }
+    }
+}
 
-fn play_sound(commands: &mut Commands, sound: &Res<CollisionSound>) {
-    commands.spawn(AudioBundle {
-        source: sound.0.clone(),
-        // auto-despawn the entity when playback finishes
-        settings: PlaybackSettings::DESPAWN,
-    });
+fn draw_trails(
+    mut gizmos: Gizmos,
+    ball_query: Query<(&Trail, &BallColor)>,
+    settings: Res<Settings>,
+) {
+    if !settings.trails_enabled {
+        return;
+    }
+    for (trail, colour) in &ball_query {
+        let length = trail.0.len() as f32;
+        gizmos.linestrip_gradient_2d(trail.0.iter().enumerate().map(|(i, &point)| {
+            // Fades out towards the oldest point.
+            (point, colour.0.with_a((i + 1) as f32 / length))
+        }));
+    }
 }
 
-fn play_collision_sound(
+/// Switches every ball, and balls spawned from then on, between flat and sprite drawing with V.
+fn toggle_sprite_balls(
+    actions: Actions,
+    mut appearance_query: Query<&mut Appearance>,
+    mut settings: ResMut<Settings>,
+) {
+    if !actions.just_pressed(Action::ToggleSpriteBalls) {
+        return;
+    }
+    settings.ball_appearance = match settings.ball_appearance {
+        BallAppearance::Flat => BallAppearance::Sprite,
+        BallAppearance::Sprite => BallAppearance::Flat,
+    };
+    for mut appearance in &mut appearance_query {
+        appearance.0 = settings.ball_appearance;
+    }
+}
+
+fn add_appearance(
+    ball_query: Query<Entity, Added<Ball>>,
     mut commands: Commands,
+    settings: Res<Settings>,
+) {
+    for entity in &ball_query {
+        commands
+            .entity(entity)
+            .insert(Appearance(settings.ball_appearance));
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn update_ball_textures(
+    mut ball_query: Query<(&Appearance, &mut Handle<ColorMaterial>), Changed<Appearance>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut ball_assets: ResMut<BallAssets>,
+    texture: Res<BallTexture>,
+) {
+    for (appearance, mut material) in &mut ball_query {
+        // The ball colour tints the texture, and the circle mesh already has matching UVs.
+        ball_assets.restyle(&mut materials, &mut material, |material| {
+            material.texture = match appearance.0 {
+                BallAppearance::Flat => None,
+                BallAppearance::Sprite => Some(texture.0.clone()),
+            };
+        });
+    }
+}
+
+/// Turns collisions blending ball colours on and off with J.
+fn toggle_colour_shift(actions: Actions, mut settings: ResMut<Settings>) {
+    if actions.just_pressed(Action::ToggleColourShift) {
+        settings.colour_shift_enabled = !settings.colour_shift_enabled;
+    }
+}
+
+/// Nudges the colour of every ball that hit something towards the colour of what it hit.
+#[allow(clippy::type_complexity)]
+fn shift_colours_on_collision(
     mut wall_collision_events: EventReader<CageCollisionEvent>,
     mut ball_collision_events: EventReader<OtherCollisionEvent>,
-    sound: Res<CollisionSound>,
+    mut ball_query: Query<(&mut BallColor, &mut Handle<ColorMaterial>), With<Ball>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut ball_assets: ResMut<BallAssets>,
+    settings: Res<Settings>,
 ) {
-    // Play a sound once per frame if a collision occurred.
-    if !wall_collision_events.is_empty() {
-        // This prevents events staying active on the next frame.
+    if !settings.colour_shift_enabled {
         wall_collision_events.clear();
-        play_sound(&mut commands, &sound);
+        ball_collision_events.clear();
+        return;
     }
 
-    if !ball_collision_events.is_empty() {
-        ball_collision_events.clear();
-        play_sound(&mut commands, &sound);
+    // Work out every target first, so a pair of balls blends towards each other's old colours.
+    let mut shifts: Vec<(Entity, Color)> = wall_collision_events
+        .read()
+        .map(|event| (event.entity, Color::WHITE))
+        .collect();
+    for event in ball_collision_events.read() {
+        let target = ball_query
+            .get(event.other_entity)
+            .map_or(Color::WHITE, |(colour, _)| colour.0);
+        shifts.push((event.self_entity, target));
+    }
+
+    for (entity, target) in shifts {
+        let Ok((mut colour, mut material)) = ball_query.get_mut(entity) else {
+            continue;
+        };
+        let blended =
+            colour_vector(colour.0).lerp(colour_vector(target), settings.colour_shift_rate);
+        colour.0 = Color::rgb_from_array(blended.to_array());
+        // Keep the alpha, which may be fading the ball out.
+        ball_assets.restyle(&mut materials, &mut material, |material| {
+            material.color = colour.0.with_a(material.color.a());
+        });
     }
 }
 
-fn spawn_ball_on_space(
+fn tilt_gravity(actions: Actions, mut gravity_field: ResMut<GravityField>, time: Res<Time>) {
+    // The stick tilts it as fast as the keys do when pushed all the way.
+    let mut direction = actions.stick_x();
+    if actions.pressed(Action::TiltGravityLeft) {
+        direction -= 1.0;
+    }
+    if actions.pressed(Action::TiltGravityRight) {
+        direction += 1.0;
+    }
+    let direction = direction.clamp(-1.0, 1.0);
+    if direction != 0.0 {
+        let angle = direction * GRAVITY_TILT_SPEED * time.delta_seconds();
+        gravity_field.0 = Vec2::from_angle(angle).rotate(gravity_field.0);
+    }
+
+    if actions.just_pressed(Action::ResetGravity) {
+        gravity_field.0 = GRAVITY;
+    }
+}
+
+fn draw_gravity_indicator(mut gizmos: Gizmos, gravity_field: Res<GravityField>) {
+    let direction = gravity_field.0.normalize_or_zero();
+    gizmos.arrow_2d(
+        GRAVITY_INDICATOR_POSITION,
+        GRAVITY_INDICATOR_POSITION + direction * GRAVITY_INDICATOR_LENGTH,
+        GRAVITY_INDICATOR_COLOR,
+    );
+}
+
+/// The value after `flag` on the command line, like the `42` in `--seed 42`.
+fn command_line_value(flag: &str) -> Option<String> {
+    std::env::args().skip_while(|arg| arg != flag).nth(1)
+}
+
+fn cursor_world_position(
+    window: &Window,
+    camera: &Camera,
+    camera_transform: &GlobalTransform,
+) -> Option<Vec2> {
+    let cursor_position = window.cursor_position()?;
+    camera.viewport_to_world_2d(camera_transform, cursor_position)
+}
+
+/// Places an attractor at the cursor with G, or a repulsor with Shift+G.
+fn place_gravity_well(
     keyboard_input: Res<ButtonInput<KeyCode>>,
-    query: Query<Entity, With<Ball>>,
+    actions: Actions,
+    window_query: Query<&Window, With<PrimaryWindow>>,
+    camera_query: Query<(&Camera, &GlobalTransform)>,
+    mut commands: Commands,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+) {
+    if !actions.just_pressed(Action::PlaceGravityWell) {
+        return;
+    }
+    let (Ok(window), Ok((camera, camera_transform))) =
+        (window_query.get_single(), camera_query.get_single())
+    else {
+        return;
+    };
+    let Some(position) = cursor_world_position(window, camera, camera_transform) else {
+        return;
+    };
+
+    let strength = if keyboard_input.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]) {
+        -GRAVITY_WELL_STRENGTH
+    } else {
+        GRAVITY_WELL_STRENGTH
+    };
+    spawn_gravity_well(
+        &mut commands,
+        &mut materials,
+        &mut meshes,
+        position,
+        strength,
+    );
+}
+
+fn toggle_colour_charge(actions: Actions, mut settings: ResMut<Settings>) {
+    if actions.just_pressed(Action::ToggleColourCharge) {
+        settings.charge_enabled = !settings.charge_enabled;
+    }
+}
+
+/// Spawns a ball where the left mouse button went down inside a cage once it's released, launched
+/// along the drag. A plain click spawns it with a random direction instead.
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+fn launch_ball_on_drag(
+    mouse_input: Res<ButtonInput<MouseButton>>,
+    window_query: Query<&Window, With<PrimaryWindow>>,
+    camera_query: Query<(&Camera, &GlobalTransform)>,
+    cage_query: Query<(Entity, &Cage, &Transform, Has<NestedIn>)>,
+    mut launch_drag: ResMut<LaunchDrag>,
+    mut commands: Commands,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut ball_assets: ResMut<BallAssets>,
+    mut rng: ResMut<SimRng>,
+    palette: Res<BallPalette>,
+    settings: Res<Settings>,
+) {
+    let (Ok(window), Ok((camera, camera_transform))) =
+        (window_query.get_single(), camera_query.get_single())
+    else {
+        return;
+    };
+    let cursor = cursor_world_position(window, camera, camera_transform);
+
+    if mouse_input.just_pressed(MouseButton::Left) {
+        launch_drag.start =
+            cursor.and_then(|position| Some((position, cage_at(&cage_query, position)?)));
+    }
+    if !mouse_input.just_released(MouseButton::Left) {
+        return;
+    }
+    // Letting go outside the window cancels the launch.
+    let (Some((start, cage)), Some(end)) = (launch_drag.start.take(), cursor) else {
+        return;
+    };
+
+    let ball = spawn_ball(
+        &mut commands,
+        &mut materials,
+        &mut ball_assets,
+        &mut rng,
+        &palette,
+        &settings,
+        cage,
+        start,
+    );
+    let drag = end - start;
+    if drag.length() >= LAUNCH_MIN_DRAG {
+        commands
+            .entity(ball)
+            .insert(Velocity(drag * LAUNCH_SPEED_PER_PIXEL));
+    }
+}
+
+/// Pops the ball under the cursor on left click, instead of starting a launch.
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+fn pop_ball_on_click(
+    mouse_input: Res<ButtonInput<MouseButton>>,
+    window_query: Query<&Window, With<PrimaryWindow>>,
+    camera_query: Query<(&Camera, &GlobalTransform)>,
+    ball_query: Query<(Entity, &Transform, &Radius, &BallColor), With<Ball>>,
+    mut launch_drag: ResMut<LaunchDrag>,
     mut commands: Commands,
     mut materials: ResMut<Assets<ColorMaterial>>,
     mut meshes: ResMut<Assets<Mesh>>,
+    sound: Res<CollisionSound>,
+    audio_settings: Res<AudioSettings>,
 ) {
-    if keyboard_input.just_pressed(KeyCode::Space) {
+    if !mouse_input.just_pressed(MouseButton::Left) {
+        return;
+    }
+    let (Ok(window), Ok((camera, camera_transform))) =
+        (window_query.get_single(), camera_query.get_single())
+    else {
+        return;
+    };
+    let Some(cursor) = cursor_world_position(window, camera, camera_transform) else {
+        return;
+    };
+    let balls = ball_query
+        .iter()
+        .map(|(entity, transform, radius, _)| (entity, transform, radius));
+    let Some(entity) = ball_at(balls, cursor) else {
+        return;
+    };
+    let Ok((_, transform, _, colour)) = ball_query.get(entity) else {
+        return;
+    };
+
+    launch_drag.start = None;
+    pop_ball(
+        &mut commands,
+        &mut materials,
+        &mut meshes,
+        &sound,
+        &audio_settings,
+        entity,
+        transform.translation.truncate(),
+        colour.0,
+    );
+}
+
+/// Despawns the ball in a burst of particles its colour, with a pop.
+#[allow(clippy::too_many_arguments)]
+fn pop_ball(
+    commands: &mut Commands,
+    materials: &mut ResMut<Assets<ColorMaterial>>,
+    meshes: &mut ResMut<Assets<Mesh>>,
+    sound: &CollisionSound,
+    audio_settings: &AudioSettings,
+    entity: Entity,
+    position: Vec2,
+    colour: Color,
+) {
+    commands.entity(entity).despawn();
+    particle::spawn_particle_burst(commands, materials, meshes, position, colour);
+    audio::play_sound(
+        commands,
+        &sound.with_speed(POP_SOUND_SPEED),
+        1.0,
+        audio_settings,
+    );
+}
+
+/// The ball covering `point`, picking the one whose center is closest if several overlap.
+fn ball_at<'a>(
+    balls: impl Iterator<Item = (Entity, &'a Transform, &'a Radius)>,
+    point: Vec2,
+) -> Option<Entity> {
+    balls
+        .map(|(entity, transform, radius)| {
+            (
+                entity,
+                transform.translation.truncate().distance(point),
+                radius.0,
+            )
+        })
+        .filter(|(_, distance, radius)| distance < radius)
+        .min_by(|(_, a, _), (_, b, _)| a.total_cmp(b))
+        .map(|(entity, _, _)| entity)
+}
+
+/// Picks up the ball under the cursor while the right mouse button is held.
+fn grab_ball(
+    mouse_input: Res<ButtonInput<MouseButton>>,
+    window_query: Query<&Window, With<PrimaryWindow>>,
+    camera_query: Query<(&Camera, &GlobalTransform)>,
+    ball_query: Query<(Entity, &Transform, &Radius), With<Ball>>,
+    grabbed_query: Query<Entity, With<Grabbed>>,
+    mut commands: Commands,
+) {
+    if mouse_input.just_released(MouseButton::Right) {
+        // Letting go keeps whatever velocity the ball had while being dragged, flinging it.
+        for entity in &grabbed_query {
+            commands.entity(entity).remove::<Grabbed>();
+        }
+    }
+    if !mouse_input.just_pressed(MouseButton::Right) {
+        return;
+    }
+    let (Ok(window), Ok((camera, camera_transform))) =
+        (window_query.get_single(), camera_query.get_single())
+    else {
+        return;
+    };
+    let Some(cursor) = cursor_world_position(window, camera, camera_transform) else {
+        return;
+    };
+    if let Some(entity) = ball_at(ball_query.iter(), cursor) {
+        commands.entity(entity).insert(Grabbed).remove::<Sleeping>();
+    }
+}
+
+/// Steers grabbed balls towards the cursor through their velocity, so they still collide on the
+/// way and fly off at the speed they were dragged at when let go.
+#[allow(clippy::type_complexity)]
+fn pull_grabbed_balls(
+    window_query: Query<&Window, With<PrimaryWindow>>,
+    camera_query: Query<(&Camera, &GlobalTransform)>,
+    mut ball_query: Query<(&Transform, &mut Velocity, &mut Acceleration), With<Grabbed>>,
+) {
+    let (Ok(window), Ok((camera, camera_transform))) =
+        (window_query.get_single(), camera_query.get_single())
+    else {
+        return;
+    };
+    let Some(cursor) = cursor_world_position(window, camera, camera_transform) else {
+        return;
+    };
+    for (transform, mut velocity, mut acceleration) in &mut ball_query {
+        let offset = cursor - transform.translation.truncate();
+        velocity.0 = (offset * GRAB_STIFFNESS).clamp_length_max(GRAB_MAX_SPEED);
+        // Held balls ignore every other force.
+        acceleration.0 = Vec2::ZERO;
+    }
+}
+
+fn draw_launch_preview(
+    mut gizmos: Gizmos,
+    launch_drag: Res<LaunchDrag>,
+    window_query: Query<&Window, With<PrimaryWindow>>,
+    camera_query: Query<(&Camera, &GlobalTransform)>,
+) {
+    let Some((start, _)) = launch_drag.start else {
+        return;
+    };
+    let (Ok(window), Ok((camera, camera_transform))) =
+        (window_query.get_single(), camera_query.get_single())
+    else {
+        return;
+    };
+    let Some(end) = cursor_world_position(window, camera, camera_transform) else {
+        return;
+    };
+    if start.distance(end) >= LAUNCH_MIN_DRAG {
+        gizmos.arrow_2d(start, end, LAUNCH_PREVIEW_COLOR);
+    }
+}
+
+/// The cage a ball at `position` would be in, preferring nested cages over the ones around them.
+#[allow(clippy::type_complexity)]
+fn cage_at(
+    cage_query: &Query<(Entity, &Cage, &Transform, Has<NestedIn>)>,
+    position: Vec2,
+) -> Option<Entity> {
+    cage_query
+        .iter()
+        .filter(|(_, cage, transform, _)| cage.contains(transform, position, BALL_RADIUS / 2.0))
+        .max_by_key(|(_, _, _, nested)| *nested)
+        .map(|(entity, _, _, _)| entity)
+}
+
+/// Spawns [`Settings::burst_count`] balls in every cage at once with B.
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+fn spawn_burst(
+    actions: Actions,
+    cage_query: Query<(Entity, &Cage, &Transform)>,
+    ball_query: Query<(&Transform, &Radius, &InCage), With<Ball>>,
+    mut commands: Commands,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut ball_assets: ResMut<BallAssets>,
+    mut rng: ResMut<SimRng>,
+    palette: Res<BallPalette>,
+    settings: Res<Settings>,
+) {
+    if !actions.just_pressed(Action::SpawnBurst) {
+        return;
+    }
+
+    for (entity, cage, cage_transform) in &cage_query {
+        let center = cage_transform.translation.truncate();
+        match settings.burst_pattern {
+            BurstPattern::Ring => {
+                // Just wide enough for the balls to sit side by side.
+                let ring_radius = settings.burst_count as f32 * BALL_RADIUS / TAU;
+                for i in 0..settings.burst_count {
+                    let direction = Vec2::from_angle(i as f32 * TAU / settings.burst_count as f32);
+                    let ball = spawn_ball(
+                        &mut commands,
+                        &mut materials,
+                        &mut ball_assets,
+                        &mut rng,
+                        &palette,
+                        &settings,
+                        entity,
+                        center + direction * ring_radius,
+                    );
+                    commands
+                        .entity(ball)
+                        .insert(Velocity(direction * settings.ball_speed));
+                }
+            }
+            BurstPattern::Random => {
+                let mut others = ball_positions_in(&ball_query, entity);
+                for _ in 0..settings.burst_count {
+                    let Some(position) =
+                        free_spawn_position(&mut rng, cage, cage_transform, &others)
+                    else {
+                        break;
+                    };
+                    spawn_ball(
+                        &mut commands,
+                        &mut materials,
+                        &mut ball_assets,
+                        &mut rng,
+                        &palette,
+                        &settings,
+                        entity,
+                        position,
+                    );
+                    others.push((position, BALL_RADIUS / 2.0));
+                }
+            }
+        }
+    }
+}
+
+/// Asks for a reset with R, through a [`ResetEvent`] so everything that forgets the old run on
+/// reset hears about it.
+fn request_reset(actions: Actions, mut reset_events: EventWriter<ResetEvent>) {
+    if actions.just_pressed(Action::Reset) {
+        reset_events.send(ResetEvent);
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn reset_balls(
+    query: Query<Entity, With<Ball>>,
+    cage_query: Query<(Entity, &Cage, &Transform)>,
+    mut commands: Commands,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut ball_assets: ResMut<BallAssets>,
+    mut rng: ResMut<SimRng>,
+    palette: Res<BallPalette>,
+    settings: Res<Settings>,
+    mut score: ResMut<Score>,
+    mut endless_run: ResMut<EndlessRun>,
+    mut player_scores: ResMut<PlayerScores>,
+    mut reset_events: EventReader<ResetEvent>,
+) {
+    let requested = !reset_events.is_empty();
+    reset_events.clear();
+    if requested {
         for entity in query.iter() {
             // Despawn all balls
             commands.entity(entity).despawn();
         }
-        spawn_ball(&mut commands, &mut materials, &mut meshes);
+        score.0 = 0;
+        endless_run.elapsed = 0.0;
+        player_scores.0 = [0; 2];
+        // Start every cage off with a single ball
+        for (entity, cage, cage_transform) in &cage_query {
+            if let Some(position) = free_spawn_position(&mut rng, cage, cage_transform, &[]) {
+                spawn_ball(
+                    &mut commands,
+                    &mut materials,
+                    &mut ball_assets,
+                    &mut rng,
+                    &palette,
+                    &settings,
+                    entity,
+                    position,
+                );
+            }
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+fn add_ball(
+    actions: Actions,
+    cage_query: Query<(Entity, &Cage, &Transform)>,
+    ball_query: Query<(&Transform, &Radius, &InCage), With<Ball>>,
+    mut commands: Commands,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut ball_assets: ResMut<BallAssets>,
+    mut rng: ResMut<SimRng>,
+    palette: Res<BallPalette>,
+    settings: Res<Settings>,
+) {
+    if !actions.just_pressed(Action::AddBall) {
+        return;
+    }
+    for (entity, cage, cage_transform) in &cage_query {
+        let others = ball_positions_in(&ball_query, entity);
+        if let Some(position) = free_spawn_position(&mut rng, cage, cage_transform, &others) {
+            spawn_ball(
+                &mut commands,
+                &mut materials,
+                &mut ball_assets,
+                &mut rng,
+                &palette,
+                &settings,
+                entity,
+                position,
+            );
+        }
     }
 }
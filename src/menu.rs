@@ -0,0 +1,249 @@
+use std::cmp::Ordering;
+
+use bevy::{app::AppExit, prelude::*};
+
+use crate::{
+    keybindings::{Action, Actions},
+    players::PlayerScores,
+    score::Score,
+    ResetEvent,
+};
+
+const MENU_BACKGROUND_COLOR: Color = Color::rgba(0.0, 0.0, 0.0, 0.6);
+const MENU_TEXT_COLOR: Color = Color::rgb(0.9, 0.9, 0.9);
+const BUTTON_COLOR: Color = Color::rgb(0.25, 0.25, 0.25);
+const BUTTON_HOVERED_COLOR: Color = Color::rgb(0.4, 0.4, 0.4);
+const BUTTON_WIDTH: Val = Val::Px(160.0);
+
+/// Which screen the app is on, and whether the simulation is running.
+#[derive(States, Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum AppState {
+    /// The title screen the app starts at.
+    #[default]
+    Menu,
+    Running,
+    /// The physics is stopped, and the pause menu is open.
+    Paused,
+    /// The run is over. The physics is stopped until a new one is started.
+    GameOver,
+    /// A recorded run is being played back. The physics is stopped.
+    Replay,
+    /// Showing the cage of the server that was joined with `--join`. The local physics is
+    /// stopped.
+    Online,
+}
+
+/// The rules a run is played by. Either way, it ends once a cage fills up.
+#[derive(Resource, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum GameMode {
+    /// Scores collisions.
+    #[default]
+    Classic,
+    /// Gets harder over time, following the [`DifficultyCurve`](crate::endless::DifficultyCurve),
+    /// and scores the seconds survived.
+    Endless,
+    /// Each player has their own cage, cannon and score.
+    TwoPlayer,
+}
+
+/// The root of whichever menu screen is open.
+#[derive(Component)]
+pub struct MenuScreen;
+
+#[derive(Component, Clone, Copy)]
+pub enum MenuButton {
+    /// Starts a new classic run with fresh balls.
+    Start,
+    /// Starts a new endless run with fresh balls.
+    StartEndless,
+    /// Starts a new two-player run with fresh balls.
+    StartTwoPlayer,
+    /// Starts a new run in the same mode as the last one.
+    Restart,
+    Resume,
+    Reset,
+    /// Plays back the last run that ended.
+    WatchReplay,
+    /// Goes back to the title screen.
+    MainMenu,
+    Quit,
+}
+
+/// Pauses with Esc, or resumes if already paused. Stops watching a replay, or leaves the server.
+pub fn toggle_pause(
+    actions: Actions,
+    state: Res<State<AppState>>,
+    mut next_state: ResMut<NextState<AppState>>,
+) {
+    if !actions.just_pressed(Action::Pause) {
+        return;
+    }
+    next_state.set(match state.get() {
+        AppState::Running => AppState::Paused,
+        AppState::Paused => AppState::Running,
+        AppState::Replay | AppState::Online => AppState::Menu,
+        AppState::Menu | AppState::GameOver => return,
+    });
+}
+
+pub fn spawn_title_screen(mut commands: Commands) {
+    spawn_menu_screen(
+        &mut commands,
+        "Bevy Balls",
+        None,
+        &[
+            (MenuButton::Start, "Start"),
+            (MenuButton::StartEndless, "Endless"),
+            (MenuButton::StartTwoPlayer, "Two players"),
+            (MenuButton::WatchReplay, "Watch replay"),
+            (MenuButton::Quit, "Quit"),
+        ],
+    );
+}
+
+pub fn spawn_pause_menu(mut commands: Commands) {
+    spawn_menu_screen(
+        &mut commands,
+        "Paused",
+        None,
+        &[
+            (MenuButton::Resume, "Resume"),
+            (MenuButton::Reset, "Reset"),
+            (MenuButton::MainMenu, "Main menu"),
+            (MenuButton::Quit, "Quit"),
+        ],
+    );
+}
+
+pub fn spawn_game_over_screen(
+    mut commands: Commands,
+    score: Res<Score>,
+    player_scores: Res<PlayerScores>,
+    mode: Res<GameMode>,
+) {
+    let result = match *mode {
+        GameMode::Classic => format!("Final score: {}", score.0),
+        GameMode::Endless => format!("Survived {} s", score.0),
+        GameMode::TwoPlayer => {
+            let [one, two] = player_scores.0;
+            match one.cmp(&two) {
+                Ordering::Greater => format!("Player 1 wins, {one} to {two}"),
+                Ordering::Less => format!("Player 2 wins, {two} to {one}"),
+                Ordering::Equal => format!("A draw at {one}"),
+            }
+        }
+    };
+    spawn_menu_screen(
+        &mut commands,
+        "Game over",
+        Some(&result),
+        &[
+            (MenuButton::Restart, "Play again"),
+            (MenuButton::MainMenu, "Main menu"),
+            (MenuButton::Quit, "Quit"),
+        ],
+    );
+}
+
+/// A full-screen overlay with a title, an optional line of text under it, and a column of buttons.
+fn spawn_menu_screen(
+    commands: &mut Commands,
+    title: &str,
+    subtitle: Option<&str>,
+    buttons: &[(MenuButton, &str)],
+) {
+    let text_style = |font_size| TextStyle {
+        font_size,
+        color: MENU_TEXT_COLOR,
+        ..Default::default()
+    };
+
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    width: Val::Percent(100.0),
+                    height: Val::Percent(100.0),
+                    flex_direction: FlexDirection::Column,
+                    align_items: AlignItems::Center,
+                    justify_content: JustifyContent::Center,
+                    row_gap: Val::Px(10.0),
+                    ..Default::default()
+                },
+                background_color: MENU_BACKGROUND_COLOR.into(),
+                ..Default::default()
+            },
+            MenuScreen,
+        ))
+        .with_children(|parent| {
+            parent.spawn(TextBundle::from_section(title, text_style(40.0)));
+            if let Some(subtitle) = subtitle {
+                parent.spawn(TextBundle::from_section(subtitle, text_style(24.0)));
+            }
+            for &(button, label) in buttons {
+                parent
+                    .spawn((
+                        ButtonBundle {
+                            style: Style {
+                                width: BUTTON_WIDTH,
+                                padding: UiRect::all(Val::Px(8.0)),
+                                justify_content: JustifyContent::Center,
+                                ..Default::default()
+                            },
+                            background_color: BUTTON_COLOR.into(),
+                            ..Default::default()
+                        },
+                        button,
+                    ))
+                    .with_children(|parent| {
+                        parent.spawn(TextBundle::from_section(label, text_style(24.0)));
+                    });
+            }
+        });
+}
+
+pub fn despawn_menu_screen(menu_query: Query<Entity, With<MenuScreen>>, mut commands: Commands) {
+    for entity in &menu_query {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+#[allow(clippy::type_complexity)]
+pub fn handle_menu_buttons(
+    mut button_query: Query<
+        (&Interaction, &MenuButton, &mut BackgroundColor),
+        Changed<Interaction>,
+    >,
+    mut next_state: ResMut<NextState<AppState>>,
+    mut mode: ResMut<GameMode>,
+    mut reset_events: EventWriter<ResetEvent>,
+    mut exit_events: EventWriter<AppExit>,
+) {
+    for (interaction, button, mut background) in &mut button_query {
+        match interaction {
+            Interaction::Pressed => match button {
+                MenuButton::Resume => next_state.set(AppState::Running),
+                MenuButton::Start | MenuButton::StartEndless | MenuButton::StartTwoPlayer => {
+                    *mode = match button {
+                        MenuButton::StartEndless => GameMode::Endless,
+                        MenuButton::StartTwoPlayer => GameMode::TwoPlayer,
+                        _ => GameMode::Classic,
+                    };
+                    reset_events.send(ResetEvent);
+                    next_state.set(AppState::Running);
+                }
+                MenuButton::Restart | MenuButton::Reset => {
+                    reset_events.send(ResetEvent);
+                    next_state.set(AppState::Running);
+                }
+                MenuButton::WatchReplay => next_state.set(AppState::Replay),
+                MenuButton::MainMenu => next_state.set(AppState::Menu),
+                MenuButton::Quit => {
+                    exit_events.send(AppExit);
+                }
+            },
+            Interaction::Hovered => *background = BUTTON_HOVERED_COLOR.into(),
+            Interaction::None => *background = BUTTON_COLOR.into(),
+        }
+    }
+}
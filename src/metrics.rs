@@ -0,0 +1,121 @@
+use std::{
+    io::{Read, Write},
+    net::TcpListener,
+    sync::{Arc, Mutex},
+    thread,
+};
+
+use bevy::{
+    diagnostic::{Diagnostic, DiagnosticId, Diagnostics, DiagnosticsStore, RegisterDiagnostic},
+    prelude::*,
+};
+
+use crate::{audio::Voice, command_line_value, Ball};
+
+/// Balls currently in the simulation.
+pub const BALLS_ALIVE: DiagnosticId =
+    DiagnosticId::from_u128(0x3b7e_91c4_0f2a_4d86_b5e1_6a0c_d8f3_2794);
+/// Pairs of balls checked for contact each fixed step: every awake ball against every other ball
+/// it could collide with in the same cage.
+pub const BROAD_PHASE_PAIRS: DiagnosticId =
+    DiagnosticId::from_u128(0x8c14_5f0b_e37d_4a92_9d06_2bf8_41a5_c6e3);
+/// Pairs of balls found to be touching each fixed step.
+pub const NARROW_PHASE_HITS: DiagnosticId =
+    DiagnosticId::from_u128(0xe6a2_0d97_4b18_4c5f_a3d4_7f91_05bc_28e6);
+/// Sound effects currently playing, out of the voices available.
+pub const AUDIO_VOICES: DiagnosticId =
+    DiagnosticId::from_u128(0x51d9_c3e7_86b0_4f2d_8e4a_b20f_9c61_7d35);
+
+const METRICS_PORT_ARG: &str = "--metrics-port";
+// Every metric name starts with this, so they're easy to tell apart on a shared dashboard.
+const METRICS_PREFIX: &str = "bevy_balls_";
+
+/// Measures the simulation as Bevy diagnostics, and serves every diagnostic over HTTP when
+/// started with `--metrics-port <port>`, for dashboards to scrape.
+pub struct MetricsPlugin;
+
+impl Plugin for MetricsPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_diagnostic(Diagnostic::new(BALLS_ALIVE, "balls_alive", 20))
+            .register_diagnostic(Diagnostic::new(BROAD_PHASE_PAIRS, "broad_phase_pairs", 20))
+            .register_diagnostic(Diagnostic::new(NARROW_PHASE_HITS, "narrow_phase_hits", 20))
+            .register_diagnostic(Diagnostic::new(AUDIO_VOICES, "audio_voices", 20))
+            .add_systems(Startup, start_metrics_endpoint)
+            .add_systems(Update, measure_simulation)
+            .add_systems(
+                Last,
+                publish_metrics.run_if(resource_exists::<MetricsEndpoint>),
+            );
+    }
+}
+
+/// The latest diagnostics in the Prometheus text format, served by a background thread.
+#[derive(Resource)]
+struct MetricsEndpoint(Arc<Mutex<String>>);
+
+fn measure_simulation(
+    mut diagnostics: Diagnostics,
+    ball_query: Query<(), With<Ball>>,
+    voice_query: Query<(), With<Voice>>,
+) {
+    diagnostics.add_measurement(BALLS_ALIVE, || ball_query.iter().count() as f64);
+    diagnostics.add_measurement(AUDIO_VOICES, || voice_query.iter().count() as f64);
+}
+
+/// Starts serving the metrics on localhost, if a port was given on the command line.
+fn start_metrics_endpoint(mut commands: Commands) {
+    let Some(port) = command_line_value(METRICS_PORT_ARG) else {
+        return;
+    };
+    let port: u16 = match port.parse() {
+        Ok(port) => port,
+        Err(error) => {
+            warn!("Ignoring metrics port {port:?}: {error}");
+            return;
+        }
+    };
+    let listener = match TcpListener::bind(("127.0.0.1", port)) {
+        Ok(listener) => listener,
+        Err(error) => {
+            warn!("Couldn't serve metrics on port {port}: {error}");
+            return;
+        }
+    };
+    info!("Serving metrics at http://127.0.0.1:{port}/metrics");
+
+    let metrics = Arc::new(Mutex::new(String::new()));
+    let served = metrics.clone();
+    thread::spawn(move || {
+        for mut stream in listener.incoming().flatten() {
+            // Every path gets the metrics, so there's no need to look at what was asked for
+            // beyond reading it off the socket.
+            let mut request = [0; 1024];
+            let _ = stream.read(&mut request);
+            let body = served.lock().map(|body| body.clone()).unwrap_or_default();
+            let _ = write!(
+                stream,
+                "HTTP/1.1 200 OK\r\n\
+                 Content-Type: text/plain; version=0.0.4\r\n\
+                 Content-Length: {}\r\n\
+                 Connection: close\r\n\r\n{body}",
+                body.len()
+            );
+        }
+    });
+    commands.insert_resource(MetricsEndpoint(metrics));
+}
+
+/// Hands every diagnostic, including Bevy's own, to the endpoint.
+fn publish_metrics(endpoint: Res<MetricsEndpoint>, diagnostics: Res<DiagnosticsStore>) {
+    let mut body = String::new();
+    for diagnostic in diagnostics.iter() {
+        let Some(value) = diagnostic.smoothed() else {
+            continue;
+        };
+        let name = format!("{METRICS_PREFIX}{}", diagnostic.name).replace(['/', ' ', '-'], "_");
+        body.push_str(&format!("{name} {value}\n"));
+    }
+    if let Ok(mut metrics) = endpoint.0.lock() {
+        *metrics = body;
+    }
+}
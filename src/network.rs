@@ -0,0 +1,327 @@
+use std::{
+    io::{self, ErrorKind, Read, Write},
+    net::{TcpListener, TcpStream},
+};
+
+use bevy::{prelude::*, window::PrimaryWindow};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::{
+    ball_assets::BallAssets,
+    cage::{Cage, NestedIn},
+    cage_at, command_line_value, cursor_world_position,
+    menu::AppState,
+    palette::BallPalette,
+    replay::{self, BallKeyframe, ReplayBall},
+    rng::SimRng,
+    settings::Settings,
+    spawn_ball, Ball, BallColor, Radius,
+};
+
+const HOST_ARG: &str = "--host";
+const JOIN_ARG: &str = "--join";
+// Snapshots per second sent to each client.
+const SNAPSHOT_RATE: f32 = 20.0;
+// Once a client has fallen this far behind, it's dropped rather than buffered for any longer.
+const MAX_OUTGOING_BYTES: usize = 1 << 20;
+// A message that's grown this long without ending is dropped along with whoever sent it.
+const MAX_INCOMING_BYTES: usize = 1 << 20;
+// How many balls each client can ask for per second, and in one go.
+const CLIENT_SPAWN_RATE: f32 = 10.0;
+
+/// What a client asks of the server.
+#[derive(Serialize, Deserialize)]
+enum ClientMessage {
+    SpawnBall { position: [f32; 2] },
+}
+
+/// What the server tells its clients.
+#[derive(Serialize, Deserialize)]
+enum ServerMessage {
+    /// Every ball in the shared cage, as of the server's last frame.
+    Snapshot { balls: Vec<BallKeyframe> },
+}
+
+/// One end of a connection, sending and receiving messages as lines of RON without blocking.
+struct Connection {
+    stream: TcpStream,
+    incoming: Vec<u8>,
+    outgoing: Vec<u8>,
+}
+
+impl Connection {
+    fn new(stream: TcpStream) -> io::Result<Self> {
+        stream.set_nonblocking(true)?;
+        // Snapshots are small and frequent, so they shouldn't wait around to be batched.
+        stream.set_nodelay(true)?;
+        Ok(Self {
+            stream,
+            incoming: Vec::new(),
+            outgoing: Vec::new(),
+        })
+    }
+
+    fn send(&mut self, message: &impl Serialize) -> io::Result<()> {
+        let line = ron::to_string(message).map_err(io::Error::other)?;
+        self.outgoing.extend_from_slice(line.as_bytes());
+        self.outgoing.push(b'\n');
+        if self.outgoing.len() > MAX_OUTGOING_BYTES {
+            return Err(io::Error::other("fell too far behind"));
+        }
+        self.flush()
+    }
+
+    /// Writes as much of what's queued up as the socket takes right now.
+    fn flush(&mut self) -> io::Result<()> {
+        while !self.outgoing.is_empty() {
+            match self.stream.write(&self.outgoing) {
+                Ok(0) => return Err(ErrorKind::WriteZero.into()),
+                Ok(written) => {
+                    self.outgoing.drain(..written);
+                }
+                Err(error) if error.kind() == ErrorKind::WouldBlock => break,
+                Err(error) => return Err(error),
+            }
+        }
+        Ok(())
+    }
+
+    /// Every whole message that has arrived since the last call. Ones that can't be read are
+    /// skipped.
+    fn receive<T: DeserializeOwned>(&mut self) -> io::Result<Vec<T>> {
+        let mut buffer = [0; 4096];
+        let mut messages = Vec::new();
+        // The rest waits for the next call, so a peer that never stops sending can't hold up
+        // the frame.
+        let mut received = 0;
+        while received < MAX_INCOMING_BYTES {
+            match self.stream.read(&mut buffer) {
+                Ok(0) => return Err(ErrorKind::UnexpectedEof.into()),
+                Ok(read) => {
+                    self.incoming.extend_from_slice(&buffer[..read]);
+                    received += read;
+                }
+                Err(error) if error.kind() == ErrorKind::WouldBlock => break,
+                Err(error) => return Err(error),
+            }
+            while let Some(end) = self.incoming.iter().position(|&byte| byte == b'\n') {
+                let line: Vec<u8> = self.incoming.drain(..=end).collect();
+                match ron::de::from_bytes(&line[..end]) {
+                    Ok(message) => messages.push(message),
+                    Err(error) => warn!("Skipping a message that couldn't be read: {error}"),
+                }
+            }
+            if self.incoming.len() > MAX_INCOMING_BYTES {
+                return Err(io::Error::other("sent a message that was too long"));
+            }
+        }
+        Ok(messages)
+    }
+}
+
+/// Runs the simulation for everyone connected, taking their spawns and sending them snapshots.
+/// Only there when started with `--host <port>`.
+#[derive(Resource)]
+pub struct NetworkServer {
+    listener: TcpListener,
+    clients: Vec<Client>,
+    snapshot_timer: Timer,
+}
+
+struct Client {
+    connection: Connection,
+    /// How many more balls the client can spawn right now. Refills at [`CLIENT_SPAWN_RATE`].
+    spawn_allowance: f32,
+}
+
+/// The connection to the server whose cage is being shown. Only there when started with
+/// `--join <address>`.
+#[derive(Resource)]
+pub struct NetworkClient {
+    server: Connection,
+}
+
+/// Starts hosting or joins a server, as asked on the command line.
+pub fn start_network_from_args(
+    mut commands: Commands,
+    mut next_state: ResMut<NextState<AppState>>,
+) {
+    if let Some(port) = command_line_value(HOST_ARG) {
+        let listener = TcpListener::bind(format!("0.0.0.0:{port}"))
+            .and_then(|listener| listener.set_nonblocking(true).map(|()| listener));
+        match listener {
+            Ok(listener) => {
+                info!("Hosting a shared cage on port {port}");
+                commands.insert_resource(NetworkServer {
+                    listener,
+                    clients: Vec::new(),
+                    snapshot_timer: Timer::from_seconds(1.0 / SNAPSHOT_RATE, TimerMode::Repeating),
+                });
+            }
+            Err(error) => warn!("Couldn't host on port {port}: {error}"),
+        }
+    } else if let Some(address) = command_line_value(JOIN_ARG) {
+        match TcpStream::connect(&address).and_then(Connection::new) {
+            Ok(server) => {
+                info!("Joined the shared cage at {address}");
+                commands.insert_resource(NetworkClient { server });
+                next_state.set(AppState::Online);
+            }
+            Err(error) => warn!("Couldn't join {address}: {error}"),
+        }
+    }
+}
+
+/// Lets new clients in, spawns the balls they ask for, and keeps them all up to date. Clients
+/// that disconnect or fall too far behind are dropped.
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+pub fn run_server(
+    mut server: ResMut<NetworkServer>,
+    ball_query: Query<(&Transform, &Radius, &BallColor), With<Ball>>,
+    cage_query: Query<(Entity, &Cage, &Transform, Has<NestedIn>)>,
+    mut commands: Commands,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut ball_assets: ResMut<BallAssets>,
+    mut rng: ResMut<SimRng>,
+    palette: Res<BallPalette>,
+    settings: Res<Settings>,
+    time: Res<Time<Real>>,
+) {
+    let server = &mut *server;
+    loop {
+        match server.listener.accept() {
+            Ok((stream, address)) => match Connection::new(stream) {
+                Ok(connection) => {
+                    info!("{address} joined");
+                    server.clients.push(Client {
+                        connection,
+                        spawn_allowance: CLIENT_SPAWN_RATE,
+                    });
+                }
+                Err(error) => warn!("Couldn't set up the connection to {address}: {error}"),
+            },
+            Err(error) if error.kind() == ErrorKind::WouldBlock => break,
+            Err(error) => {
+                warn!("Couldn't accept a client: {error}");
+                break;
+            }
+        }
+    }
+
+    let snapshot_due = server.snapshot_timer.tick(time.delta()).just_finished();
+    let snapshot = snapshot_due.then(|| ServerMessage::Snapshot {
+        balls: ball_query
+            .iter()
+            .map(|(transform, radius, colour)| BallKeyframe::new(transform, radius, colour))
+            .collect(),
+    });
+
+    server.clients.retain_mut(|client| {
+        client.spawn_allowance = (client.spawn_allowance
+            + CLIENT_SPAWN_RATE * time.delta_seconds())
+        .min(CLIENT_SPAWN_RATE);
+        let connection = &mut client.connection;
+        let result = connection.receive::<ClientMessage>().map(|messages| {
+            for ClientMessage::SpawnBall { position } in messages {
+                // Spawns past the client's allowance are ignored.
+                if client.spawn_allowance < 1.0 {
+                    continue;
+                }
+                client.spawn_allowance -= 1.0;
+                let position = Vec2::from_array(position);
+                let Some(cage) = cage_at(&cage_query, position) else {
+                    continue;
+                };
+                spawn_ball(
+                    &mut commands,
+                    &mut materials,
+                    &mut ball_assets,
+                    &mut rng,
+                    &palette,
+                    &settings,
+                    cage,
+                    position,
+                );
+            }
+        });
+        let result = result.and_then(|()| match &snapshot {
+            // A client still busy with the last snapshot skips this one, rather than getting
+            // further behind.
+            Some(snapshot) if connection.outgoing.is_empty() => connection.send(snapshot),
+            _ => connection.flush(),
+        });
+        match result {
+            Ok(()) => true,
+            Err(error) => {
+                info!("A client left: {error}");
+                false
+            }
+        }
+    });
+}
+
+/// Hides the local balls, which the server's take the place of.
+pub fn start_client(mut ball_query: Query<&mut Visibility, With<Ball>>) {
+    for mut visibility in &mut ball_query {
+        *visibility = Visibility::Hidden;
+    }
+}
+
+/// Shows the balls from the server's latest snapshot, and asks it for a ball wherever the left
+/// mouse button is clicked. Goes back to the title screen if the server goes away.
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+pub fn run_client(
+    mut client: ResMut<NetworkClient>,
+    mut replay_ball_query: Query<
+        (&mut Transform, &Handle<ColorMaterial>, &mut Visibility),
+        With<ReplayBall>,
+    >,
+    mouse_input: Res<ButtonInput<MouseButton>>,
+    window_query: Query<&Window, With<PrimaryWindow>>,
+    camera_query: Query<(&Camera, &GlobalTransform)>,
+    mut next_state: ResMut<NextState<AppState>>,
+    mut commands: Commands,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    ball_assets: Res<BallAssets>,
+) {
+    let mut result = client.server.receive::<ServerMessage>().map(|messages| {
+        // Only the newest snapshot matters.
+        if let Some(ServerMessage::Snapshot { balls }) = messages.last() {
+            replay::show_balls(
+                balls,
+                &mut replay_ball_query,
+                &mut commands,
+                &mut materials,
+                &ball_assets,
+            );
+        }
+    });
+
+    if mouse_input.just_pressed(MouseButton::Left) {
+        let cursor = window_query
+            .get_single()
+            .ok()
+            .zip(camera_query.get_single().ok())
+            .and_then(|(window, (camera, camera_transform))| {
+                cursor_world_position(window, camera, camera_transform)
+            });
+        if let Some(position) = cursor {
+            result = result.and_then(|()| {
+                client.server.send(&ClientMessage::SpawnBall {
+                    position: position.to_array(),
+                })
+            });
+        }
+    }
+    let result = result.and_then(|()| client.server.flush());
+
+    if let Err(error) = result {
+        warn!("Lost the connection to the server: {error}");
+        next_state.set(AppState::Menu);
+    }
+}
+
+/// Disconnects from the server.
+pub fn stop_client(mut commands: Commands) {
+    commands.remove_resource::<NetworkClient>();
+}
@@ -0,0 +1,339 @@
+use std::f32::consts::TAU;
+
+use bevy::{prelude::*, sprite::MaterialMesh2dBundle};
+
+use crate::{
+    cage::{Cage, CageVelocity, InCage},
+    keybindings::{Action, Actions},
+    kind::BallKind,
+    settings::{PegLattice, Settings},
+    Ball, Collision, OtherCollisionEvent, Radius, Sleeping, Velocity, BALL_RADIUS,
+};
+
+const OBSTACLE_COLOR: Color = Color::rgb(0.6, 0.6, 0.6);
+// Segments are drawn and collided with as capsules of this thickness.
+const OBSTACLE_THICKNESS: f32 = 4.0;
+
+/// Something fixed inside a cage that balls bounce off.
+#[derive(Component, Clone, Copy)]
+pub enum Obstacle {
+    Circle {
+        radius: f32,
+    },
+    /// A line through the obstacle's position, along its local x axis.
+    Segment {
+        half_length: f32,
+    },
+}
+
+/// A static [`Obstacle`] placed as part of a peg field.
+#[derive(Component)]
+pub struct Peg;
+
+/// Scripted movement for an [`Obstacle`].
+#[derive(Component)]
+pub struct ObstacleMotion {
+    /// Center of the oscillation.
+    pub origin: Vec2,
+    /// Furthest offset from `origin`, reached twice every period.
+    pub amplitude: Vec2,
+    /// In oscillations per second.
+    pub frequency: f32,
+    /// In radians per second.
+    pub angular_velocity: f32,
+}
+
+impl ObstacleMotion {
+    fn phase(&self, time: f32) -> f32 {
+        TAU * self.frequency * time
+    }
+
+    fn position(&self, time: f32) -> Vec2 {
+        self.origin + self.amplitude * self.phase(time).sin()
+    }
+
+    fn velocity(&self, time: f32) -> Vec2 {
+        self.amplitude * TAU * self.frequency * self.phase(time).cos()
+    }
+}
+
+pub fn spawn_obstacle(
+    commands: &mut Commands,
+    materials: &mut ResMut<Assets<ColorMaterial>>,
+    meshes: &mut ResMut<Assets<Mesh>>,
+    obstacle: Obstacle,
+    cage: Entity,
+    position: Vec2,
+) -> Entity {
+    let mesh = match obstacle {
+        Obstacle::Circle { radius } => meshes.add(Circle { radius }),
+        Obstacle::Segment { half_length } => meshes.add(Rectangle::new(
+            half_length * 2.0 + OBSTACLE_THICKNESS,
+            OBSTACLE_THICKNESS,
+        )),
+    };
+
+    commands
+        .spawn((
+            MaterialMesh2dBundle {
+                mesh: mesh.into(),
+                material: materials.add(OBSTACLE_COLOR),
+                transform: Transform::from_translation(position.extend(0.5)),
+                ..Default::default()
+            },
+            obstacle,
+            InCage(cage),
+        ))
+        .id()
+}
+
+/// Adds a rotating bar and a swinging bumper to every cage with X, or removes them again.
+pub fn toggle_obstacles(
+    actions: Actions,
+    obstacle_query: Query<Entity, With<ObstacleMotion>>,
+    cage_query: Query<(Entity, &Cage, &Transform)>,
+    mut commands: Commands,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+) {
+    if !actions.just_pressed(Action::ToggleObstacles) {
+        return;
+    }
+    if !obstacle_query.is_empty() {
+        for entity in &obstacle_query {
+            commands.entity(entity).despawn();
+        }
+        return;
+    }
+
+    for (cage_entity, cage, cage_transform) in &cage_query {
+        let center = cage_transform.translation.truncate();
+
+        let bar_position = center + Vec2::new(0.0, -0.5 * cage.radius);
+        let bar = spawn_obstacle(
+            &mut commands,
+            &mut materials,
+            &mut meshes,
+            Obstacle::Segment {
+                half_length: 0.25 * cage.radius,
+            },
+            cage_entity,
+            bar_position,
+        );
+        commands.entity(bar).insert(ObstacleMotion {
+            origin: bar_position,
+            amplitude: Vec2::ZERO,
+            frequency: 0.0,
+            angular_velocity: 1.5,
+        });
+
+        let bumper_position = center + Vec2::new(0.0, 0.3 * cage.radius);
+        let bumper = spawn_obstacle(
+            &mut commands,
+            &mut materials,
+            &mut meshes,
+            Obstacle::Circle {
+                radius: 0.08 * cage.radius,
+            },
+            cage_entity,
+            bumper_position,
+        );
+        commands.entity(bumper).insert(ObstacleMotion {
+            origin: bumper_position,
+            amplitude: Vec2::new(0.4 * cage.radius, 0.0),
+            frequency: 0.25,
+            angular_velocity: 0.0,
+        });
+    }
+}
+
+/// Fills every cage with a lattice of pegs with L, or clears them again.
+pub fn toggle_peg_field(
+    actions: Actions,
+    peg_query: Query<Entity, With<Peg>>,
+    cage_query: Query<(Entity, &Cage, &Transform)>,
+    mut commands: Commands,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    settings: Res<Settings>,
+) {
+    if !actions.just_pressed(Action::TogglePegField) {
+        return;
+    }
+    if !peg_query.is_empty() {
+        for entity in &peg_query {
+            commands.entity(entity).despawn();
+        }
+        return;
+    }
+
+    let spacing = settings.peg_spacing;
+    let row_spacing = match settings.peg_lattice {
+        PegLattice::Grid => spacing,
+        PegLattice::Hex => spacing * 3.0_f32.sqrt() / 2.0,
+    };
+    // Room for a ball to squeeze between a peg and the wall.
+    let margin = settings.peg_radius + BALL_RADIUS;
+
+    for (cage_entity, cage, cage_transform) in &cage_query {
+        let center = cage_transform.translation.truncate();
+        let extent = cage.radius * 2.0;
+        let rows = (extent / row_spacing).ceil() as i32;
+        let columns = (extent / spacing).ceil() as i32;
+
+        for row in -rows..=rows {
+            let shift = match settings.peg_lattice {
+                PegLattice::Hex if row % 2 != 0 => spacing / 2.0,
+                _ => 0.0,
+            };
+            for column in -columns..=columns {
+                let position =
+                    center + Vec2::new(column as f32 * spacing + shift, row as f32 * row_spacing);
+                // Balls spawn in the middle, so keep that clear.
+                if position.distance(center) < margin
+                    || !cage.contains(cage_transform, position, margin)
+                {
+                    continue;
+                }
+
+                let peg = spawn_obstacle(
+                    &mut commands,
+                    &mut materials,
+                    &mut meshes,
+                    Obstacle::Circle {
+                        radius: settings.peg_radius,
+                    },
+                    cage_entity,
+                    position,
+                );
+                commands.entity(peg).insert(Peg);
+            }
+        }
+    }
+}
+
+/// Keeps obstacles in place relative to a cage that's being dragged around.
+#[allow(clippy::type_complexity)]
+#[cfg_attr(feature = "rapier", allow(dead_code))]
+pub fn carry_obstacles(
+    mut obstacle_query: Query<
+        (&mut Transform, Option<&mut ObstacleMotion>, &InCage),
+        With<Obstacle>,
+    >,
+    cage_query: Query<&CageVelocity>,
+    time: Res<Time>,
+) {
+    for (mut transform, motion, in_cage) in &mut obstacle_query {
+        let Ok(cage_velocity) = cage_query.get(in_cage.0) else {
+            continue;
+        };
+        let offset = cage_velocity.0 * time.delta_seconds();
+        match motion {
+            Some(mut motion) => motion.origin += offset,
+            None => transform.translation += offset.extend(0.0),
+        }
+    }
+}
+
+#[cfg_attr(feature = "rapier", allow(dead_code))]
+pub fn move_obstacles(
+    mut obstacle_query: Query<(&mut Transform, &ObstacleMotion), With<Obstacle>>,
+    time: Res<Time>,
+) {
+    for (mut transform, motion) in &mut obstacle_query {
+        let position = motion.position(time.elapsed_seconds());
+        transform.translation = position.extend(transform.translation.z);
+        transform.rotate_z(motion.angular_velocity * time.delta_seconds());
+    }
+}
+
+#[allow(clippy::type_complexity)]
+#[cfg_attr(feature = "rapier", allow(dead_code))]
+pub fn collide_obstacles(
+    mut commands: Commands,
+    mut ball_query: Query<
+        (
+            Entity,
+            &mut Transform,
+            &mut Velocity,
+            &Radius,
+            &BallKind,
+            &Collision,
+            &InCage,
+            Has<Sleeping>,
+        ),
+        With<Ball>,
+    >,
+    obstacle_query: Query<
+        (
+            Entity,
+            &Obstacle,
+            &Transform,
+            Option<&ObstacleMotion>,
+            &InCage,
+        ),
+        Without<Ball>,
+    >,
+    mut collision_events: EventWriter<OtherCollisionEvent>,
+    settings: Res<Settings>,
+    time: Res<Time>,
+) {
+    for (entity, mut ball_transform, mut ball_velocity, radius, kind, _, in_cage, sleeping) in
+        &mut ball_query
+    {
+        let ball_radius = radius.0;
+
+        for (obstacle_entity, obstacle, obstacle_transform, motion, obstacle_cage) in
+            &obstacle_query
+        {
+            // Only moving obstacles can disturb a sleeping ball.
+            if in_cage != obstacle_cage || (sleeping && motion.is_none()) {
+                continue;
+            }
+            let ball_position = ball_transform.translation.truncate();
+            let obstacle_position = obstacle_transform.translation.truncate();
+
+            let (closest_point, reach) = match *obstacle {
+                Obstacle::Circle { radius } => (obstacle_position, ball_radius + radius),
+                Obstacle::Segment { half_length } => {
+                    let direction = (obstacle_transform.rotation * Vec3::X).truncate();
+                    let along = (ball_position - obstacle_position)
+                        .dot(direction)
+                        .clamp(-half_length, half_length);
+                    (
+                        obstacle_position + direction * along,
+                        ball_radius + OBSTACLE_THICKNESS / 2.0,
+                    )
+                }
+            };
+            let distance = ball_position.distance(closest_point);
+            if distance >= reach || distance == 0.0 {
+                continue;
+            }
+            let normal = (ball_position - closest_point) / distance;
+            if sleeping {
+                commands.entity(entity).remove::<Sleeping>();
+            }
+
+            // Bounce relative to the surface, so moving obstacles knock balls along.
+            let surface_velocity = motion.map_or(Vec2::ZERO, |motion| {
+                motion.velocity(time.elapsed_seconds())
+                    + motion.angular_velocity * (closest_point - obstacle_position).perp()
+            });
+            let approach = (ball_velocity.0 - surface_velocity).dot(normal);
+            if approach < 0.0 {
+                ball_velocity.0 -=
+                    (1.0 + kind.restitution(settings.restitution)) * approach * normal;
+            }
+            ball_transform.translation += ((reach - distance) * normal).extend(0.0);
+
+            collision_events.send(OtherCollisionEvent {
+                self_entity: entity,
+                other_entity: obstacle_entity,
+                impact_speed: (-approach).max(0.0),
+                point: ball_position - normal * ball_radius,
+                normal,
+            });
+        }
+    }
+}
@@ -0,0 +1,59 @@
+use bevy::prelude::*;
+use rand::Rng;
+
+use crate::{
+    keybindings::{Action, Actions},
+    rng::SimRng,
+};
+
+const NEON_COLORS: [Color; 6] = [
+    Color::rgb(1.0, 0.1, 0.6),
+    Color::rgb(0.1, 1.0, 0.9),
+    Color::rgb(0.6, 1.0, 0.1),
+    Color::rgb(1.0, 0.9, 0.1),
+    Color::rgb(0.6, 0.2, 1.0),
+    Color::rgb(1.0, 0.4, 0.1),
+];
+
+/// The colours new balls are drawn from.
+#[derive(Resource, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum BallPalette {
+    /// Any RGB colour at all.
+    #[default]
+    Random,
+    Pastel,
+    Neon,
+    Grayscale,
+}
+
+impl BallPalette {
+    pub fn next(self) -> Self {
+        match self {
+            BallPalette::Random => BallPalette::Pastel,
+            BallPalette::Pastel => BallPalette::Neon,
+            BallPalette::Neon => BallPalette::Grayscale,
+            BallPalette::Grayscale => BallPalette::Random,
+        }
+    }
+
+    pub fn random_colour(self, rng: &mut SimRng) -> Color {
+        match self {
+            BallPalette::Random => Color::rgb(rng.gen(), rng.gen(), rng.gen()),
+            BallPalette::Pastel => Color::hsl(rng.gen::<f32>() * 360.0, 0.7, 0.8),
+            BallPalette::Neon => NEON_COLORS[rng.gen_range(0..NEON_COLORS.len())],
+            BallPalette::Grayscale => {
+                // Not too dark to stand out against the background.
+                let lightness = 0.3 + rng.gen::<f32>() * 0.7;
+                Color::rgb(lightness, lightness, lightness)
+            }
+        }
+    }
+}
+
+/// Switches the palette new balls are drawn from with Tab.
+pub fn cycle_palette(actions: Actions, mut palette: ResMut<BallPalette>) {
+    if actions.just_pressed(Action::CyclePalette) {
+        *palette = palette.next();
+        info!("Ball palette: {:?}", *palette);
+    }
+}
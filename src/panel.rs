@@ -0,0 +1,247 @@
+use bevy::{prelude::*, ui::RelativeCursorPosition};
+
+use crate::{
+    cage::{wake_all, Cage, NestedIn, CAGE_MAX_RADIUS, CAGE_MIN_RADIUS},
+    keybindings::{Action, Actions},
+    settings::Settings,
+    GravityField, Sleeping,
+};
+
+const PANEL_BACKGROUND_COLOR: Color = Color::rgba(0.0, 0.0, 0.0, 0.6);
+const PANEL_TEXT_COLOR: Color = Color::rgb(0.9, 0.9, 0.9);
+const SLIDER_TRACK_COLOR: Color = Color::rgb(0.25, 0.25, 0.25);
+const SLIDER_FILL_COLOR: Color = Color::rgb(0.5, 0.6, 0.9);
+const SLIDER_WIDTH: Val = Val::Px(200.0);
+const SLIDER_HEIGHT: Val = Val::Px(12.0);
+const MAX_GRAVITY: f32 = 1000.0;
+// In pixels per second.
+const MAX_BALL_SPEED: f32 = 800.0;
+const MAX_SCREEN_SHAKE: f32 = 2.0;
+
+/// The root of the settings panel opened with F2.
+#[derive(Component)]
+pub struct SettingsPanel;
+
+/// What a slider in the settings panel changes.
+#[derive(Component, Clone, Copy)]
+pub enum SliderTarget {
+    /// The strength of the global gravity field, keeping its direction.
+    Gravity,
+    BallSpeed,
+    SpawnChance,
+    /// The radius of every cage that isn't nested in another one.
+    CageRadius,
+    Restitution,
+    ScreenShake,
+}
+
+impl SliderTarget {
+    const ALL: [SliderTarget; 6] = [
+        SliderTarget::Gravity,
+        SliderTarget::BallSpeed,
+        SliderTarget::SpawnChance,
+        SliderTarget::CageRadius,
+        SliderTarget::Restitution,
+        SliderTarget::ScreenShake,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            SliderTarget::Gravity => "Gravity",
+            SliderTarget::BallSpeed => "Ball speed",
+            SliderTarget::SpawnChance => "Spawn chance",
+            SliderTarget::CageRadius => "Cage radius",
+            SliderTarget::Restitution => "Restitution",
+            SliderTarget::ScreenShake => "Screen shake",
+        }
+    }
+
+    fn range(self) -> (f32, f32) {
+        match self {
+            SliderTarget::Gravity => (0.0, MAX_GRAVITY),
+            SliderTarget::BallSpeed => (0.0, MAX_BALL_SPEED),
+            SliderTarget::SpawnChance | SliderTarget::Restitution => (0.0, 1.0),
+            SliderTarget::CageRadius => (CAGE_MIN_RADIUS, CAGE_MAX_RADIUS),
+            SliderTarget::ScreenShake => (0.0, MAX_SCREEN_SHAKE),
+        }
+    }
+
+    fn format(self, value: f32) -> String {
+        match self {
+            SliderTarget::SpawnChance | SliderTarget::Restitution | SliderTarget::ScreenShake => {
+                format!("{value:.2}")
+            }
+            _ => format!("{value:.0}"),
+        }
+    }
+}
+
+/// The part of a slider's track that is filled in up to its value.
+#[derive(Component)]
+pub struct SliderFill(SliderTarget);
+
+/// The text above a slider, with its name and value.
+#[derive(Component)]
+pub struct SliderLabel(SliderTarget);
+
+/// Opens the settings panel with F2, or closes it if already open.
+pub fn toggle_settings_panel(
+    actions: Actions,
+    panel_query: Query<Entity, With<SettingsPanel>>,
+    mut commands: Commands,
+) {
+    if !actions.just_pressed(Action::ToggleSettingsPanel) {
+        return;
+    }
+    if panel_query.is_empty() {
+        spawn_settings_panel(&mut commands);
+    }
+    for entity in &panel_query {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+fn spawn_settings_panel(commands: &mut Commands) {
+    let text_style = TextStyle {
+        font_size: 16.0,
+        color: PANEL_TEXT_COLOR,
+        ..Default::default()
+    };
+
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    bottom: Val::Px(8.0),
+                    left: Val::Px(8.0),
+                    flex_direction: FlexDirection::Column,
+                    row_gap: Val::Px(6.0),
+                    padding: UiRect::all(Val::Px(10.0)),
+                    ..Default::default()
+                },
+                background_color: PANEL_BACKGROUND_COLOR.into(),
+                ..Default::default()
+            },
+            // So clicks on the panel don't also reach the cages behind it.
+            Interaction::default(),
+            SettingsPanel,
+        ))
+        .with_children(|parent| {
+            for target in SliderTarget::ALL {
+                parent.spawn((
+                    TextBundle::from_section("", text_style.clone()),
+                    SliderLabel(target),
+                ));
+                parent
+                    .spawn((
+                        ButtonBundle {
+                            style: Style {
+                                width: SLIDER_WIDTH,
+                                height: SLIDER_HEIGHT,
+                                ..Default::default()
+                            },
+                            background_color: SLIDER_TRACK_COLOR.into(),
+                            ..Default::default()
+                        },
+                        RelativeCursorPosition::default(),
+                        target,
+                    ))
+                    .with_children(|parent| {
+                        parent.spawn((
+                            NodeBundle {
+                                style: Style {
+                                    width: Val::Percent(0.0),
+                                    height: Val::Percent(100.0),
+                                    ..Default::default()
+                                },
+                                background_color: SLIDER_FILL_COLOR.into(),
+                                ..Default::default()
+                            },
+                            SliderFill(target),
+                        ));
+                    });
+            }
+        });
+}
+
+/// Whether the cursor is over the settings panel, or one of its sliders is being dragged.
+pub fn pointer_over_panel(interaction_query: Query<&Interaction>) -> bool {
+    interaction_query
+        .iter()
+        .any(|interaction| *interaction != Interaction::None)
+}
+
+/// Sets the value of a held slider from where the cursor is along it.
+pub fn drag_sliders(
+    slider_query: Query<(&Interaction, &RelativeCursorPosition, &SliderTarget)>,
+    mut settings: ResMut<Settings>,
+    mut gravity_field: ResMut<GravityField>,
+    mut cage_query: Query<&mut Cage, Without<NestedIn>>,
+    sleeping_query: Query<Entity, With<Sleeping>>,
+    mut commands: Commands,
+) {
+    for (interaction, cursor, target) in &slider_query {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+        // Keeps following the cursor once it leaves the track, as long as the button is held.
+        let Some(cursor) = cursor.normalized else {
+            continue;
+        };
+        let (min, max) = target.range();
+        let value = min + (max - min) * cursor.x.clamp(0.0, 1.0);
+        match target {
+            SliderTarget::Gravity => {
+                let direction = gravity_field.try_normalize().unwrap_or(Vec2::NEG_Y);
+                gravity_field.0 = direction * value;
+            }
+            SliderTarget::BallSpeed => settings.ball_speed = value,
+            SliderTarget::SpawnChance => settings.spawn_chance = value,
+            SliderTarget::CageRadius => {
+                for mut cage in &mut cage_query {
+                    cage.radius = value;
+                }
+                wake_all(&mut commands, &sleeping_query);
+            }
+            SliderTarget::Restitution => settings.restitution = value,
+            SliderTarget::ScreenShake => settings.screen_shake = value,
+        }
+    }
+}
+
+/// Shows the current values on the sliders, however they were changed.
+pub fn update_sliders(
+    mut fill_query: Query<(&mut Style, &SliderFill)>,
+    mut label_query: Query<(&mut Text, &SliderLabel)>,
+    settings: Res<Settings>,
+    gravity_field: Res<GravityField>,
+    cage_query: Query<&Cage, Without<NestedIn>>,
+) {
+    let value = |target| match target {
+        SliderTarget::Gravity => gravity_field.length(),
+        SliderTarget::BallSpeed => settings.ball_speed,
+        SliderTarget::SpawnChance => settings.spawn_chance,
+        SliderTarget::CageRadius => cage_query
+            .iter()
+            .next()
+            .map_or(CAGE_MIN_RADIUS, |cage| cage.radius),
+        SliderTarget::Restitution => settings.restitution,
+        SliderTarget::ScreenShake => settings.screen_shake,
+    };
+
+    for (mut style, fill) in &mut fill_query {
+        let (min, max) = fill.0.range();
+        let fraction = ((value(fill.0) - min) / (max - min)).clamp(0.0, 1.0);
+        let width = Val::Percent(fraction * 100.0);
+        if style.width != width {
+            style.width = width;
+        }
+    }
+    for (mut text, label) in &mut label_query {
+        let value = format!("{}: {}", label.0.name(), label.0.format(value(label.0)));
+        if text.sections[0].value != value {
+            text.sections[0].value = value;
+        }
+    }
+}
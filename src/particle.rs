@@ -0,0 +1,152 @@
+use std::f32::consts::TAU;
+
+use bevy::{prelude::*, sprite::MaterialMesh2dBundle};
+
+use crate::{BallColor, CageCollisionEvent, OtherCollisionEvent};
+
+const PARTICLE_COUNT: u32 = 10;
+const PARTICLE_RADIUS: f32 = 1.5;
+// In pixels per second.
+const PARTICLE_SPEED: f32 = 120.0;
+// In seconds.
+const PARTICLE_LIFETIME: f32 = 0.4;
+// Softer collisions don't throw sparks. Harder ones throw more, up to `SPARK_COUNT` at
+// `SPARK_FULL_IMPACT_SPEED`.
+const SPARK_MIN_IMPACT_SPEED: f32 = 150.0;
+const SPARK_FULL_IMPACT_SPEED: f32 = 600.0;
+const SPARK_COUNT: u32 = 6;
+// Sparks fly off within this many radians either side of the contact normal.
+const SPARK_SPREAD: f32 = 1.0;
+// With lots of balls rattling around, only this many collisions a frame throw sparks.
+const MAX_SPARK_BURSTS: usize = 8;
+
+/// A short-lived speck of colour flying away from where something happened.
+#[derive(Component)]
+pub struct Particle {
+    velocity: Vec2,
+    lifetime: Timer,
+}
+
+/// Scatters a ring of particles outwards from `position`.
+pub fn spawn_particle_burst(
+    commands: &mut Commands,
+    materials: &mut ResMut<Assets<ColorMaterial>>,
+    meshes: &mut ResMut<Assets<Mesh>>,
+    position: Vec2,
+    colour: Color,
+) {
+    let mesh = meshes.add(Circle {
+        radius: PARTICLE_RADIUS,
+    });
+    for i in 0..PARTICLE_COUNT {
+        let angle = (i as f32 + rand::random::<f32>()) * TAU / PARTICLE_COUNT as f32;
+        let speed = PARTICLE_SPEED * (0.5 + rand::random::<f32>());
+        spawn_particle(
+            commands,
+            materials,
+            &mesh,
+            position,
+            colour,
+            Vec2::from_angle(angle) * speed,
+        );
+    }
+}
+
+/// Throws sparks off the contact points of hard collisions, back the way the ball came, in its
+/// colour. Each of two colliding balls throws its own.
+pub fn spawn_collision_sparks(
+    mut wall_collision_events: EventReader<CageCollisionEvent>,
+    mut ball_collision_events: EventReader<OtherCollisionEvent>,
+    colour_query: Query<&BallColor>,
+    mut commands: Commands,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+) {
+    let impacts = wall_collision_events
+        .read()
+        .map(|event| (event.entity, event.impact_speed, event.point, event.normal))
+        .chain(ball_collision_events.read().map(|event| {
+            (
+                event.self_entity,
+                event.impact_speed,
+                event.point,
+                event.normal,
+            )
+        }))
+        .filter(|&(_, impact_speed, _, _)| impact_speed >= SPARK_MIN_IMPACT_SPEED)
+        .take(MAX_SPARK_BURSTS);
+
+    let mut mesh = None;
+    for (entity, impact_speed, point, normal) in impacts {
+        let Ok(colour) = colour_query.get(entity) else {
+            continue;
+        };
+        let hardness = (impact_speed / SPARK_FULL_IMPACT_SPEED).min(1.0);
+        let count = (SPARK_COUNT as f32 * hardness).ceil() as u32;
+        let mesh = mesh.get_or_insert_with(|| {
+            meshes.add(Circle {
+                radius: PARTICLE_RADIUS,
+            })
+        });
+        for _ in 0..count {
+            let angle = (rand::random::<f32>() * 2.0 - 1.0) * SPARK_SPREAD;
+            let speed = PARTICLE_SPEED * (0.5 + rand::random::<f32>()) * hardness;
+            let velocity = Vec2::from_angle(angle).rotate(normal) * speed;
+            spawn_particle(
+                &mut commands,
+                &mut materials,
+                mesh,
+                point,
+                colour.0,
+                velocity,
+            );
+        }
+    }
+}
+
+fn spawn_particle(
+    commands: &mut Commands,
+    materials: &mut Assets<ColorMaterial>,
+    mesh: &Handle<Mesh>,
+    position: Vec2,
+    colour: Color,
+    velocity: Vec2,
+) {
+    commands.spawn((
+        MaterialMesh2dBundle {
+            mesh: mesh.clone().into(),
+            // Each has its own, to fade out on its own.
+            material: materials.add(colour),
+            transform: Transform::from_translation(position.extend(2.0)),
+            ..Default::default()
+        },
+        Particle {
+            velocity,
+            lifetime: Timer::from_seconds(PARTICLE_LIFETIME, TimerMode::Once),
+        },
+    ));
+}
+
+#[allow(clippy::type_complexity)]
+pub fn update_particles(
+    mut particle_query: Query<(
+        Entity,
+        &mut Particle,
+        &mut Transform,
+        &Handle<ColorMaterial>,
+    )>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut commands: Commands,
+    time: Res<Time>,
+) {
+    for (entity, mut particle, mut transform, material) in &mut particle_query {
+        if particle.lifetime.tick(time.delta()).finished() {
+            commands.entity(entity).despawn();
+            continue;
+        }
+        transform.translation += (particle.velocity * time.delta_seconds()).extend(0.0);
+        if let Some(material) = materials.get_mut(material) {
+            material.color.set_a(particle.lifetime.fraction_remaining());
+        }
+    }
+}
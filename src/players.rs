@@ -0,0 +1,127 @@
+use bevy::prelude::*;
+
+use crate::{
+    cage::{self, Cage, InCage, NestedIn, CAGE_RADIUS},
+    cannon::Cannon,
+    keybindings::Action,
+    menu::GameMode,
+};
+
+// Horizontal distance between the centers of the two players' cages.
+const PLAYER_CAGE_SPACING: f32 = 4.0 * CAGE_RADIUS;
+
+/// Marks the cage a player plays in, in two-player mode. Its balls, cannon and score belong to
+/// that player.
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Player {
+    One,
+    Two,
+}
+
+impl Player {
+    pub fn index(self) -> usize {
+        match self {
+            Player::One => 0,
+            Player::Two => 1,
+        }
+    }
+
+    /// The actions that turn this player's cannon left and right, and fire it.
+    pub fn cannon_actions(player: Option<Player>) -> (Action, Action, Action) {
+        match player {
+            None => (
+                Action::AimCannonsLeft,
+                Action::AimCannonsRight,
+                Action::FireCannons,
+            ),
+            Some(Player::One) => (
+                Action::AimCannonsLeft,
+                Action::AimCannonsRight,
+                Action::PlayerOneFire,
+            ),
+            Some(Player::Two) => (
+                Action::PlayerTwoAimLeft,
+                Action::PlayerTwoAimRight,
+                Action::PlayerTwoFire,
+            ),
+        }
+    }
+}
+
+/// Each player's points in two-player mode, indexed by [`Player::index`].
+#[derive(Resource, Default)]
+pub struct PlayerScores(pub [u64; 2]);
+
+/// Run condition for systems whose keys the players use for their cannons instead.
+pub fn two_player(mode: Res<GameMode>) -> bool {
+    *mode == GameMode::TwoPlayer
+}
+
+/// Sets up a cage with a cannon for each player when two-player mode starts, and takes the
+/// second one away again when it ends.
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+pub fn arrange_player_cages(
+    mode: Res<GameMode>,
+    mut cage_query: Query<(Entity, &mut Transform, Option<&Player>), With<Cage>>,
+    cannon_query: Query<(Entity, &InCage), With<Cannon>>,
+    in_cage_query: Query<(Entity, &InCage), Without<Cannon>>,
+    nested_query: Query<(Entity, &NestedIn)>,
+    mut commands: Commands,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+) {
+    if !mode.is_changed() {
+        return;
+    }
+    let player_two_cage = cage_query
+        .iter()
+        .find(|(_, _, player)| *player == Some(&Player::Two))
+        .map(|(entity, _, _)| entity);
+    // The first player plays in the cage that came with a cannon.
+    let Some(main_cage) = cannon_query
+        .iter()
+        .map(|(_, in_cage)| in_cage.0)
+        .find(|&cage| Some(cage) != player_two_cage)
+    else {
+        return;
+    };
+
+    if *mode == GameMode::TwoPlayer {
+        if player_two_cage.is_some() {
+            return;
+        }
+        if let Ok((_, mut transform, _)) = cage_query.get_mut(main_cage) {
+            transform.translation.x = -PLAYER_CAGE_SPACING / 2.0;
+        }
+        commands.entity(main_cage).insert(Player::One);
+        let second_cage = cage::spawn_cage(
+            &mut commands,
+            &mut materials,
+            &mut meshes,
+            Vec3::new(PLAYER_CAGE_SPACING / 2.0, 0.0, 0.0),
+            Cage::new(CAGE_RADIUS),
+        );
+        commands.entity(second_cage).insert(Player::Two);
+        commands.spawn((Cannon::default(), InCage(second_cage)));
+    } else {
+        let Some(player_two_cage) = player_two_cage else {
+            return;
+        };
+        // Takes the second cage's balls, spawners, obstacles and cannon with it.
+        for (entity, in_cage) in cannon_query.iter().chain(&in_cage_query) {
+            if in_cage.0 == player_two_cage {
+                commands.entity(entity).despawn_recursive();
+            }
+        }
+        for (entity, nested_in) in &nested_query {
+            if nested_in.0 == player_two_cage {
+                commands.entity(entity).despawn_recursive();
+            }
+        }
+        commands.entity(player_two_cage).despawn_recursive();
+        if let Ok((_, mut transform, _)) = cage_query.get_mut(main_cage) {
+            transform.translation.x = 0.0;
+        }
+        commands.entity(main_cage).remove::<Player>();
+    }
+}
@@ -0,0 +1,241 @@
+use bevy::{prelude::*, sprite::MaterialMesh2dBundle, utils::HashMap};
+use rand::Rng;
+
+use crate::{
+    ball_assets::BallAssets,
+    cage::{wake_all, Cage, InCage, NestedIn},
+    free_spawn_position,
+    palette::BallPalette,
+    rng::SimRng,
+    settings::Settings,
+    spawn_ball, Ball, Radius, ResetEvent, Sleeping,
+};
+
+const POWER_UP_RADIUS: f32 = 8.0;
+// Average seconds between pickups appearing, give or take `POWER_UP_JITTER`.
+const POWER_UP_INTERVAL: f32 = 10.0;
+const POWER_UP_JITTER: f32 = 4.0;
+// Per cage.
+const MAX_POWER_UPS: usize = 2;
+// How long timed effects last, in real seconds.
+const POWER_UP_DURATION: f32 = 6.0;
+// On top of the time scale.
+pub const SLOW_MOTION_SPEED: f32 = 0.4;
+// Extra balls released by a multiball pickup.
+const MULTIBALL_COUNT: usize = 3;
+
+/// What a pickup does to the simulation once a ball touches it.
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum PowerUp {
+    /// Slows everything down for a while.
+    SlowMotion,
+    /// Turns gravity upside down for a while.
+    AntiGravity,
+    /// Releases a few extra balls where it was picked up.
+    Multiball,
+}
+
+impl PowerUp {
+    const ALL: [PowerUp; 3] = [
+        PowerUp::SlowMotion,
+        PowerUp::AntiGravity,
+        PowerUp::Multiball,
+    ];
+
+    fn colour(self) -> Color {
+        match self {
+            PowerUp::SlowMotion => Color::rgb(0.3, 0.6, 1.0),
+            PowerUp::AntiGravity => Color::rgb(0.8, 0.3, 1.0),
+            PowerUp::Multiball => Color::rgb(1.0, 0.8, 0.2),
+        }
+    }
+}
+
+/// When the next pickup appears.
+#[derive(Resource)]
+pub struct PowerUpTimer(Timer);
+
+impl Default for PowerUpTimer {
+    fn default() -> Self {
+        Self(Timer::from_seconds(POWER_UP_INTERVAL, TimerMode::Once))
+    }
+}
+
+fn next_power_up_timer(rng: &mut SimRng) -> Timer {
+    let wait = POWER_UP_INTERVAL + rng.gen_range(-1.0..1.0) * POWER_UP_JITTER;
+    Timer::from_seconds(wait, TimerMode::Once)
+}
+
+/// The timed power-up effects currently running, with how long each has left.
+#[derive(Resource, Default)]
+pub struct ActiveEffects(HashMap<PowerUp, Timer>);
+
+impl ActiveEffects {
+    pub fn is_active(&self, power_up: PowerUp) -> bool {
+        self.0.contains_key(&power_up)
+    }
+}
+
+/// Every so often, places a random pickup somewhere free in a random cage.
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+pub fn spawn_power_ups(
+    mut timer: ResMut<PowerUpTimer>,
+    cage_query: Query<(Entity, &Cage, &Transform), Without<NestedIn>>,
+    ball_query: Query<(&Transform, &Radius, &InCage), With<Ball>>,
+    power_up_query: Query<&InCage, With<PowerUp>>,
+    mut commands: Commands,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut rng: ResMut<SimRng>,
+    time: Res<Time>,
+) {
+    timer.0.tick(time.delta());
+    if !timer.0.finished() {
+        return;
+    }
+    timer.0 = next_power_up_timer(&mut rng);
+
+    let cages: Vec<_> = cage_query.iter().collect();
+    if cages.is_empty() {
+        return;
+    }
+    let (cage_entity, cage, cage_transform) = cages[rng.gen_range(0..cages.len())];
+    let power_ups = power_up_query
+        .iter()
+        .filter(|in_cage| in_cage.0 == cage_entity)
+        .count();
+    if power_ups >= MAX_POWER_UPS {
+        return;
+    }
+    let others: Vec<_> = ball_query
+        .iter()
+        .filter(|(_, _, in_cage)| in_cage.0 == cage_entity)
+        .map(|(transform, radius, _)| (transform.translation.truncate(), radius.0))
+        .collect();
+    let Some(position) = free_spawn_position(&mut rng, cage, cage_transform, &others) else {
+        return;
+    };
+
+    let power_up = PowerUp::ALL[rng.gen_range(0..PowerUp::ALL.len())];
+    commands.spawn((
+        MaterialMesh2dBundle {
+            mesh: meshes
+                .add(Circle {
+                    radius: POWER_UP_RADIUS,
+                })
+                .into(),
+            material: materials.add(power_up.colour()),
+            transform: Transform::from_translation(position.extend(0.5)),
+            ..Default::default()
+        },
+        power_up,
+        InCage(cage_entity),
+    ));
+}
+
+/// Uses up any pickup a ball in the same cage is touching.
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+pub fn collect_power_ups(
+    power_up_query: Query<(Entity, &PowerUp, &Transform, &InCage)>,
+    ball_query: Query<(&Transform, &Radius, &InCage), With<Ball>>,
+    sleeping_query: Query<Entity, With<Sleeping>>,
+    mut effects: ResMut<ActiveEffects>,
+    mut commands: Commands,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut ball_assets: ResMut<BallAssets>,
+    mut rng: ResMut<SimRng>,
+    palette: Res<BallPalette>,
+    settings: Res<Settings>,
+) {
+    for (entity, &power_up, transform, in_cage) in &power_up_query {
+        let position = transform.translation.truncate();
+        let touched = ball_query
+            .iter()
+            .any(|(ball_transform, radius, ball_cage)| {
+                ball_cage == in_cage
+                    && ball_transform.translation.truncate().distance(position)
+                        < radius.0 + POWER_UP_RADIUS
+            });
+        if !touched {
+            continue;
+        }
+        commands.entity(entity).despawn();
+        info!("Picked up {power_up:?}");
+
+        let timed = match power_up {
+            // Applied along with the time scale.
+            PowerUp::SlowMotion => true,
+            PowerUp::AntiGravity => {
+                // Balls resting on the floor have to notice gravity pulling them up.
+                wake_all(&mut commands, &sleeping_query);
+                true
+            }
+            PowerUp::Multiball => {
+                for _ in 0..MULTIBALL_COUNT {
+                    spawn_ball(
+                        &mut commands,
+                        &mut materials,
+                        &mut ball_assets,
+                        &mut rng,
+                        &palette,
+                        &settings,
+                        in_cage.0,
+                        position,
+                    );
+                }
+                false
+            }
+        };
+        if timed {
+            // Picking up an effect that's already running starts it over.
+            effects.0.insert(
+                power_up,
+                Timer::from_seconds(POWER_UP_DURATION, TimerMode::Once),
+            );
+        }
+    }
+}
+
+/// Counts down the timed effects in real time, so slow motion doesn't make itself last longer,
+/// and undoes each one when it runs out.
+pub fn update_effects(
+    mut effects: ResMut<ActiveEffects>,
+    sleeping_query: Query<Entity, With<Sleeping>>,
+    mut commands: Commands,
+    mut reset_events: EventReader<ResetEvent>,
+    real_time: Res<Time<Real>>,
+) {
+    // A reset ends every effect straight away.
+    let reset = !reset_events.is_empty();
+    reset_events.clear();
+    let mut ended = Vec::new();
+    for (&power_up, timer) in &mut effects.0 {
+        timer.tick(real_time.delta());
+        if reset || timer.finished() {
+            ended.push(power_up);
+        }
+    }
+    for power_up in ended {
+        effects.0.remove(&power_up);
+        info!("{power_up:?} wore off");
+        match power_up {
+            PowerUp::AntiGravity => wake_all(&mut commands, &sleeping_query),
+            PowerUp::SlowMotion | PowerUp::Multiball => {}
+        }
+    }
+}
+
+/// Clears away the pickups when the balls are reset.
+pub fn despawn_power_ups_on_reset(
+    mut reset_events: EventReader<ResetEvent>,
+    power_up_query: Query<Entity, With<PowerUp>>,
+    mut commands: Commands,
+) {
+    if reset_events.is_empty() {
+        return;
+    }
+    reset_events.clear();
+    for entity in &power_up_query {
+        commands.entity(entity).despawn();
+    }
+}
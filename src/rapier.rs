@@ -0,0 +1,223 @@
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::{
+    ActiveEvents, Ccd, CoefficientCombineRule, Collider, ColliderMassProperties, CollisionEvent,
+    CollisionGroups, Friction, Group, NoUserData, RapierConfiguration, RapierContext,
+    RapierPhysicsPlugin, Restitution, RigidBody, Velocity as RapierVelocity,
+};
+
+use crate::{
+    cage::Cage,
+    kind::BallKind,
+    menu::AppState,
+    settings::Settings,
+    time_control::{physics_running, PhysicsPaused, RewindBuffer},
+    Acceleration, Ball, CageCollisionEvent, OtherCollisionEvent, Velocity,
+};
+
+const PIXELS_PER_METER: f32 = 100.0;
+// Straight edges making up the walls of round cages.
+const CURVED_WALL_SEGMENTS: u32 = 64;
+const BALL_GROUP: Group = Group::GROUP_1;
+const WALL_GROUP: Group = Group::GROUP_2;
+
+/// Hands the balls over to Rapier: every ball becomes a dynamic rigid body and every cage a fixed
+/// one, with its wall as the collider.
+///
+/// The accelerations from gravity and the other forces, the sounds, scoring, merging and splitting
+/// all keep working, since the velocities and collision events are copied back. Cage gaps,
+/// spinning and dragged cages, nested cages, obstacles, pegs, portals, sticky balls and single
+/// stepping aren't supported.
+pub struct RapierBackendPlugin;
+
+impl Plugin for RapierBackendPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins(
+            RapierPhysicsPlugin::<NoUserData>::pixels_per_meter(PIXELS_PER_METER)
+                .in_fixed_schedule(),
+        )
+        .add_systems(Startup, disable_rapier_gravity)
+        .add_systems(
+            Update,
+            (
+                add_ball_bodies,
+                update_ball_restitution,
+                update_cage_colliders,
+                pause_rapier,
+            ),
+        );
+    }
+}
+
+/// Gravity is one of the accelerations applied by [`apply_accelerations`], along with the rest.
+fn disable_rapier_gravity(mut config: ResMut<RapierConfiguration>) {
+    config.gravity = Vec2::ZERO;
+}
+
+/// Keeps Rapier still whenever the built-in physics would be.
+fn pause_rapier(
+    mut config: ResMut<RapierConfiguration>,
+    state: Res<State<AppState>>,
+    paused: Res<PhysicsPaused>,
+    rewind_buffer: Res<RewindBuffer>,
+) {
+    let active = *state.get() == AppState::Running && physics_running(paused, rewind_buffer);
+    if config.physics_pipeline_active != active {
+        config.physics_pipeline_active = active;
+    }
+}
+
+fn add_ball_bodies(
+    ball_query: Query<(Entity, &BallKind), Added<Ball>>,
+    mut commands: Commands,
+    settings: Res<Settings>,
+) {
+    for (entity, kind) in &ball_query {
+        // Ghosts only collide with walls.
+        let filter = if kind.collides_with_balls() {
+            Group::ALL
+        } else {
+            WALL_GROUP
+        };
+        commands.entity(entity).insert((
+            RigidBody::Dynamic,
+            // The transform scales this up to the ball's radius.
+            Collider::ball(0.5),
+            ColliderMassProperties::Density(kind.mass_scale()),
+            ball_restitution(*kind, &settings),
+            Friction::coefficient(0.0),
+            CollisionGroups::new(BALL_GROUP, filter),
+            RapierVelocity::zero(),
+            ActiveEvents::COLLISION_EVENTS,
+            Ccd::enabled(),
+        ));
+    }
+}
+
+/// Like the built-in physics, a ball bounces off the walls with its own restitution. The walls
+/// have none of their own, and the `Max` rule keeps Rapier from averaging the ball's with it.
+fn ball_restitution(kind: BallKind, settings: &Settings) -> Restitution {
+    Restitution {
+        coefficient: kind.restitution(settings.restitution),
+        combine_rule: CoefficientCombineRule::Max,
+    }
+}
+
+/// Passes changes to [`Settings::restitution`] on to the balls already in Rapier.
+fn update_ball_restitution(
+    mut ball_query: Query<(&BallKind, &mut Restitution), With<Ball>>,
+    settings: Res<Settings>,
+) {
+    if !settings.is_changed() {
+        return;
+    }
+    for (kind, mut restitution) in &mut ball_query {
+        let new = ball_restitution(*kind, &settings);
+        if restitution.coefficient != new.coefficient {
+            *restitution = new;
+        }
+    }
+}
+
+/// Rebuilds a cage's wall whenever it's resized or changes shape.
+fn update_cage_colliders(
+    cage_query: Query<(Entity, &Cage), Changed<Cage>>,
+    mut commands: Commands,
+) {
+    for (entity, cage) in &cage_query {
+        let outline = cage.inner_outline(CURVED_WALL_SEGMENTS);
+        let count = outline.len() as u32;
+        let edges = (0..count).map(|i| [i, (i + 1) % count]).collect();
+        commands.entity(entity).insert((
+            RigidBody::Fixed,
+            Collider::polyline(outline, Some(edges)),
+            CollisionGroups::new(WALL_GROUP, Group::ALL),
+        ));
+    }
+}
+
+/// Hands the balls' velocities to Rapier, sped up by the accelerations of this step.
+#[allow(clippy::type_complexity)]
+pub fn apply_accelerations(
+    mut ball_query: Query<(&Velocity, &mut Acceleration, &mut RapierVelocity), With<Ball>>,
+    time: Res<Time>,
+) {
+    for (velocity, mut acceleration, mut rapier_velocity) in &mut ball_query {
+        rapier_velocity.linvel = velocity.0 + acceleration.0 * time.delta_seconds();
+        acceleration.0 = Vec2::ZERO;
+    }
+}
+
+/// Turns the contacts Rapier started this step into the usual collision events. Runs before
+/// [`read_velocities`], so the impact speeds are from before the bounce.
+pub fn send_collision_events(
+    mut rapier_events: EventReader<CollisionEvent>,
+    rapier_context: Res<RapierContext>,
+    ball_query: Query<&Velocity, With<Ball>>,
+    cage_query: Query<(), With<Cage>>,
+    mut wall_collision_events: EventWriter<CageCollisionEvent>,
+    mut ball_collision_events: EventWriter<OtherCollisionEvent>,
+) {
+    for event in rapier_events.read() {
+        let &CollisionEvent::Started(first, second, _) = event else {
+            continue;
+        };
+        let Some(pair) = rapier_context.contact_pair(first, second) else {
+            continue;
+        };
+        let Some(manifold) = pair.manifolds().next() else {
+            continue;
+        };
+        let Some(point) = manifold.solver_contact(0).map(|contact| contact.point()) else {
+            continue;
+        };
+        // Points from the pair's first collider towards its second.
+        let (first, second) = (pair.collider1(), pair.collider2());
+        let normal = manifold.normal();
+
+        match (ball_query.get(first), ball_query.get(second)) {
+            (Ok(first_velocity), Ok(second_velocity)) => {
+                let impact_speed = (first_velocity.0 - second_velocity.0).dot(normal).max(0.0);
+                ball_collision_events.send_batch([
+                    OtherCollisionEvent {
+                        self_entity: first,
+                        other_entity: second,
+                        impact_speed,
+                        point,
+                        normal: -normal,
+                    },
+                    OtherCollisionEvent {
+                        self_entity: second,
+                        other_entity: first,
+                        impact_speed,
+                        point,
+                        normal,
+                    },
+                ]);
+            }
+            (Ok(velocity), Err(_)) if cage_query.contains(second) => {
+                wall_collision_events.send(CageCollisionEvent {
+                    entity: first,
+                    impact_speed: velocity.0.dot(normal).max(0.0),
+                    point,
+                    normal: -normal,
+                });
+            }
+            (Err(_), Ok(velocity)) if cage_query.contains(first) => {
+                wall_collision_events.send(CageCollisionEvent {
+                    entity: second,
+                    impact_speed: (-velocity.0).dot(normal).max(0.0),
+                    point,
+                    normal,
+                });
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Copies Rapier's velocities back, for everything that reads them.
+pub fn read_velocities(mut ball_query: Query<(&mut Velocity, &RapierVelocity), With<Ball>>) {
+    for (mut velocity, rapier_velocity) in &mut ball_query {
+        velocity.0 = rapier_velocity.linvel;
+    }
+}
@@ -0,0 +1,132 @@
+use std::{
+    fs::{self, File},
+    io::BufWriter,
+    sync::{Arc, Mutex},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use bevy::{
+    prelude::*, render::view::screenshot::ScreenshotManager, tasks::AsyncComputeTaskPool,
+    window::PrimaryWindow,
+};
+use image::{
+    codecs::gif::{GifEncoder, Repeat},
+    imageops::{self, FilterType},
+    Delay, Frame, RgbaImage,
+};
+
+use crate::keybindings::{Action, Actions};
+
+const CLIP_DIR: &str = "clips";
+const CLIP_FRAME_RATE: u32 = 15;
+// Recording stops by itself after this long, to keep clips short enough to share.
+const CLIP_MAX_SECONDS: u32 = 10;
+// Frames are shrunk by this much, which keeps the GIFs small and quick to encode.
+const CLIP_DOWNSCALE: u32 = 2;
+// From 1 to 30, trading colour quality for encoding speed.
+const GIF_ENCODING_SPEED: i32 = 10;
+
+/// Whether a clip is being recorded, and the frames captured for it so far.
+#[derive(Resource, Default)]
+pub struct ClipRecorder {
+    recording: bool,
+    frame_timer: Timer,
+    /// Filled in by the screenshot callbacks, a frame or so after each capture is asked for.
+    frames: Arc<Mutex<Vec<RgbaImage>>>,
+}
+
+/// Starts recording a clip with F10, or stops and saves it. It's saved to [`CLIP_DIR`] as a
+/// looping GIF, encoded in the background. Only on desktop, since the web build has no files.
+pub fn toggle_recording(actions: Actions, mut recorder: ResMut<ClipRecorder>) {
+    let full =
+        recorder.frames.lock().unwrap().len() >= (CLIP_FRAME_RATE * CLIP_MAX_SECONDS) as usize;
+    let toggled = actions.just_pressed(Action::ToggleRecording);
+    if recorder.recording && (toggled || full) {
+        recorder.recording = false;
+        let frames = std::mem::take(&mut *recorder.frames.lock().unwrap());
+        save_clip(frames);
+    } else if !recorder.recording && toggled {
+        // There's nowhere to save a clip in the browser, and no clock to name it after.
+        if cfg!(target_arch = "wasm32") {
+            warn!("Clips can't be recorded in the browser");
+            return;
+        }
+        info!("Recording a clip, up to {CLIP_MAX_SECONDS} s long");
+        recorder.recording = true;
+        // Any capture that was still on its way when the last clip ended.
+        recorder.frames.lock().unwrap().clear();
+        recorder.frame_timer =
+            Timer::from_seconds(1.0 / CLIP_FRAME_RATE as f32, TimerMode::Repeating);
+    }
+}
+
+/// Asks for a capture of the window every `1 / CLIP_FRAME_RATE` seconds while recording.
+pub fn capture_clip_frames(
+    mut recorder: ResMut<ClipRecorder>,
+    window_query: Query<Entity, With<PrimaryWindow>>,
+    mut screenshot_manager: ResMut<ScreenshotManager>,
+    time: Res<Time<Real>>,
+) {
+    if !recorder.recording {
+        return;
+    }
+    if !recorder.frame_timer.tick(time.delta()).just_finished() {
+        return;
+    }
+    let Ok(window) = window_query.get_single() else {
+        return;
+    };
+    let frames = recorder.frames.clone();
+    // Fails when a screenshot of this frame has already been asked for, which can be skipped.
+    let _ = screenshot_manager.take_screenshot(window, move |image| {
+        let Ok(image) = image.try_into_dynamic() else {
+            return;
+        };
+        let image = image.to_rgba8();
+        let frame = imageops::resize(
+            &image,
+            image.width() / CLIP_DOWNSCALE,
+            image.height() / CLIP_DOWNSCALE,
+            FilterType::Triangle,
+        );
+        frames.lock().unwrap().push(frame);
+    });
+}
+
+fn save_clip(frames: Vec<RgbaImage>) {
+    if frames.is_empty() {
+        return;
+    }
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis();
+    let path = format!("{CLIP_DIR}/clip-{timestamp}.gif");
+    info!("Saving a {}-frame clip to {path}", frames.len());
+    AsyncComputeTaskPool::get()
+        .spawn(async move {
+            let result = fs::create_dir_all(CLIP_DIR)
+                .and_then(|()| File::create(&path))
+                .map_err(|error| error.to_string())
+                .and_then(|file| {
+                    let mut encoder =
+                        GifEncoder::new_with_speed(BufWriter::new(file), GIF_ENCODING_SPEED);
+                    encoder
+                        .set_repeat(Repeat::Infinite)
+                        .map_err(|error| error.to_string())?;
+                    let delay = Delay::from_numer_denom_ms(1000, CLIP_FRAME_RATE);
+                    encoder
+                        .encode_frames(
+                            frames
+                                .into_iter()
+                                .map(|frame| Frame::from_parts(frame, 0, 0, delay)),
+                        )
+                        .map_err(|error| error.to_string())
+                });
+            match result {
+                Ok(()) => info!("Saved the clip to {path}"),
+                Err(error) => warn!("Couldn't save {path}: {error}"),
+            }
+        })
+        .detach();
+}
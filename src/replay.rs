@@ -0,0 +1,266 @@
+use std::{collections::VecDeque, fs};
+
+use bevy::{prelude::*, sprite::MaterialMesh2dBundle};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    ball_assets::BallAssets, command_line_value, menu::AppState, Ball, BallColor, Radius,
+    ResetEvent,
+};
+
+const REPLAY_PATH: &str = "replay.ron";
+const REPLAY_ARG: &str = "--replay";
+// In seconds.
+const KEYFRAME_INTERVAL: f32 = 1.0 / 30.0;
+// Only the end of longer runs is kept, in seconds.
+const MAX_REPLAY_LENGTH: f32 = 120.0;
+
+/// A ball as it's shown in a replay, or sent to the clients of a shared cage.
+#[derive(Serialize, Deserialize)]
+pub struct BallKeyframe {
+    position: [f32; 2],
+    radius: f32,
+    colour: [f32; 4],
+}
+
+impl BallKeyframe {
+    pub fn new(transform: &Transform, radius: &Radius, colour: &BallColor) -> Self {
+        Self {
+            position: transform.translation.truncate().to_array(),
+            radius: radius.0,
+            colour: colour.0.as_rgba_f32(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct Keyframe {
+    /// Seconds since the start of the run.
+    time: f32,
+    balls: Vec<BallKeyframe>,
+}
+
+/// A run as a series of snapshots of every ball, [`KEYFRAME_INTERVAL`] apart. Played back as is,
+/// rather than simulated again, so it looks the same however the systems happen to be ordered.
+/// Only the last [`MAX_REPLAY_LENGTH`] seconds are kept.
+#[derive(Serialize, Deserialize, Default)]
+pub struct Replay {
+    keyframes: VecDeque<Keyframe>,
+}
+
+/// Records the current run, which is saved to [`REPLAY_PATH`] once it's over.
+#[derive(Resource, Default)]
+pub struct ReplayRecorder {
+    replay: Replay,
+    elapsed: f32,
+}
+
+/// The replay being watched, and how far into it playback is.
+#[derive(Resource)]
+pub struct Playback {
+    replay: Replay,
+    elapsed: f32,
+}
+
+/// Which replay file the Watch replay button plays. Set with `--replay <path>`, which also
+/// starts playing it straight away.
+#[derive(Resource)]
+pub struct ReplayFile(pub String);
+
+impl Default for ReplayFile {
+    fn default() -> Self {
+        Self(REPLAY_PATH.to_string())
+    }
+}
+
+/// Stands in for a recorded ball during playback.
+#[derive(Component)]
+pub struct ReplayBall;
+
+pub fn start_replay_from_args(
+    mut replay_file: ResMut<ReplayFile>,
+    mut next_state: ResMut<NextState<AppState>>,
+) {
+    if let Some(path) = command_line_value(REPLAY_ARG) {
+        replay_file.0 = path;
+        next_state.set(AppState::Replay);
+    }
+}
+
+#[allow(clippy::type_complexity)]
+pub fn record_keyframes(
+    mut recorder: ResMut<ReplayRecorder>,
+    mut reset_events: EventReader<ResetEvent>,
+    ball_query: Query<(&Transform, &Radius, &BallColor), With<Ball>>,
+    time: Res<Time>,
+) {
+    // A reset starts a new run.
+    if !reset_events.is_empty() {
+        reset_events.clear();
+        *recorder = ReplayRecorder::default();
+    }
+
+    let due = recorder.replay.keyframes.back().map_or(true, |last| {
+        recorder.elapsed - last.time >= KEYFRAME_INTERVAL
+    });
+    if due {
+        let keyframe_time = recorder.elapsed;
+        let keyframe = Keyframe {
+            time: keyframe_time,
+            balls: ball_query
+                .iter()
+                .map(|(transform, radius, colour)| BallKeyframe::new(transform, radius, colour))
+                .collect(),
+        };
+        let keyframes = &mut recorder.replay.keyframes;
+        keyframes.push_back(keyframe);
+        while keyframes
+            .front()
+            .is_some_and(|first| keyframe_time - first.time > MAX_REPLAY_LENGTH)
+        {
+            keyframes.pop_front();
+        }
+    }
+    recorder.elapsed += time.delta_seconds();
+}
+
+pub fn save_replay(recorder: Res<ReplayRecorder>) {
+    let result = ron::to_string(&recorder.replay)
+        .map_err(|error| error.to_string())
+        .and_then(|contents| fs::write(REPLAY_PATH, contents).map_err(|error| error.to_string()));
+    match result {
+        Ok(()) => info!("Saved the replay to {REPLAY_PATH}"),
+        Err(error) => warn!("Couldn't save {REPLAY_PATH}: {error}"),
+    }
+}
+
+/// Loads the replay file, going back to the title screen if it can't, and hides the balls of the
+/// run that was left off.
+pub fn start_playback(
+    replay_file: Res<ReplayFile>,
+    mut ball_query: Query<&mut Visibility, With<Ball>>,
+    mut next_state: ResMut<NextState<AppState>>,
+    mut commands: Commands,
+) {
+    let replay = fs::read_to_string(&replay_file.0)
+        .map_err(|error| error.to_string())
+        .and_then(|contents| ron::from_str::<Replay>(&contents).map_err(|error| error.to_string()));
+    let replay = match replay {
+        Ok(replay) => replay,
+        Err(error) => {
+            warn!("Couldn't load the replay from {}: {error}", replay_file.0);
+            next_state.set(AppState::Menu);
+            return;
+        }
+    };
+    for mut visibility in &mut ball_query {
+        *visibility = Visibility::Hidden;
+    }
+    commands.insert_resource(Playback {
+        replay,
+        elapsed: 0.0,
+    });
+}
+
+/// Shows the latest keyframe, and goes back to the title screen after the last one.
+#[allow(clippy::type_complexity)]
+pub fn play_replay(
+    mut playback: ResMut<Playback>,
+    mut replay_ball_query: Query<
+        (&mut Transform, &Handle<ColorMaterial>, &mut Visibility),
+        With<ReplayBall>,
+    >,
+    mut next_state: ResMut<NextState<AppState>>,
+    mut commands: Commands,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    ball_assets: Res<BallAssets>,
+    time: Res<Time>,
+) {
+    playback.elapsed += time.delta_seconds();
+    let keyframes = &playback.replay.keyframes;
+    // Long runs only kept their end, so playback starts from the first keyframe left.
+    let start = keyframes.front().map_or(0.0, |first| first.time);
+    let elapsed = start + playback.elapsed;
+    if keyframes.back().map_or(true, |last| elapsed > last.time) {
+        next_state.set(AppState::Menu);
+        return;
+    }
+    let index = keyframes
+        .partition_point(|keyframe| keyframe.time <= elapsed)
+        .saturating_sub(1);
+    show_balls(
+        &keyframes[index].balls,
+        &mut replay_ball_query,
+        &mut commands,
+        &mut materials,
+        &ball_assets,
+    );
+}
+
+/// Moves the stand-ins to `balls`. They're reused from one call to the next, and hidden when
+/// there are spare.
+#[allow(clippy::type_complexity)]
+pub fn show_balls(
+    balls: &[BallKeyframe],
+    replay_ball_query: &mut Query<
+        (&mut Transform, &Handle<ColorMaterial>, &mut Visibility),
+        With<ReplayBall>,
+    >,
+    commands: &mut Commands,
+    materials: &mut Assets<ColorMaterial>,
+    ball_assets: &BallAssets,
+) {
+    let mut balls = balls.iter();
+    for (mut transform, material, mut visibility) in replay_ball_query {
+        match balls.next() {
+            Some(ball) => {
+                *transform = replay_ball_transform(ball);
+                // Getting the material mutably uploads it again, so only when its colour changes.
+                let colour = Color::rgba_from_array(ball.colour);
+                if materials
+                    .get(material)
+                    .is_some_and(|material| material.color != colour)
+                {
+                    if let Some(material) = materials.get_mut(material) {
+                        material.color = colour;
+                    }
+                }
+                *visibility = Visibility::Inherited;
+            }
+            None => *visibility = Visibility::Hidden,
+        }
+    }
+    for ball in balls {
+        commands.spawn((
+            MaterialMesh2dBundle {
+                mesh: ball_assets.mesh.clone().into(),
+                material: materials.add(Color::rgba_from_array(ball.colour)),
+                transform: replay_ball_transform(ball),
+                ..Default::default()
+            },
+            ReplayBall,
+        ));
+    }
+}
+
+fn replay_ball_transform(ball: &BallKeyframe) -> Transform {
+    Transform {
+        translation: Vec2::from_array(ball.position).extend(1.0),
+        scale: Vec3::new(ball.radius * 2.0, ball.radius * 2.0, 1.0),
+        ..Default::default()
+    }
+}
+
+pub fn stop_playback(
+    replay_ball_query: Query<Entity, With<ReplayBall>>,
+    mut ball_query: Query<&mut Visibility, With<Ball>>,
+    mut commands: Commands,
+) {
+    for entity in &replay_ball_query {
+        commands.entity(entity).despawn();
+    }
+    for mut visibility in &mut ball_query {
+        *visibility = Visibility::Inherited;
+    }
+    commands.remove_resource::<Playback>();
+}
@@ -0,0 +1,50 @@
+use bevy::prelude::*;
+use rand::{rngs::StdRng, SeedableRng};
+
+use crate::command_line_value;
+
+const SEED_ARG: &str = "--seed";
+
+/// Where everything random in the simulation comes from, so a run can be repeated by starting
+/// it with the same seed. Randomness that only changes how things look or sound, like particles
+/// and screen shake, uses `rand::random` instead, so it can't throw a replay off.
+#[derive(Resource, Deref, DerefMut)]
+pub struct SimRng {
+    seed: u64,
+    #[deref]
+    rng: StdRng,
+}
+
+impl SimRng {
+    pub fn from_seed(seed: u64) -> Self {
+        Self {
+            seed,
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+}
+
+/// The seed passed with `--seed <number>`, or a random one.
+pub fn seed_from_args() -> u64 {
+    match command_line_value(SEED_ARG).map(|seed| seed.parse()) {
+        Some(Ok(seed)) => seed,
+        Some(Err(error)) => {
+            warn!("Ignoring the invalid {SEED_ARG}: {error}");
+            rand::random()
+        }
+        None => rand::random(),
+    }
+}
+
+/// Logs the seed, which isn't possible until logging has been set up.
+pub fn log_seed(rng: Res<SimRng>) {
+    info!(
+        "RNG seed: {} (repeat with {SEED_ARG} {})",
+        rng.seed(),
+        rng.seed()
+    );
+}
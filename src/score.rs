@@ -0,0 +1,145 @@
+use bevy::prelude::*;
+
+use crate::{
+    cage::{BallEscapedEvent, InCage},
+    menu::GameMode,
+    players::{Player, PlayerScores},
+    Ball, CageCollisionEvent, OtherCollisionEvent,
+};
+
+// Bounces off walls and obstacles.
+const WALL_POINTS: u64 = 1;
+const BALL_POINTS: u64 = 5;
+// Getting a ball out through a gap in its cage.
+const ESCAPE_POINTS: u64 = 25;
+// Slower contacts, like balls resting or rolling on the floor, don't score.
+const MIN_SCORING_IMPACT_SPEED: f32 = 50.0;
+// Seconds a ball has after one scoring hit to chain the next onto its combo.
+const COMBO_WINDOW: f32 = 0.5;
+const MAX_COMBO_MULTIPLIER: u32 = 8;
+
+/// Points scored since the last reset. Endless runs score the seconds survived instead.
+#[derive(Resource, Default)]
+pub struct Score(pub u64);
+
+/// A ball's run of scoring hits, each following the last within [`COMBO_WINDOW`].
+#[derive(Component, Default)]
+pub struct Combo {
+    hits: u32,
+    /// When the last scoring hit was, measured from startup.
+    last_hit: f32,
+}
+
+impl Combo {
+    /// Counts a scoring hit, returning the multiplier it's worth.
+    fn hit(&mut self, now: f32) -> u32 {
+        self.hits = if now - self.last_hit <= COMBO_WINDOW {
+            self.hits + 1
+        } else {
+            1
+        };
+        self.last_hit = now;
+        self.hits.min(MAX_COMBO_MULTIPLIER)
+    }
+}
+
+/// The most recent combo, for the HUD to flash.
+#[derive(Resource, Default)]
+pub struct LastCombo {
+    pub multiplier: u32,
+    /// Measured from startup.
+    pub at: f32,
+}
+
+pub fn add_combos(ball_query: Query<Entity, Added<Ball>>, mut commands: Commands) {
+    for entity in &ball_query {
+        commands.entity(entity).insert(Combo::default());
+    }
+}
+
+/// Scores every hard enough collision, hitting another ball being worth more than a wall.
+/// Hits chained onto a ball's combo are multiplied. In two-player mode, the points go to whoever
+/// owns the ball's cage.
+#[allow(clippy::too_many_arguments)]
+pub fn score_collisions(
+    mode: Res<GameMode>,
+    mut wall_collision_events: EventReader<CageCollisionEvent>,
+    mut ball_collision_events: EventReader<OtherCollisionEvent>,
+    mut combo_query: Query<&mut Combo>,
+    ball_query: Query<(), With<Ball>>,
+    in_cage_query: Query<&InCage>,
+    player_query: Query<&Player>,
+    mut score: ResMut<Score>,
+    mut player_scores: ResMut<PlayerScores>,
+    mut last_combo: ResMut<LastCombo>,
+    time: Res<Time>,
+) {
+    if *mode == GameMode::Endless {
+        wall_collision_events.clear();
+        ball_collision_events.clear();
+        return;
+    }
+    let now = time.elapsed_seconds();
+    let hits = wall_collision_events
+        .read()
+        .map(|event| (event.entity, event.impact_speed, WALL_POINTS))
+        .chain(ball_collision_events.read().filter_map(|event| {
+            if !ball_query.contains(event.other_entity) {
+                return Some((event.self_entity, event.impact_speed, WALL_POINTS));
+            }
+            // Both balls send an event for the same hit, which only scores once.
+            (event.self_entity < event.other_entity).then_some((
+                event.self_entity,
+                event.impact_speed,
+                BALL_POINTS,
+            ))
+        }));
+
+    let mut points = 0;
+    for (entity, impact_speed, base_points) in hits {
+        if impact_speed < MIN_SCORING_IMPACT_SPEED {
+            continue;
+        }
+        let multiplier = combo_query
+            .get_mut(entity)
+            .map_or(1, |mut combo| combo.hit(now));
+        if multiplier > 1 {
+            *last_combo = LastCombo {
+                multiplier,
+                at: now,
+            };
+        }
+        let points_scored = base_points * multiplier as u64;
+        points += points_scored;
+        let player = in_cage_query
+            .get(entity)
+            .and_then(|in_cage| player_query.get(in_cage.0));
+        if let Ok(player) = player {
+            player_scores.0[player.index()] += points_scored;
+        }
+    }
+    if points > 0 {
+        score.0 += points;
+    }
+}
+
+/// Scores every ball that gets out through a gap in its cage. In two-player mode, the points go to
+/// whoever owns the cage.
+pub fn score_escapes(
+    mode: Res<GameMode>,
+    mut escaped_events: EventReader<BallEscapedEvent>,
+    player_query: Query<&Player>,
+    mut score: ResMut<Score>,
+    mut player_scores: ResMut<PlayerScores>,
+) {
+    if *mode == GameMode::Endless {
+        escaped_events.clear();
+        return;
+    }
+    for event in escaped_events.read() {
+        score.0 += ESCAPE_POINTS;
+        if let Ok(player) = player_query.get(event.cage) {
+            player_scores.0[player.index()] += ESCAPE_POINTS;
+        }
+    }
+}
@@ -0,0 +1,37 @@
+use std::{
+    fs,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use bevy::{prelude::*, render::view::screenshot::ScreenshotManager, window::PrimaryWindow};
+
+use crate::keybindings::{Action, Actions};
+
+const SCREENSHOT_DIR: &str = "screenshots";
+
+/// Saves the next frame to a PNG in [`SCREENSHOT_DIR`] with F12, named after when it was taken.
+pub fn take_screenshot(
+    actions: Actions,
+    window_query: Query<Entity, With<PrimaryWindow>>,
+    mut screenshot_manager: ResMut<ScreenshotManager>,
+) {
+    if !actions.just_pressed(Action::Screenshot) {
+        return;
+    }
+    let Ok(window) = window_query.get_single() else {
+        return;
+    };
+    if let Err(error) = fs::create_dir_all(SCREENSHOT_DIR) {
+        warn!("Couldn't create {SCREENSHOT_DIR}: {error}");
+        return;
+    }
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis();
+    let path = format!("{SCREENSHOT_DIR}/screenshot-{timestamp}.png");
+    match screenshot_manager.save_screenshot_to_disk(window, &path) {
+        Ok(()) => info!("Saving a screenshot to {path}"),
+        Err(error) => warn!("Couldn't take a screenshot: {error}"),
+    }
+}
@@ -0,0 +1,280 @@
+use bevy::{
+    asset::{io::Reader, AssetLoader, AsyncReadExt, LoadContext},
+    prelude::*,
+    utils::BoxedFuture,
+};
+use rhai::{Array, CallFnOptions, Engine, Scope, AST};
+
+use crate::{
+    ball_assets::BallAssets,
+    cage::{Cage, NestedIn},
+    cage_at, command_line_value,
+    menu::AppState,
+    palette::BallPalette,
+    rng::SimRng,
+    settings::Settings,
+    spawn_ball, time_control, Acceleration, Ball, Radius, Sleeping, Velocity,
+};
+
+const SCRIPT_ARG: &str = "--script";
+// In seconds.
+const SCRIPT_SPAWN_INTERVAL: f32 = 1.0;
+
+/// Loads the script given with `--script` and runs its `force` and `spawn` functions.
+pub struct ScriptingPlugin;
+
+impl Plugin for ScriptingPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_asset::<Script>()
+            .init_asset_loader::<ScriptLoader>()
+            .init_resource::<Scripting>()
+            .add_systems(Startup, load_script)
+            .add_systems(
+                Update,
+                (
+                    watch_script,
+                    run_script_spawns.run_if(in_state(AppState::Running)),
+                ),
+            )
+            .add_systems(
+                FixedUpdate,
+                apply_script_forces
+                    .after(crate::apply_colour_charge)
+                    .before(crate::apply_drag)
+                    .run_if(in_state(AppState::Running).and_then(time_control::physics_running)),
+            );
+    }
+}
+
+/// A Rhai script that extends the simulation, loaded from a `.rhai` file and reloaded whenever
+/// the file changes. It can define either or both of:
+///
+/// - `fn force(position, velocity, radius, time)`, called for every awake ball each physics step,
+///   returning an extra acceleration for it.
+/// - `fn spawn(time, balls)`, called every [`SCRIPT_SPAWN_INTERVAL`] with how many balls there
+///   are, returning an array of positions to spawn new balls at.
+///
+/// Positions and velocities are `Vec2`s, made with `vec2(x, y)`.
+#[derive(Asset, TypePath)]
+pub struct Script {
+    ast: AST,
+}
+
+impl Script {
+    fn defines(&self, name: &str) -> bool {
+        self.ast
+            .iter_functions()
+            .any(|function| function.name == name)
+    }
+}
+
+#[derive(Default)]
+pub struct ScriptLoader;
+
+impl AssetLoader for ScriptLoader {
+    type Asset = Script;
+    type Settings = ();
+    type Error = Box<dyn std::error::Error + Send + Sync>;
+
+    fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader,
+        _settings: &'a (),
+        _load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<Script, Self::Error>> {
+        Box::pin(async move {
+            let mut source = String::new();
+            reader.read_to_string(&mut source).await?;
+            // Only parsing, so any engine will do. The functions are looked up when it's run.
+            let ast = Engine::new_raw().compile(source)?;
+            Ok(Script { ast })
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["rhai"]
+    }
+}
+
+/// The script given with `--script <path>`, and the engine it runs on.
+#[derive(Resource)]
+pub struct Scripting {
+    engine: Engine,
+    script: Option<Handle<Script>>,
+    spawn_timer: Timer,
+    /// Set once the script has failed, which stops it running until it's changed.
+    failed: bool,
+}
+
+impl Default for Scripting {
+    fn default() -> Self {
+        let mut engine = Engine::new();
+        engine
+            .register_type_with_name::<Vec2>("Vec2")
+            .register_fn("vec2", Vec2::new)
+            .register_get_set("x", |v: &mut Vec2| v.x, |v: &mut Vec2, x: f32| v.x = x)
+            .register_get_set("y", |v: &mut Vec2| v.y, |v: &mut Vec2, y: f32| v.y = y)
+            .register_fn("+", |a: Vec2, b: Vec2| a + b)
+            .register_fn("-", |a: Vec2, b: Vec2| a - b)
+            .register_fn("-", |v: Vec2| -v)
+            .register_fn("*", |v: Vec2, scale: f32| v * scale)
+            .register_fn("*", |scale: f32, v: Vec2| scale * v)
+            .register_fn("/", |v: Vec2, scale: f32| v / scale)
+            .register_fn("length", |v: &mut Vec2| v.length())
+            .register_fn("normalize", |v: &mut Vec2| v.normalize_or_zero())
+            .register_fn("perp", |v: &mut Vec2| v.perp())
+            .register_fn("dot", |a: Vec2, b: Vec2| a.dot(b))
+            .register_fn("to_string", |v: &mut Vec2| v.to_string());
+        Self {
+            engine,
+            script: None,
+            spawn_timer: Timer::from_seconds(SCRIPT_SPAWN_INTERVAL, TimerMode::Repeating),
+            failed: false,
+        }
+    }
+}
+
+impl Scripting {
+    fn report(&mut self, error: impl std::fmt::Display) {
+        if !self.failed {
+            warn!("The script failed, and won't run again until it's changed: {error}");
+            self.failed = true;
+        }
+    }
+}
+
+fn load_script(mut scripting: ResMut<Scripting>, asset_server: Res<AssetServer>) {
+    if let Some(path) = command_line_value(SCRIPT_ARG) {
+        info!("Running the script {path}");
+        scripting.script = Some(asset_server.load(path));
+    }
+}
+
+/// Logs the script being reloaded, and gives a changed script another go if the old one failed.
+fn watch_script(
+    mut scripting: ResMut<Scripting>,
+    mut script_events: EventReader<AssetEvent<Script>>,
+) {
+    for event in script_events.read() {
+        if let AssetEvent::Modified { .. } = event {
+            info!("Reloaded the script");
+            scripting.failed = false;
+        }
+    }
+}
+
+/// Adds the acceleration from the script's `force` function to every awake ball.
+#[allow(clippy::type_complexity)]
+fn apply_script_forces(
+    mut query: Query<(&Transform, &Velocity, &Radius, &mut Acceleration), Without<Sleeping>>,
+    mut scripting: ResMut<Scripting>,
+    scripts: Res<Assets<Script>>,
+    time: Res<Time>,
+) {
+    let Some(script) = scripting
+        .script
+        .as_ref()
+        .and_then(|handle| scripts.get(handle))
+    else {
+        return;
+    };
+    if scripting.failed || !script.defines("force") {
+        return;
+    }
+
+    let elapsed = time.elapsed_seconds();
+    let mut scope = Scope::new();
+    let mut error = None;
+    for (transform, velocity, radius, mut acceleration) in &mut query {
+        let arguments = (
+            transform.translation.truncate(),
+            velocity.0,
+            radius.0,
+            elapsed,
+        );
+        // Only the function is needed, not the top level of the script run again for every ball.
+        let force = scripting.engine.call_fn_with_options::<Vec2>(
+            CallFnOptions::new().eval_ast(false),
+            &mut scope,
+            &script.ast,
+            "force",
+            arguments,
+        );
+        match force {
+            Ok(force) => acceleration.0 += force,
+            Err(call_error) => {
+                error = Some(call_error);
+                break;
+            }
+        }
+    }
+    if let Some(error) = error {
+        scripting.report(error);
+    }
+}
+
+/// Spawns balls wherever the script's `spawn` function asks for them.
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+fn run_script_spawns(
+    ball_query: Query<(), With<Ball>>,
+    cage_query: Query<(Entity, &Cage, &Transform, Has<NestedIn>)>,
+    mut scripting: ResMut<Scripting>,
+    scripts: Res<Assets<Script>>,
+    mut commands: Commands,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut ball_assets: ResMut<BallAssets>,
+    mut rng: ResMut<SimRng>,
+    palette: Res<BallPalette>,
+    settings: Res<Settings>,
+    time: Res<Time>,
+) {
+    if !scripting.spawn_timer.tick(time.delta()).just_finished() {
+        return;
+    }
+    let Some(script) = scripting
+        .script
+        .as_ref()
+        .and_then(|handle| scripts.get(handle))
+    else {
+        return;
+    };
+    if scripting.failed || !script.defines("spawn") {
+        return;
+    }
+
+    let arguments = (
+        time.elapsed_seconds(),
+        ball_query.iter().count() as rhai::INT,
+    );
+    let positions =
+        scripting
+            .engine
+            .call_fn::<Array>(&mut Scope::new(), &script.ast, "spawn", arguments);
+    let positions = match positions {
+        Ok(positions) => positions,
+        Err(error) => {
+            scripting.report(error);
+            return;
+        }
+    };
+    for position in positions {
+        let Some(position) = position.try_cast::<Vec2>() else {
+            scripting.report("spawn has to return an array of vec2s");
+            return;
+        };
+        // Positions outside every cage are skipped.
+        let Some(cage) = cage_at(&cage_query, position) else {
+            continue;
+        };
+        spawn_ball(
+            &mut commands,
+            &mut materials,
+            &mut ball_assets,
+            &mut rng,
+            &palette,
+            &settings,
+            cage,
+            position,
+        );
+    }
+}
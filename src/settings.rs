@@ -0,0 +1,285 @@
+use std::f32::consts::TAU;
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::kind::BallKind;
+
+const WIND_STRENGTH: f32 = 0.0;
+const WIND_GUST_STRENGTH: f32 = 0.0;
+const WIND_GUST_INTERVAL: f32 = 2.0;
+const CHARGE_STRENGTH: f32 = 200_000.0;
+const RESTITUTION: f32 = 1.0;
+// In pixels per second.
+const BALL_SPEED: f32 = 200.0;
+const SPAWN_CHANCE: f32 = 0.1;
+const TIME_SCALE: f32 = 1.0;
+// In radians per second.
+const CAGE_ANGULAR_VELOCITY: f32 = 0.5;
+// In radians.
+const CAGE_GAP_WIDTH: f32 = 0.5;
+const PEG_SPACING: f32 = 24.0;
+const PEG_RADIUS: f32 = 2.5;
+// In seconds.
+const SPAWNER_INTERVAL: f32 = 0.5;
+const SPAWNER_JITTER: f32 = 0.2;
+const SPAWNER_MAX_ALIVE: usize = 20;
+const MAX_BALLS: usize = 500;
+const BURST_COUNT: usize = 24;
+// In pixels per second.
+const SPLIT_SPEED: f32 = 600.0;
+const SPLIT_COUNT: u32 = 2;
+const TRAIL_LENGTH: usize = 20;
+const COLOUR_SHIFT_RATE: f32 = 0.1;
+const GLOW_INTENSITY: f32 = 4.0;
+const SCREEN_SHAKE: f32 = 1.0;
+// The shortest wait, in seconds, that loaded settings can ask for between gusts or spawns.
+const MIN_INTERVAL: f32 = 0.01;
+// Past this, a split ball would turn into more pieces than can fit around it.
+const MAX_SPLIT_COUNT: u32 = 16;
+
+/// The arrangement of the pegs placed with L.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PegLattice {
+    Grid,
+    /// Every other row is shifted by half the spacing, like a Plinko board.
+    #[default]
+    Hex,
+}
+
+/// How the balls spawned with B are laid out.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BurstPattern {
+    /// A ring around the cage center, flying outwards.
+    #[default]
+    Ring,
+    /// Scattered over the cage, with random directions.
+    Random,
+}
+
+/// What's drawn behind the cages.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Background {
+    /// Just the clear colour.
+    #[default]
+    Plain,
+    /// A vertical gradient whose colours slowly shift.
+    Gradient,
+    /// Layers of drifting stars, which move at different speeds as the camera pans.
+    Starfield,
+}
+
+/// How balls are drawn.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BallAppearance {
+    /// A circle in the ball's colour.
+    #[default]
+    Flat,
+    /// A shaded ball texture tinted with the ball's colour, which turns as the ball spins.
+    Sprite,
+}
+
+/// How ball positions are advanced each fixed step.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Integrator {
+    /// Applies the acceleration to the velocity, then moves by the new velocity.
+    #[default]
+    Euler,
+    /// Velocity Verlet. Moves by the old velocity plus half the step's acceleration, which is
+    /// exact for constant forces like gravity.
+    Verlet,
+}
+
+/// Tunable parameters of the simulation.
+#[derive(Resource, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    /// Changed with the console's `integrator` command.
+    pub integrator: Integrator,
+    /// Fraction of the normal velocity kept on every bounce, from 1.0 (perfectly elastic) down
+    /// to 0.0 (balls stop dead against whatever they hit).
+    pub restitution: f32,
+    /// How fast new balls start out, in pixels per second.
+    pub ball_speed: f32,
+    /// The chance, from 0.0 to 1.0, that a ball hitting a cage wall spawns another ball.
+    pub spawn_chance: f32,
+    /// How fast the simulation runs compared to real time. Changed with - and = through
+    /// [`TIME_SCALES`](crate::time_control::TIME_SCALES).
+    pub time_scale: f32,
+    /// How fast polygonal cages spin, in radians per second. Positive is counter-clockwise.
+    pub cage_angular_velocity: f32,
+    /// Angular width of the gap opened in the cage wall with E, in radians.
+    pub cage_gap_width: f32,
+    /// Whether balls that make it out through a gap are despawned, or left to fall into whichever
+    /// cage is below.
+    pub despawn_escaped_balls: bool,
+    pub peg_lattice: PegLattice,
+    /// Distance between neighbouring pegs.
+    pub peg_spacing: f32,
+    pub peg_radius: f32,
+    /// Constant horizontal acceleration applied to every ball.
+    pub wind_strength: f32,
+    /// Maximum strength of a random gust on top of `wind_strength`. Zero disables gusts.
+    pub wind_gust_strength: f32,
+    /// Seconds between picking a new gust.
+    pub wind_gust_interval: f32,
+    /// Whether similarly coloured balls repel and differently coloured balls attract.
+    pub charge_enabled: bool,
+    /// Inverse-square strength of the colour charge force.
+    pub charge_strength: f32,
+    /// Average seconds between balls from spawners placed with S.
+    pub spawner_interval: f32,
+    /// The most a spawner's wait can randomly differ from `spawner_interval`, in seconds.
+    pub spawner_jitter: f32,
+    /// How many balls from one spawner can be around at once.
+    pub spawner_max_alive: usize,
+    /// Past this many balls, the oldest ones are despawned to make room.
+    pub max_balls: usize,
+    /// How many balls B spawns in every cage.
+    pub burst_count: usize,
+    pub burst_pattern: BurstPattern,
+    /// Whether touching balls of the same size merge into one bigger ball.
+    pub merge_enabled: bool,
+    /// Whether balls break apart when they hit something hard enough.
+    pub split_enabled: bool,
+    /// The relative speed, in pixels per second, above which a collision splits a ball.
+    pub split_speed: f32,
+    /// How many smaller balls a split ball breaks into.
+    pub split_count: u32,
+    /// Seconds new balls last before fading out and despawning, or `None` to keep them forever.
+    pub ball_lifetime: Option<f32>,
+    /// How many collisions new balls survive before being destroyed, or `None` for no limit.
+    pub ball_hit_points: Option<u32>,
+    /// Whether new balls stick to each other on contact, forming rigid clusters.
+    pub sticky_balls: bool,
+    /// The kind of newly spawned balls, or `None` to pick a random kind for every ball.
+    pub spawn_kind: Option<BallKind>,
+    /// Whether balls leave a fading line behind them.
+    pub trails_enabled: bool,
+    /// How many past positions, one per frame, a trail reaches back.
+    pub trail_length: usize,
+    /// How newly spawned balls are drawn. Individual balls can be changed afterwards.
+    pub ball_appearance: BallAppearance,
+    /// Whether collisions pull a ball's colour towards whatever it hit. Walls and obstacles
+    /// count as white.
+    pub colour_shift_enabled: bool,
+    /// How far, from 0.0 to 1.0, a single collision moves a ball's colour towards the other's.
+    pub colour_shift_rate: f32,
+    /// Whether balls glow, with an HDR camera and bloom.
+    pub glow_enabled: bool,
+    /// How many times brighter than their colour balls are drawn while glowing.
+    pub glow_intensity: f32,
+    /// Whether ball velocities, collision radii and contact normals are drawn over everything.
+    pub debug_overlay: bool,
+    /// How hard heavy impacts shake the screen, from 0.0 (not at all) up.
+    pub screen_shake: f32,
+    /// Whether to leave out screen shake, whatever `screen_shake` is set to.
+    pub reduced_motion: bool,
+    pub background: Background,
+}
+
+impl Settings {
+    /// Puts every number back in a range the simulation can cope with, for settings read from a
+    /// file that could have been edited by hand. Ones that aren't numbers at all, like NaN, go
+    /// back to their defaults.
+    pub fn sanitize(&mut self) {
+        let defaults = Settings::default();
+        let limit = |value: f32, default: f32, min: f32, max: f32| {
+            if value.is_finite() {
+                value.clamp(min, max)
+            } else {
+                default
+            }
+        };
+        let finite = |value: f32, default: f32| limit(value, default, f32::MIN, f32::MAX);
+        self.restitution = limit(self.restitution, defaults.restitution, 0.0, 1.0);
+        self.ball_speed = limit(self.ball_speed, defaults.ball_speed, 0.0, f32::MAX);
+        self.spawn_chance = limit(self.spawn_chance, defaults.spawn_chance, 0.0, 1.0);
+        self.time_scale = limit(self.time_scale, defaults.time_scale, 0.0, f32::MAX);
+        self.cage_angular_velocity =
+            finite(self.cage_angular_velocity, defaults.cage_angular_velocity);
+        self.cage_gap_width = limit(self.cage_gap_width, defaults.cage_gap_width, 0.0, TAU);
+        // Pegs closer than this would fill a cage with hundreds of thousands of them.
+        self.peg_spacing = limit(
+            self.peg_spacing,
+            defaults.peg_spacing,
+            PEG_SPACING / 4.0,
+            f32::MAX,
+        );
+        self.peg_radius = limit(self.peg_radius, defaults.peg_radius, 0.0, f32::MAX);
+        self.wind_strength = finite(self.wind_strength, defaults.wind_strength);
+        self.wind_gust_strength = finite(self.wind_gust_strength, defaults.wind_gust_strength);
+        self.wind_gust_interval = limit(
+            self.wind_gust_interval,
+            defaults.wind_gust_interval,
+            MIN_INTERVAL,
+            f32::MAX,
+        );
+        self.charge_strength = finite(self.charge_strength, defaults.charge_strength);
+        self.spawner_interval = limit(
+            self.spawner_interval,
+            defaults.spawner_interval,
+            MIN_INTERVAL,
+            f32::MAX,
+        );
+        self.spawner_jitter = limit(self.spawner_jitter, defaults.spawner_jitter, 0.0, f32::MAX);
+        self.split_speed = limit(self.split_speed, defaults.split_speed, 0.0, f32::MAX);
+        self.split_count = self.split_count.min(MAX_SPLIT_COUNT);
+        self.ball_lifetime = self
+            .ball_lifetime
+            .filter(|lifetime| lifetime.is_finite())
+            .map(|lifetime| lifetime.max(MIN_INTERVAL));
+        self.colour_shift_rate =
+            limit(self.colour_shift_rate, defaults.colour_shift_rate, 0.0, 1.0);
+        self.glow_intensity = limit(self.glow_intensity, defaults.glow_intensity, 0.0, f32::MAX);
+        self.screen_shake = limit(self.screen_shake, defaults.screen_shake, 0.0, f32::MAX);
+    }
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            integrator: Integrator::default(),
+            restitution: RESTITUTION,
+            ball_speed: BALL_SPEED,
+            spawn_chance: SPAWN_CHANCE,
+            time_scale: TIME_SCALE,
+            cage_angular_velocity: CAGE_ANGULAR_VELOCITY,
+            cage_gap_width: CAGE_GAP_WIDTH,
+            despawn_escaped_balls: true,
+            peg_lattice: PegLattice::default(),
+            peg_spacing: PEG_SPACING,
+            peg_radius: PEG_RADIUS,
+            wind_strength: WIND_STRENGTH,
+            wind_gust_strength: WIND_GUST_STRENGTH,
+            wind_gust_interval: WIND_GUST_INTERVAL,
+            charge_enabled: false,
+            charge_strength: CHARGE_STRENGTH,
+            spawner_interval: SPAWNER_INTERVAL,
+            spawner_jitter: SPAWNER_JITTER,
+            spawner_max_alive: SPAWNER_MAX_ALIVE,
+            max_balls: MAX_BALLS,
+            burst_count: BURST_COUNT,
+            burst_pattern: BurstPattern::default(),
+            merge_enabled: false,
+            split_enabled: false,
+            split_speed: SPLIT_SPEED,
+            split_count: SPLIT_COUNT,
+            ball_lifetime: None,
+            ball_hit_points: None,
+            sticky_balls: false,
+            spawn_kind: Some(BallKind::Normal),
+            trails_enabled: false,
+            trail_length: TRAIL_LENGTH,
+            ball_appearance: BallAppearance::default(),
+            colour_shift_enabled: false,
+            colour_shift_rate: COLOUR_SHIFT_RATE,
+            glow_enabled: false,
+            glow_intensity: GLOW_INTENSITY,
+            debug_overlay: false,
+            screen_shake: SCREEN_SHAKE,
+            reduced_motion: false,
+            background: Background::default(),
+        }
+    }
+}
@@ -0,0 +1,107 @@
+use std::f32::consts::TAU;
+
+use bevy::prelude::*;
+
+use crate::{
+    keybindings::{Action, Actions},
+    kind::BallKind,
+    settings::Settings,
+    CageCollisionEvent, OtherCollisionEvent, Radius, BALL_RADIUS,
+};
+
+// Impacts with a smaller impulse than this don't shake the screen. Impulses are the impact
+// speed times the ball's mass, with a normal ball of the usual size weighing 1.
+const SHAKE_IMPULSE_THRESHOLD: f32 = 500.0;
+// How far the camera is thrown, in pixels, for each unit of impulse past the threshold.
+const SHAKE_PER_IMPULSE: f32 = 0.02;
+// In pixels, at an intensity of 1.
+const MAX_SHAKE: f32 = 10.0;
+// How quickly shaking dies down. It halves about every `ln 2 / SHAKE_DECAY_RATE` seconds.
+const SHAKE_DECAY_RATE: f32 = 12.0;
+// Shakes weaker than this many pixels are stopped.
+const MIN_SHAKE: f32 = 0.1;
+
+/// How hard the camera is shaking, and how far it's been thrown off this frame.
+#[derive(Resource, Default)]
+pub struct ScreenShake {
+    /// In pixels.
+    strength: f32,
+    offset: Vec2,
+}
+
+/// Turns reduced motion, which leaves out screen shake, on and off with F4.
+pub fn toggle_reduced_motion(actions: Actions, mut settings: ResMut<Settings>) {
+    if actions.just_pressed(Action::ToggleReducedMotion) {
+        settings.reduced_motion = !settings.reduced_motion;
+    }
+}
+
+/// Shakes the screen for this frame's hardest impact, if it was hard enough.
+pub fn add_screen_shake(
+    mut wall_collision_events: EventReader<CageCollisionEvent>,
+    mut ball_collision_events: EventReader<OtherCollisionEvent>,
+    ball_query: Query<(&Radius, &BallKind)>,
+    mut shake: ResMut<ScreenShake>,
+    settings: Res<Settings>,
+) {
+    if settings.reduced_motion || settings.screen_shake == 0.0 {
+        wall_collision_events.clear();
+        ball_collision_events.clear();
+        return;
+    }
+    let impacts = wall_collision_events
+        .read()
+        .map(|event| (event.entity, event.impact_speed))
+        .chain(
+            ball_collision_events
+                .read()
+                .map(|event| (event.self_entity, event.impact_speed)),
+        );
+    let impulse = impacts
+        .filter_map(|(entity, impact_speed)| {
+            let (radius, kind) = ball_query.get(entity).ok()?;
+            Some(impact_speed * (radius.0 / BALL_RADIUS).powi(2) * kind.mass_scale())
+        })
+        .fold(0.0, f32::max);
+    if impulse <= SHAKE_IMPULSE_THRESHOLD {
+        return;
+    }
+    let strength = ((impulse - SHAKE_IMPULSE_THRESHOLD) * SHAKE_PER_IMPULSE).min(MAX_SHAKE)
+        * settings.screen_shake;
+    shake.strength = shake.strength.max(strength);
+}
+
+/// Takes back last frame's shake, so everything else sees the camera where it really is. Runs
+/// in `PreUpdate`.
+pub fn unshake_camera(
+    mut shake: ResMut<ScreenShake>,
+    mut camera_query: Query<&mut Transform, With<Camera>>,
+) {
+    if shake.offset == Vec2::ZERO {
+        return;
+    }
+    for mut transform in &mut camera_query {
+        transform.translation -= shake.offset.extend(0.0);
+    }
+    shake.offset = Vec2::ZERO;
+}
+
+/// Throws the camera in a random direction by however hard it's shaking, which dies down over
+/// time. Runs in `PostUpdate`, before the camera's transform is propagated.
+pub fn shake_camera(
+    mut shake: ResMut<ScreenShake>,
+    mut camera_query: Query<&mut Transform, With<Camera>>,
+    settings: Res<Settings>,
+    time: Res<Time<Real>>,
+) {
+    shake.strength *= (-SHAKE_DECAY_RATE * time.delta_seconds()).exp();
+    if shake.strength < MIN_SHAKE || settings.reduced_motion {
+        shake.strength = 0.0;
+        return;
+    }
+    let direction = Vec2::from_angle(rand::random::<f32>() * TAU);
+    shake.offset = direction * shake.strength;
+    for mut transform in &mut camera_query {
+        transform.translation += shake.offset.extend(0.0);
+    }
+}
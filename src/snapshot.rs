@@ -0,0 +1,124 @@
+use std::fs;
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    ball_assets::BallAssets,
+    cage::{Cage, NestedIn},
+    cage_at,
+    keybindings::{Action, Actions},
+    rng::SimRng,
+    settings::Settings,
+    spawn_sized_ball,
+    time_control::RewindBuffer,
+    Ball, BallColor, Radius, Velocity,
+};
+
+const SNAPSHOT_PATH: &str = "snapshot.ron";
+
+#[derive(Serialize, Deserialize)]
+struct BallSnapshot {
+    position: [f32; 2],
+    velocity: [f32; 2],
+    radius: f32,
+    colour: [f32; 4],
+}
+
+/// Every ball, and the settings they were moving under, saved with F5 and loaded with F9.
+#[derive(Serialize, Deserialize)]
+struct Snapshot {
+    settings: Settings,
+    balls: Vec<BallSnapshot>,
+}
+
+#[allow(clippy::type_complexity)]
+pub fn save_snapshot(
+    actions: Actions,
+    ball_query: Query<(&Transform, &Velocity, &Radius, &BallColor), With<Ball>>,
+    settings: Res<Settings>,
+) {
+    if !actions.just_pressed(Action::SaveSnapshot) {
+        return;
+    }
+    let snapshot = Snapshot {
+        settings: settings.clone(),
+        balls: ball_query
+            .iter()
+            .map(|(transform, velocity, radius, colour)| BallSnapshot {
+                position: transform.translation.truncate().to_array(),
+                velocity: velocity.0.to_array(),
+                radius: radius.0,
+                colour: colour.0.as_rgba_f32(),
+            })
+            .collect(),
+    };
+    let result = ron::ser::to_string_pretty(&snapshot, Default::default())
+        .map_err(|error| error.to_string())
+        .and_then(|contents| fs::write(SNAPSHOT_PATH, contents).map_err(|error| error.to_string()));
+    match result {
+        Ok(()) => info!("Saved {} balls to {SNAPSHOT_PATH}", snapshot.balls.len()),
+        Err(error) => warn!("Couldn't save {SNAPSHOT_PATH}: {error}"),
+    }
+}
+
+/// Clears away every ball and puts the saved ones back, each in whichever cage it's over.
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+pub fn load_snapshot(
+    actions: Actions,
+    ball_query: Query<Entity, With<Ball>>,
+    cage_query: Query<(Entity, &Cage, &Transform, Has<NestedIn>)>,
+    mut rewind_buffer: ResMut<RewindBuffer>,
+    mut commands: Commands,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut ball_assets: ResMut<BallAssets>,
+    mut rng: ResMut<SimRng>,
+    mut settings: ResMut<Settings>,
+) {
+    if !actions.just_pressed(Action::LoadSnapshot) {
+        return;
+    }
+    let snapshot = fs::read_to_string(SNAPSHOT_PATH)
+        .map_err(|error| error.to_string())
+        .and_then(|contents| {
+            ron::from_str::<Snapshot>(&contents).map_err(|error| error.to_string())
+        });
+    let snapshot = match snapshot {
+        Ok(snapshot) => snapshot,
+        Err(error) => {
+            warn!("Couldn't load {SNAPSHOT_PATH}: {error}");
+            return;
+        }
+    };
+
+    for entity in &ball_query {
+        commands.entity(entity).despawn();
+    }
+    // The recorded steps are of balls that are gone now.
+    rewind_buffer.clear();
+    *settings = snapshot.settings;
+    settings.sanitize();
+
+    // Balls that had escaped go back into the first cage.
+    let Some((fallback, ..)) = cage_query.iter().next() else {
+        return;
+    };
+    for ball in &snapshot.balls {
+        let position = Vec2::from_array(ball.position);
+        let entity = spawn_sized_ball(
+            &mut commands,
+            &mut materials,
+            &mut ball_assets,
+            &mut rng,
+            cage_at(&cage_query, position).unwrap_or(fallback),
+            position,
+            Color::rgba_from_array(ball.colour),
+            ball.radius,
+            settings.ball_speed,
+        );
+        commands
+            .entity(entity)
+            .insert(Velocity(Vec2::from_array(ball.velocity)));
+    }
+    info!("Loaded {} balls from {SNAPSHOT_PATH}", snapshot.balls.len());
+}
@@ -0,0 +1,143 @@
+use bevy::{prelude::*, sprite::MaterialMesh2dBundle, utils::HashMap, window::PrimaryWindow};
+use rand::Rng;
+
+use crate::{
+    ball_assets::BallAssets,
+    cage::{Cage, InCage, NestedIn},
+    cage_at, cursor_world_position,
+    keybindings::{Action, Actions},
+    palette::BallPalette,
+    rng::SimRng,
+    settings::Settings,
+    spawn_ball,
+};
+
+const SPAWNER_COLOR: Color = Color::rgb(0.3, 0.8, 1.0);
+const SPAWNER_RADIUS: f32 = 4.0;
+// Never fire faster than this, however much jitter there is.
+const SPAWNER_MIN_INTERVAL: f32 = 0.05;
+
+/// Emits balls into the cage it was placed in, from its own position.
+#[derive(Component)]
+pub struct BallSpawner {
+    /// Average seconds between balls.
+    pub interval: f32,
+    /// The most each wait can randomly differ from `interval`, in seconds.
+    pub jitter: f32,
+    /// The spawner pauses while this many of its balls are still around.
+    pub max_alive: usize,
+    timer: Timer,
+}
+
+impl BallSpawner {
+    pub fn new(interval: f32, jitter: f32, max_alive: usize, rng: &mut SimRng) -> Self {
+        let mut spawner = Self {
+            interval,
+            jitter,
+            max_alive,
+            timer: Timer::default(),
+        };
+        spawner.reset_timer(rng);
+        spawner
+    }
+
+    fn reset_timer(&mut self, rng: &mut SimRng) {
+        let wait = self.interval + rng.gen_range(-1.0..1.0) * self.jitter;
+        self.timer = Timer::from_seconds(wait.max(SPAWNER_MIN_INTERVAL), TimerMode::Once);
+    }
+}
+
+/// The spawner a ball came from.
+#[derive(Component)]
+pub struct SpawnedBy(pub Entity);
+
+/// Places a spawner at the cursor with S, if it's inside a cage.
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+pub fn place_spawner(
+    actions: Actions,
+    window_query: Query<&Window, With<PrimaryWindow>>,
+    camera_query: Query<(&Camera, &GlobalTransform)>,
+    cage_query: Query<(Entity, &Cage, &Transform, Has<NestedIn>)>,
+    mut commands: Commands,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut rng: ResMut<SimRng>,
+    settings: Res<Settings>,
+) {
+    if !actions.just_pressed(Action::PlaceSpawner) {
+        return;
+    }
+    let (Ok(window), Ok((camera, camera_transform))) =
+        (window_query.get_single(), camera_query.get_single())
+    else {
+        return;
+    };
+    let Some(position) = cursor_world_position(window, camera, camera_transform) else {
+        return;
+    };
+    let Some(cage) = cage_at(&cage_query, position) else {
+        return;
+    };
+
+    commands.spawn((
+        MaterialMesh2dBundle {
+            mesh: meshes
+                .add(Circle {
+                    radius: SPAWNER_RADIUS,
+                })
+                .into(),
+            material: materials.add(SPAWNER_COLOR),
+            transform: Transform::from_translation(position.extend(0.5)),
+            ..Default::default()
+        },
+        BallSpawner::new(
+            settings.spawner_interval,
+            settings.spawner_jitter,
+            settings.spawner_max_alive,
+            &mut rng,
+        ),
+        InCage(cage),
+    ));
+}
+
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+pub fn run_spawners(
+    mut spawner_query: Query<(Entity, &mut BallSpawner, &Transform, &InCage)>,
+    spawned_query: Query<&SpawnedBy>,
+    mut commands: Commands,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut ball_assets: ResMut<BallAssets>,
+    mut rng: ResMut<SimRng>,
+    palette: Res<BallPalette>,
+    settings: Res<Settings>,
+    time: Res<Time>,
+) {
+    let mut alive: HashMap<Entity, usize> = HashMap::new();
+    for spawned_by in &spawned_query {
+        *alive.entry(spawned_by.0).or_default() += 1;
+    }
+
+    for (entity, mut spawner, transform, in_cage) in &mut spawner_query {
+        spawner.timer.tick(time.delta());
+        if !spawner.timer.finished() {
+            continue;
+        }
+        // A finished timer stays finished, so the next ball comes as soon as there's room for it.
+        if alive.get(&entity).copied().unwrap_or(0) >= spawner.max_alive {
+            continue;
+        }
+
+        let ball = spawn_ball(
+            &mut commands,
+            &mut materials,
+            &mut ball_assets,
+            &mut rng,
+            &palette,
+            &settings,
+            in_cage.0,
+            transform.translation.truncate(),
+        );
+        commands.entity(ball).insert(SpawnedBy(entity));
+        spawner.reset_timer(&mut rng);
+    }
+}
@@ -0,0 +1,101 @@
+use std::{
+    fs::{File, OpenOptions},
+    io::Write,
+};
+
+use bevy::prelude::*;
+
+use crate::{
+    command_line_value, Ball, CageCollisionEvent, EnergyStats, OtherCollisionEvent, Velocity,
+};
+
+const STATS_ARG: &str = "--stats";
+// In seconds of simulated time, so pausing doesn't leave gaps of empty rows.
+const STATS_INTERVAL: f32 = 1.0;
+const STATS_HEADER: &str = "time,balls,collisions_per_second,average_speed,total_energy";
+
+/// Appends a row of statistics to a CSV file every [`STATS_INTERVAL`], for looking at long runs
+/// afterwards. Only records when started with `--stats <path>`.
+#[derive(Resource)]
+pub struct StatsLog {
+    file: Option<File>,
+    timer: Timer,
+    /// Between balls and against the cage walls, since the last row.
+    collisions: usize,
+}
+
+impl Default for StatsLog {
+    fn default() -> Self {
+        Self {
+            file: None,
+            timer: Timer::from_seconds(STATS_INTERVAL, TimerMode::Repeating),
+            collisions: 0,
+        }
+    }
+}
+
+pub fn open_stats_log(mut stats_log: ResMut<StatsLog>) {
+    let Some(path) = command_line_value(STATS_ARG) else {
+        return;
+    };
+    let file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .and_then(|mut file| {
+            // Appending to an earlier log keeps its header.
+            if file.metadata()?.len() == 0 {
+                writeln!(file, "{STATS_HEADER}")?;
+            }
+            Ok(file)
+        });
+    match file {
+        Ok(file) => {
+            info!("Logging statistics to {path}");
+            stats_log.file = Some(file);
+        }
+        Err(error) => warn!("Couldn't open {path} for statistics: {error}"),
+    }
+}
+
+pub fn write_stats(
+    mut stats_log: ResMut<StatsLog>,
+    mut wall_collision_events: EventReader<CageCollisionEvent>,
+    mut ball_collision_events: EventReader<OtherCollisionEvent>,
+    ball_query: Query<&Velocity, With<Ball>>,
+    energy: Res<EnergyStats>,
+    time: Res<Time>,
+) {
+    let collisions = wall_collision_events.read().count() + ball_collision_events.read().count();
+    if stats_log.file.is_none() {
+        return;
+    }
+    stats_log.collisions += collisions;
+    if !stats_log.timer.tick(time.delta()).just_finished() {
+        return;
+    }
+
+    let balls = ball_query.iter().count();
+    let average_speed = if balls == 0 {
+        0.0
+    } else {
+        ball_query
+            .iter()
+            .map(|velocity| velocity.length())
+            .sum::<f32>()
+            / balls as f32
+    };
+    let collisions_per_second = std::mem::take(&mut stats_log.collisions) as f32 / STATS_INTERVAL;
+    let row = format!(
+        "{:.1},{balls},{collisions_per_second:.0},{average_speed:.1},{:.0}",
+        time.elapsed_seconds(),
+        energy.total(),
+    );
+    let Some(file) = stats_log.file.as_mut() else {
+        return;
+    };
+    if let Err(error) = writeln!(file, "{row}") {
+        warn!("Couldn't write statistics, so stopped logging them: {error}");
+        stats_log.file = None;
+    }
+}
@@ -0,0 +1,84 @@
+use bevy::prelude::*;
+
+use crate::keybindings::{Action, Actions};
+
+/// The colours of everything around the balls: the background, the cages and the HUD.
+#[derive(Resource, Clone, Copy, Debug, PartialEq)]
+pub struct Theme {
+    pub name: &'static str,
+    /// Behind everything, and inside the cages.
+    pub background: Color,
+    pub cage: Color,
+    /// The text of the HUD and its overlays.
+    pub hud: Color,
+    /// The combo counter, and anything else on the HUD that should stand out.
+    pub hud_accent: Color,
+}
+
+impl Theme {
+    pub const DARK: Theme = Theme {
+        name: "Dark",
+        background: Color::rgb(0.1, 0.1, 0.1),
+        cage: Color::rgb(1.0, 1.0, 1.0),
+        hud: Color::rgb(0.8, 0.8, 0.8),
+        hud_accent: Color::rgb(1.0, 0.8, 0.2),
+    };
+
+    pub const LIGHT: Theme = Theme {
+        name: "Light",
+        background: Color::rgb(0.92, 0.92, 0.9),
+        cage: Color::rgb(0.2, 0.2, 0.25),
+        hud: Color::rgb(0.15, 0.15, 0.15),
+        hud_accent: Color::rgb(0.8, 0.4, 0.0),
+    };
+
+    pub const HIGH_CONTRAST: Theme = Theme {
+        name: "High contrast",
+        background: Color::rgb(0.0, 0.0, 0.0),
+        cage: Color::rgb(1.0, 1.0, 0.0),
+        hud: Color::rgb(1.0, 1.0, 1.0),
+        hud_accent: Color::rgb(0.0, 1.0, 1.0),
+    };
+
+    pub const ALL: [Theme; 3] = [Theme::DARK, Theme::LIGHT, Theme::HIGH_CONTRAST];
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Theme::DARK
+    }
+}
+
+/// Marks text drawn in the theme's [`hud`](Theme::hud) colour, so it follows theme changes.
+#[derive(Component)]
+pub struct HudText;
+
+/// Switches to the next built-in theme with F7.
+pub fn cycle_theme(actions: Actions, mut theme: ResMut<Theme>) {
+    if !actions.just_pressed(Action::CycleTheme) {
+        return;
+    }
+    let current = Theme::ALL
+        .iter()
+        .position(|builtin| builtin.name == theme.name)
+        .unwrap_or(0);
+    *theme = Theme::ALL[(current + 1) % Theme::ALL.len()];
+    info!("Switched to the {} theme", theme.name);
+}
+
+pub fn apply_background_theme(theme: Res<Theme>, mut clear_color: ResMut<ClearColor>) {
+    if theme.is_changed() {
+        clear_color.0 = theme.background;
+    }
+}
+
+pub fn apply_hud_theme(theme: Res<Theme>, mut text_query: Query<&mut Text, With<HudText>>) {
+    if !theme.is_changed() {
+        return;
+    }
+    for mut text in &mut text_query {
+        for section in &mut text.sections {
+            section.style.color = theme.hud;
+        }
+    }
+}
@@ -0,0 +1,190 @@
+use std::collections::VecDeque;
+
+use bevy::{app::FixedMain, ecs::system::SystemState, prelude::*};
+
+use crate::{
+    cage::wake_all,
+    keybindings::{Action, Actions},
+    powerup::{ActiveEffects, PowerUp, SLOW_MOTION_SPEED},
+    settings::Settings,
+    Ball, ResetEvent, Sleeping, Velocity,
+};
+
+// How far back the simulation can be rewound, in simulated seconds.
+const REWIND_SECONDS: f32 = 5.0;
+
+/// The speeds the simulation can be run at, compared to real time.
+pub const TIME_SCALES: [f32; 5] = [0.25, 0.5, 1.0, 2.0, 4.0];
+
+/// Whether the fixed step physics is frozen with P. Unlike the pause menu, this leaves the UI,
+/// sounds and controls running.
+#[derive(Resource, Default)]
+pub struct PhysicsPaused(pub bool);
+
+/// The physics also stands still while it's being rewound.
+pub fn physics_running(paused: Res<PhysicsPaused>, rewind_buffer: Res<RewindBuffer>) -> bool {
+    !paused.0 && !rewind_buffer.rewinding
+}
+
+pub fn toggle_physics_pause(actions: Actions, mut paused: ResMut<PhysicsPaused>) {
+    if actions.just_pressed(Action::PausePhysics) {
+        paused.0 = !paused.0;
+        info!("Physics {}", if paused.0 { "paused" } else { "resumed" });
+    }
+}
+
+/// Runs the fixed step schedules by hand, exactly once, when . is pressed while the physics is
+/// frozen.
+pub fn step_physics(world: &mut World) {
+    let mut actions = SystemState::<Actions>::new(world);
+    if !world.resource::<PhysicsPaused>().0 || !actions.get(world).just_pressed(Action::StepPhysics)
+    {
+        return;
+    }
+
+    // Like the fixed main loop does, the systems see the fixed clock as `Time` while they run.
+    let mut fixed_time = world.resource_mut::<Time<Fixed>>();
+    let timestep = fixed_time.timestep();
+    fixed_time.advance_by(timestep);
+    let fixed_time = fixed_time.as_generic();
+    *world.resource_mut::<Time>() = fixed_time;
+
+    world.resource_mut::<PhysicsPaused>().0 = false;
+    world.run_schedule(FixedMain);
+    world.resource_mut::<PhysicsPaused>().0 = true;
+
+    let virtual_time = world.resource::<Time<Virtual>>().as_generic();
+    *world.resource_mut::<Time>() = virtual_time;
+}
+
+/// Steps through [`TIME_SCALES`] with - and =.
+pub fn change_time_scale(actions: Actions, mut settings: ResMut<Settings>) {
+    let slower = actions.just_pressed(Action::SlowDown);
+    let faster = actions.just_pressed(Action::SpeedUp);
+    if slower == faster {
+        return;
+    }
+    // The closest scale to the current one, in case it was set to something in between.
+    let current = TIME_SCALES
+        .iter()
+        .enumerate()
+        .min_by(|(_, a), (_, b)| {
+            (*a - settings.time_scale)
+                .abs()
+                .total_cmp(&(*b - settings.time_scale).abs())
+        })
+        .map_or(0, |(index, _)| index);
+    let next = if faster {
+        (current + 1).min(TIME_SCALES.len() - 1)
+    } else {
+        current.saturating_sub(1)
+    };
+    settings.time_scale = TIME_SCALES[next];
+    info!("Time scale: {}x", settings.time_scale);
+}
+
+/// Runs virtual time, and with it the fixed steps, at the time scale, slowed down further while
+/// the slow motion power-up lasts.
+pub fn apply_time_scale(
+    settings: Res<Settings>,
+    effects: Res<ActiveEffects>,
+    mut virtual_time: ResMut<Time<Virtual>>,
+) {
+    if !settings.is_changed() && !effects.is_changed() {
+        return;
+    }
+    let mut speed = settings.time_scale;
+    if effects.is_active(PowerUp::SlowMotion) {
+        speed *= SLOW_MOTION_SPEED;
+    }
+    if virtual_time.relative_speed() != speed {
+        virtual_time.set_relative_speed(speed);
+    }
+}
+
+/// Where every ball was, and how it was moving, after one fixed step.
+struct BallState {
+    entity: Entity,
+    translation: Vec3,
+    velocity: Vec2,
+}
+
+/// The ball states of the last [`REWIND_SECONDS`] of fixed steps, oldest first.
+#[derive(Resource, Default)]
+pub struct RewindBuffer {
+    steps: VecDeque<Vec<BallState>>,
+    /// Whether Backspace is being held, with something left to rewind.
+    rewinding: bool,
+}
+
+impl RewindBuffer {
+    pub fn clear(&mut self) {
+        self.steps.clear();
+    }
+}
+
+/// Runs last in the fixed step, so the states are the ones the step ended with.
+pub fn record_ball_states(
+    mut rewind_buffer: ResMut<RewindBuffer>,
+    ball_query: Query<(Entity, &Transform, &Velocity), With<Ball>>,
+    time: Res<Time>,
+) {
+    let capacity = (REWIND_SECONDS / time.delta_seconds()).ceil() as usize;
+    while rewind_buffer.steps.len() >= capacity {
+        rewind_buffer.steps.pop_front();
+    }
+    rewind_buffer.steps.push_back(
+        ball_query
+            .iter()
+            .map(|(entity, transform, velocity)| BallState {
+                entity,
+                translation: transform.translation,
+                velocity: velocity.0,
+            })
+            .collect(),
+    );
+}
+
+/// While Backspace is held, goes back one recorded step every frame. Balls that didn't exist yet
+/// are despawned. Letting go carries on from wherever the rewind got to.
+pub fn rewind(
+    actions: Actions,
+    mut rewind_buffer: ResMut<RewindBuffer>,
+    mut ball_query: Query<(Entity, &mut Transform, &mut Velocity), With<Ball>>,
+    sleeping_query: Query<Entity, With<Sleeping>>,
+    mut reset_events: EventReader<ResetEvent>,
+    mut commands: Commands,
+) {
+    // There's no going back to before a reset.
+    if !reset_events.is_empty() {
+        reset_events.clear();
+        rewind_buffer.clear();
+    }
+
+    let holding = actions.pressed(Action::Rewind);
+    // The latest step is where the balls already are, so it's only worth going back while
+    // there's an earlier one.
+    let rewinding = holding && rewind_buffer.steps.len() > 1;
+    if rewind_buffer.rewinding != rewinding {
+        rewind_buffer.rewinding = rewinding;
+    }
+    if !rewinding {
+        return;
+    }
+
+    rewind_buffer.steps.pop_back();
+    let Some(states) = rewind_buffer.steps.back() else {
+        return;
+    };
+    for (entity, mut transform, mut velocity) in &mut ball_query {
+        match states.iter().find(|state| state.entity == entity) {
+            Some(state) => {
+                transform.translation = state.translation;
+                velocity.0 = state.velocity;
+            }
+            None => commands.entity(entity).despawn(),
+        }
+    }
+    // They might not be resting anymore once the physics carries on.
+    wake_all(&mut commands, &sleeping_query);
+}
@@ -0,0 +1,135 @@
+use std::{f32::consts::TAU, time::Duration};
+
+use bevy::{
+    audio::{Decodable, Source},
+    prelude::*,
+};
+
+const TONE_SAMPLE_RATE: u32 = 44_100;
+// In seconds.
+const TONE_DURATION: f32 = 0.4;
+// Seconds for a tone to die down to about a third of its starting volume.
+const TONE_DECAY: f32 = 0.08;
+// Seconds for the quickest note of a pad to swell and fade again. The others are a little slower.
+const PAD_SWELL_PERIOD: f64 = 6.0;
+
+/// A sine wave that dies away, synthesized rather than loaded from a file.
+#[derive(Asset, TypePath)]
+pub struct Tone {
+    /// In hertz.
+    pub frequency: f32,
+}
+
+impl Decodable for Tone {
+    type DecoderItem = f32;
+    type Decoder = ToneDecoder;
+
+    fn decoder(&self) -> Self::Decoder {
+        ToneDecoder {
+            frequency: self.frequency,
+            sample: 0,
+        }
+    }
+}
+
+pub struct ToneDecoder {
+    frequency: f32,
+    sample: u32,
+}
+
+impl Iterator for ToneDecoder {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        let time = self.sample as f32 / TONE_SAMPLE_RATE as f32;
+        if time >= TONE_DURATION {
+            return None;
+        }
+        self.sample += 1;
+        Some((TAU * self.frequency * time).sin() * (-time / TONE_DECAY).exp())
+    }
+}
+
+impl Source for ToneDecoder {
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        1
+    }
+
+    fn sample_rate(&self) -> u32 {
+        TONE_SAMPLE_RATE
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        Some(Duration::from_secs_f32(TONE_DURATION))
+    }
+}
+
+/// A chord of sine waves that slowly swells and fades and never ends, synthesized as background
+/// music.
+#[derive(Asset, TypePath)]
+pub struct Pad {
+    /// The notes of the chord, in hertz.
+    pub frequencies: Vec<f32>,
+}
+
+impl Decodable for Pad {
+    type DecoderItem = f32;
+    type Decoder = PadDecoder;
+
+    fn decoder(&self) -> Self::Decoder {
+        PadDecoder {
+            frequencies: self.frequencies.clone(),
+            sample: 0,
+        }
+    }
+}
+
+pub struct PadDecoder {
+    frequencies: Vec<f32>,
+    sample: u64,
+}
+
+impl Iterator for PadDecoder {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        // In f64, since the pad plays for as long as the app runs and the phase of an f32 would
+        // soon lose its precision.
+        let time = self.sample as f64 / TONE_SAMPLE_RATE as f64;
+        self.sample += 1;
+        let tau = std::f64::consts::TAU;
+        let chord: f64 = self
+            .frequencies
+            .iter()
+            .enumerate()
+            .map(|(index, &frequency)| {
+                // Each note swells at its own pace, so the chord keeps shifting.
+                let swell = 0.5 + 0.5 * (tau * time / (PAD_SWELL_PERIOD + index as f64)).sin();
+                (tau * frequency as f64 * time).sin() * swell
+            })
+            .sum();
+        Some((chord / self.frequencies.len().max(1) as f64) as f32)
+    }
+}
+
+impl Source for PadDecoder {
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        1
+    }
+
+    fn sample_rate(&self) -> u32 {
+        TONE_SAMPLE_RATE
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        None
+    }
+}
@@ -0,0 +1,190 @@
+use bevy::{input::touch::Touch, prelude::*, utils::HashMap};
+
+use crate::{
+    audio::{AudioSettings, CollisionSound},
+    ball_assets::BallAssets,
+    ball_at,
+    cage::{Cage, NestedIn},
+    cage_at,
+    camera::{self, CameraZoom},
+    palette::BallPalette,
+    pop_ball,
+    rng::SimRng,
+    settings::Settings,
+    spawn_ball, Ball, BallColor, Radius,
+};
+
+// How far a finger can wander, in logical pixels, and still count as holding still.
+const TOUCH_SLOP: f32 = 12.0;
+// How long a finger has to be held still to pop the ball under it, in seconds.
+const LONG_PRESS_TIME: f32 = 0.5;
+
+/// The fingers that could still turn out to be a tap or a long press, and when each touched
+/// down. A finger stops being one once it moves, is held long enough to pop, or is joined by
+/// another for a pinch.
+#[derive(Resource, Default)]
+pub struct TouchGestures {
+    pending: HashMap<u64, f32>,
+}
+
+fn held_still(touch: &Touch) -> bool {
+    touch.distance().length() <= TOUCH_SLOP
+}
+
+fn touch_world_position(
+    touch: &Touch,
+    camera_query: &Query<(&Camera, &GlobalTransform)>,
+) -> Option<Vec2> {
+    let (camera, camera_transform) = camera_query.get_single().ok()?;
+    camera.viewport_to_world_2d(camera_transform, touch.position())
+}
+
+/// Keeps [`TouchGestures`] up to date with the fingers on the screen.
+pub fn track_touches(
+    touches: Res<Touches>,
+    mut gestures: ResMut<TouchGestures>,
+    time: Res<Time<Real>>,
+) {
+    for touch in touches.iter_just_pressed() {
+        gestures.pending.insert(touch.id(), time.elapsed_seconds());
+    }
+    for touch in touches.iter_just_canceled() {
+        gestures.pending.remove(&touch.id());
+    }
+    if touches.iter().count() > 1 {
+        gestures.pending.clear();
+    }
+    for touch in touches.iter() {
+        if !held_still(touch) {
+            gestures.pending.remove(&touch.id());
+        }
+    }
+}
+
+/// Pops the ball under a finger that's been held still for [`LONG_PRESS_TIME`].
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+pub fn long_press_to_pop(
+    touches: Res<Touches>,
+    mut gestures: ResMut<TouchGestures>,
+    time: Res<Time<Real>>,
+    camera_query: Query<(&Camera, &GlobalTransform)>,
+    ball_query: Query<(Entity, &Transform, &Radius, &BallColor), With<Ball>>,
+    mut commands: Commands,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    sound: Res<CollisionSound>,
+    audio_settings: Res<AudioSettings>,
+) {
+    let now = time.elapsed_seconds();
+    for touch in touches.iter() {
+        let Some(&start) = gestures.pending.get(&touch.id()) else {
+            continue;
+        };
+        if now - start < LONG_PRESS_TIME {
+            continue;
+        }
+        // Even with no ball under it, a press this long isn't a tap.
+        gestures.pending.remove(&touch.id());
+        let Some(point) = touch_world_position(touch, &camera_query) else {
+            continue;
+        };
+        let balls = ball_query
+            .iter()
+            .map(|(entity, transform, radius, _)| (entity, transform, radius));
+        let Some(entity) = ball_at(balls, point) else {
+            continue;
+        };
+        let Ok((_, transform, _, colour)) = ball_query.get(entity) else {
+            continue;
+        };
+        pop_ball(
+            &mut commands,
+            &mut materials,
+            &mut meshes,
+            &sound,
+            &audio_settings,
+            entity,
+            transform.translation.truncate(),
+            colour.0,
+        );
+    }
+}
+
+/// Spawns a ball where a finger is lifted, if it was a quick tap rather than a drag, a long press
+/// or part of a pinch.
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+pub fn tap_to_spawn(
+    touches: Res<Touches>,
+    mut gestures: ResMut<TouchGestures>,
+    camera_query: Query<(&Camera, &GlobalTransform)>,
+    cage_query: Query<(Entity, &Cage, &Transform, Has<NestedIn>)>,
+    mut commands: Commands,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut ball_assets: ResMut<BallAssets>,
+    mut rng: ResMut<SimRng>,
+    palette: Res<BallPalette>,
+    settings: Res<Settings>,
+) {
+    for touch in touches.iter_just_released() {
+        if gestures.pending.remove(&touch.id()).is_none() || !held_still(touch) {
+            continue;
+        }
+        let Some(position) = touch_world_position(touch, &camera_query) else {
+            continue;
+        };
+        let Some(cage) = cage_at(&cage_query, position) else {
+            continue;
+        };
+        spawn_ball(
+            &mut commands,
+            &mut materials,
+            &mut ball_assets,
+            &mut rng,
+            &palette,
+            &settings,
+            cage,
+            position,
+        );
+    }
+}
+
+/// Zooms in and out as two fingers spread apart or pinch together, keeping the point between them
+/// where it is.
+#[allow(clippy::type_complexity)]
+pub fn pinch_to_zoom(
+    touches: Res<Touches>,
+    mut camera_query: Query<(
+        &Camera,
+        &GlobalTransform,
+        &mut Transform,
+        &mut OrthographicProjection,
+    )>,
+    mut camera_zoom: ResMut<CameraZoom>,
+) {
+    let mut fingers = touches.iter();
+    let (Some(first), Some(second), None) = (fingers.next(), fingers.next(), fingers.next()) else {
+        return;
+    };
+    let previous = first
+        .previous_position()
+        .distance(second.previous_position());
+    let current = first.position().distance(second.position());
+    if previous <= 0.0 || current <= 0.0 || previous == current {
+        return;
+    }
+    let Ok((camera, camera_global_transform, mut camera_transform, mut projection)) =
+        camera_query.get_single_mut()
+    else {
+        return;
+    };
+    let midpoint = (first.position() + second.position()) / 2.0;
+    let anchor = camera.viewport_to_world_2d(camera_global_transform, midpoint);
+    // Spreading the fingers apart zooms in.
+    camera::zoom_around(
+        &mut camera_zoom,
+        &mut camera_transform,
+        &mut projection,
+        previous / current,
+        anchor,
+    );
+}